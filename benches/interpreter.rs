@@ -0,0 +1,45 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! Benchmarks of the interpreter on representative programs: `cargo bench`.
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+use bfi::bench::run_counted;
+
+// `loops` stands in for an excerpt of mandelbrot.b, which is not vendored here.
+const PROGRAMS: &[(&str, &str)] = &[
+    ("hello", include_str!("programs/hello.b")),
+    ("sierpinski", include_str!("programs/sierpinski.b")),
+    ("loops", include_str!("programs/loops.b")),
+];
+
+fn interpret(c: &mut Criterion) {
+    let mut group = c.benchmark_group("interpret");
+    for &(name, code) in PROGRAMS {
+        // Reported as commands per second; a failing program fails the run.
+        let steps = run_counted(code, b"").unwrap().steps;
+        group.throughput(Throughput::Elements(steps));
+        group.bench_function(name, |b| b.iter(|| run_counted(code, b"").unwrap()));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, interpret);
+criterion_main!(benches);
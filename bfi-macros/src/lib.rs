@@ -0,0 +1,122 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! `bf!` macro embedding brainfuck programs into Rust code.
+//!
+//! ```ignore
+//! let out: &[u8] = bf!("++++++++[>++++++++<-]>+.");
+//! let sum: &[u8] = bf!(",>,<>[-<+>]<.", input = b"\x01\x02");
+//! ```
+//!
+//! A program is pure when it has no `,` or when `input` is given. Pure programs
+//! are executed at compile time and expand to a byte string literal. The other
+//! programs are embedded and executed on first use, reading from stdin; the
+//! expansion then depends on the `bfi` crate (package `bfi-rs`).
+
+extern crate proc_macro;
+
+use std::io::Cursor;
+
+use bfi::BFI;
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::{parse_macro_input, Ident, LitByteStr, LitStr, Token};
+
+/// Number of steps a pure program may take at compile time.
+const MAX_COMPILE_TIME_STEPS: u64 = 100_000_000;
+
+struct BfInput {
+    code: LitStr,
+    input: Option<LitByteStr>,
+}
+
+impl Parse for BfInput {
+    fn parse(stream: ParseStream) -> syn::Result<Self> {
+        let code = stream.parse()?;
+        let mut input = None;
+        while stream.parse::<Option<Token![,]>>()?.is_some() {
+            if stream.is_empty() {
+                break;
+            }
+            let key: Ident = stream.parse()?;
+            if key != "input" {
+                return Err(syn::Error::new(key.span(), "expected `input`"));
+            }
+            stream.parse::<Token![=]>()?;
+            input = Some(stream.parse()?);
+        }
+        Ok(Self { code, input })
+    }
+}
+
+fn run_at_compile_time(code: String, input: Vec<u8>) -> Result<Vec<u8>, String> {
+    let mut bfi = BFI::new(code);
+    let mut reader = Cursor::new(input);
+    let mut writer = Vec::new();
+    let mut steps = 0;
+    while bfi.step(&mut reader, &mut writer).map_err(|err| err.to_string())? {
+        steps += 1;
+        if steps >= MAX_COMPILE_TIME_STEPS {
+            return Err(format!("did not halt within {} steps", MAX_COMPILE_TIME_STEPS));
+        }
+    }
+    Ok(writer)
+}
+
+/// Executes a brainfuck program and evaluates to its output as `&'static [u8]`.
+#[proc_macro]
+pub fn bf(tokens: TokenStream) -> TokenStream {
+    let BfInput { code, input } = parse_macro_input!(tokens as BfInput);
+
+    // The interpreter tells the commands from comments and annotations.
+    let commands: String = BFI::new(code.value())
+        .commands()
+        .map(|(_, byte)| char::from(byte))
+        .collect();
+    if let Err(err) = BFI::new(commands.clone()).check_syntax() {
+        return syn::Error::new(code.span(), err).to_compile_error().into();
+    }
+
+    if input.is_some() || !commands.contains(',') {
+        let input = input.map(|input| input.value()).unwrap_or_default();
+        return match run_at_compile_time(commands, input) {
+            Ok(output) => {
+                let output = LitByteStr::new(&output, code.span());
+                quote!(&#output[..]).into()
+            }
+            Err(message) => syn::Error::new(code.span(), message).to_compile_error().into(),
+        };
+    }
+
+    let commands = LitStr::new(&commands, code.span());
+    quote!({
+        static OUTPUT: ::std::sync::OnceLock<::std::vec::Vec<u8>> = ::std::sync::OnceLock::new();
+        OUTPUT
+            .get_or_init(|| {
+                let mut output = ::std::vec::Vec::new();
+                ::bfi::BFI::new(::std::string::String::from(#commands))
+                    .interpret(&mut ::std::io::stdin(), &mut output)
+                    .expect("brainfuck program failed");
+                output
+            })
+            .as_slice()
+    })
+    .into()
+}
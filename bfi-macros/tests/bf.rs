@@ -0,0 +1,38 @@
+use bfi_macros::bf;
+
+#[test]
+fn test_pure_program() {
+    let out: &[u8] = bf!("++++++++[>++++++++<-]>+.+.");
+    assert_eq!(out, b"AB");
+
+    let out: &[u8] = bf!(
+        r#"
+        >+++++++++[<++++++++>-]<.>+++++++[<++++>-]<+.+++++++..+++.[-]>++++++++[<++++>-]
+        <.#>+++++++++++[<+++++>-]<.>++++++++[<+++>-]<.+++.------.--------.[-]>++++++++[
+        <++++>-]<+.[-]++++++++++.
+        "#
+    );
+    assert_eq!(out, b"Hello World!\n");
+}
+
+#[test]
+fn test_pure_program_with_input() {
+    let out: &[u8] = bf!(",>,<>[-<+>]<.", input = b"\x01\x02");
+    assert_eq!(out, [3]);
+}
+
+#[allow(dead_code)]
+fn impure_program() -> &'static [u8] {
+    bf!(",[.,]")
+}
+
+#[test]
+fn test_annotations() {
+    // Only the lines naming cells or asserting are skipped, as by the interpreter.
+    let out: &[u8] = bf!(";; cell 0 = counter.\n;; a comment runs +\n+.");
+    assert_eq!(out, [2]);
+
+    // Only spaces and tabs may come before the `;;`.
+    let out: &[u8] = bf!("\u{3000};; cell 0 = x.\n+.");
+    assert_eq!(out, [0, 1]);
+}
@@ -0,0 +1,31 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+fn main() {
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/bfi.proto");
+        let descriptors = protox::compile(["proto/bfi.proto"], ["proto"]).expect("proto/bfi.proto");
+        // The generated `connect` needs the prelude of edition 2021.
+        tonic_build::configure()
+            .build_transport(false)
+            .compile_fds(descriptors)
+            .expect("generating the gRPC service");
+    }
+}
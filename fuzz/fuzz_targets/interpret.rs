@@ -0,0 +1,40 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! Runs arbitrary programs over arbitrary input: `cargo +nightly fuzz run interpret`.
+
+#![no_main]
+
+use bfi::Limits;
+use libfuzzer_sys::fuzz_target;
+
+const LIMITS: Limits = Limits {
+    max_steps: Some(100_000),
+    max_cells: None,
+    max_output: Some(4096),
+};
+
+fuzz_target!(|data: &[u8]| {
+    // The first zero byte separates the program from its input.
+    let (code, input) = match data.iter().position(|&b| b == 0) {
+        Some(split) => (&data[..split], &data[split + 1..]),
+        None => (data, &[][..]),
+    };
+    let _ = bfi::fuzz_entry(code, input, LIMITS);
+});
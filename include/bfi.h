@@ -0,0 +1,86 @@
+#ifndef BFI_H
+#define BFI_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Status codes returned by the C API.
+typedef enum BFIStatus {
+  BFI_STATUS_OK = 0,
+  BFI_STATUS_HALTED = 1,
+  BFI_STATUS_NULL_POINTER = -1,
+  BFI_STATUS_IO = -2,
+  BFI_STATUS_MISSING_CLOSING_BRACKETS = -3,
+  BFI_STATUS_MISSING_OPENING_BRACKETS = -4,
+  BFI_STATUS_OUT_OF_MEMORY = -5,
+  BFI_STATUS_ARITHMETIC_OVERFLOW = -6,
+  BFI_STATUS_STEP_LIMIT_EXCEEDED = -7,
+  BFI_STATUS_OUTPUT_LIMIT_EXCEEDED = -8,
+  BFI_STATUS_MEMORY_LIMIT_EXCEEDED = -9,
+  BFI_STATUS_NESTING_TOO_DEEP = -10,
+  BFI_STATUS_INPUT_FORBIDDEN = -11,
+  BFI_STATUS_STRAY_CHARACTERS = -12,
+  BFI_STATUS_INVALID_ASSERTION = -13,
+  BFI_STATUS_ASSERTION_FAILED = -14,
+} BFIStatus;
+
+typedef struct BFI BFI;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Creates an interpreter with an empty program.
+//
+// The returned pointer must be released with `bfi_free`.
+struct BFI *bfi_new(void);
+
+// Loads `len` bytes of `code` as the program, resetting the memory and the pointer.
+//
+// On a syntax error, the previously loaded program and its state are kept.
+//
+// # Safety
+//
+// `bfi` must come from `bfi_new`, and `code` must point to `len` readable bytes.
+enum BFIStatus bfi_load(struct BFI *bfi, const char *code, size_t len);
+
+// Runs the loaded program from the beginning, reading from stdin and writing to stdout.
+//
+// stdout is flushed before returning.
+//
+// # Safety
+//
+// `bfi` must come from `bfi_new`.
+enum BFIStatus bfi_run(struct BFI *bfi);
+
+// Executes one command of the loaded program, skipping comments, reading from stdin and writing to stdout.
+//
+// Returns `BFI_STATUS_HALTED` once the end of the program has been reached.
+//
+// # Safety
+//
+// `bfi` must come from `bfi_new`.
+enum BFIStatus bfi_step(struct BFI *bfi);
+
+// Stores the value of the cell at `index` into `value`.
+//
+// # Safety
+//
+// `bfi` must come from `bfi_new`, and `value` must be writable.
+enum BFIStatus bfi_get_cell(const struct BFI *bfi, size_t index, int8_t *value);
+
+// Releases an interpreter created by `bfi_new`. Passing NULL is a no-op.
+//
+// # Safety
+//
+// `bfi` must come from `bfi_new` and must not be used afterwards.
+void bfi_free(struct BFI *bfi);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* BFI_H */
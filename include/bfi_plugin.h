@@ -0,0 +1,42 @@
+#ifndef BFI_PLUGIN_H
+#define BFI_PLUGIN_H
+
+// ABI of the dialect plugins loaded by `bfi run --plugin`.
+//
+// A plugin is a shared library exporting `bfi_plugin_entry`, which returns the
+// vtable of the ABI version it implements. bfi refuses other versions.
+
+#include <stddef.h>
+#include <stdint.h>
+
+#define BFI_PLUGIN_ABI_VERSION 1
+
+typedef struct BfiPluginV1 {
+  // Must be BFI_PLUGIN_ABI_VERSION.
+  uint32_t abi_version;
+  // Name of the dialect, NUL-terminated.
+  const char *name;
+  // Translates `len` bytes of `code` into standard brainfuck, written to `out`
+  // if at most `capacity` bytes long. Returns the length of the translation,
+  // or a negative value if `code` is invalid. May be NULL.
+  intptr_t (*translate)(const uint8_t *code, size_t len, uint8_t *out, size_t capacity);
+  // ASCII characters of the commands handled by `execute`, NUL-terminated.
+  // May be NULL.
+  const char *commands;
+  // Executes one of `commands` on the value of the current cell.
+  // Returns 0 on success, a negative value on failure. May be NULL.
+  int32_t (*execute)(uint8_t command, int8_t *cell);
+} BfiPluginV1;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Returns the vtable of the plugin. `host_abi_version` is the version of bfi.
+const BfiPluginV1 *bfi_plugin_entry(uint32_t host_abi_version);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* BFI_PLUGIN_H */
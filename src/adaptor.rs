@@ -0,0 +1,187 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! Adaptors running a program as an `io::Read`/`io::Write` transform.
+//!
+//! ```
+//! use std::io::{Read, Write};
+//!
+//! use bfi::adaptor::{BfReader, BfWriter};
+//! use bfi::BFI;
+//!
+//! // Shifts every byte by one until the end of input.
+//! let mut output = String::new();
+//! let mut reader = BfReader::new(BFI::new(",[+.,]".to_string()), &b"HAL"[..]).unwrap();
+//! reader.read_to_string(&mut output).unwrap();
+//! assert_eq!(output, "IBM");
+//!
+//! let mut writer = BfWriter::new(BFI::new(",[.,]".to_string()), Vec::new()).unwrap();
+//! writer.write_all(b"abc").unwrap();
+//! assert_eq!(writer.finish().unwrap(), b"abc");
+//! ```
+
+use std::collections::VecDeque;
+use std::io;
+
+use crate::BFIError;
+use crate::BFI;
+
+fn to_io_error(err: BFIError) -> io::Error {
+    match err {
+        BFIError::Io(err) => err,
+        err => io::Error::other(err.to_string()),
+    }
+}
+
+/// Reads the output of a program that takes its input from `inner` on demand.
+///
+/// Each `,` reads a single byte from `inner`, so wrap unbuffered sources in
+/// an `io::BufReader`.
+pub struct BfReader<R: io::Read> {
+    bfi: BFI,
+    inner: R,
+    output: VecDeque<u8>,
+    done: bool,
+}
+
+impl<R: io::Read> BfReader<R> {
+    pub fn new(bfi: BFI, inner: R) -> Result<Self, BFIError> {
+        bfi.check_syntax()?;
+        Ok(Self {
+            bfi,
+            inner,
+            output: VecDeque::new(),
+            done: false,
+        })
+    }
+
+    /// Returns the interpreter, e.g. to inspect its statistics.
+    pub fn into_inner(self) -> (BFI, R) {
+        (self.bfi, self.inner)
+    }
+}
+
+impl<R: io::Read> io::Read for BfReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.output.is_empty() && !self.done {
+            self.done = !self
+                .bfi
+                .step(&mut self.inner, &mut self.output)
+                .map_err(to_io_error)?;
+        }
+        io::Read::read(&mut self.output, buf)
+    }
+}
+
+/// Feeds written bytes as the input of a program whose output goes to `inner`.
+///
+/// The program runs until it waits for more input than was written. Call
+/// [`BfWriter::finish`] to signal the end of input and let it complete.
+/// Bytes written after the program has ended are ignored.
+pub struct BfWriter<W: io::Write> {
+    bfi: BFI,
+    inner: W,
+    input: VecDeque<u8>,
+    done: bool,
+}
+
+impl<W: io::Write> BfWriter<W> {
+    pub fn new(bfi: BFI, inner: W) -> Result<Self, BFIError> {
+        bfi.check_syntax()?;
+        Ok(Self {
+            bfi,
+            inner,
+            input: VecDeque::new(),
+            done: false,
+        })
+    }
+
+    fn run(&mut self, eof: bool) -> io::Result<()> {
+        while !self.done && (eof || !self.input.is_empty() || self.bfi.current_command() != Some(b',')) {
+            self.done = !self
+                .bfi
+                .step(&mut self.input, &mut self.inner)
+                .map_err(to_io_error)?;
+        }
+        Ok(())
+    }
+
+    /// Ends the input, runs the program to completion, and returns `inner`.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.run(true)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: io::Write> io::Write for BfWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !self.done {
+            self.input.extend(buf);
+            self.run(false)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read, Write};
+
+    use crate::adaptor::{BfReader, BfWriter};
+    use crate::BFIError;
+    use crate::BFI;
+
+    #[test]
+    fn test_reader() {
+        // Reads no more input than the program asks for.
+        let mut reader = BfReader::new(BFI::new("++[>,.<-]".to_string()), &b"xyz"[..]).unwrap();
+        let mut buf = [0; 4];
+        assert_eq!(reader.read(&mut buf).unwrap(), 1);
+        assert_eq!(reader.read(&mut buf).unwrap(), 1);
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+        assert_eq!(reader.into_inner().1, b"z");
+
+        let mut reader = BfReader::new(BFI::new("+[>+]".to_string()), io::empty()).unwrap();
+        assert!(reader.read(&mut [0; 4]).is_err());
+
+        assert!(matches!(
+            BfReader::new(BFI::new("[".to_string()), io::empty()),
+            Err(BFIError::MissingClosingBrackets)
+        ));
+    }
+
+    #[test]
+    fn test_writer() {
+        // Prints the number of bytes of input after the end of input.
+        let mut writer = BfWriter::new(BFI::new(",[.>+<,]>.".to_string()), Vec::new()).unwrap();
+        writer.write_all(b"ab").unwrap();
+        writer.write_all(b"c").unwrap();
+        assert_eq!(writer.finish().unwrap(), b"abc\x03");
+
+        // Stops after the first byte.
+        let mut writer = BfWriter::new(BFI::new(",.".to_string()), Vec::new()).unwrap();
+        writer.write_all(b"xyz").unwrap();
+        assert_eq!(writer.finish().unwrap(), b"x");
+    }
+}
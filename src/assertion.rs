@@ -0,0 +1,236 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! Assertions of annotation lines, checked whenever the execution moves
+//! forward past them.
+//!
+//! `;;assert cell[2] == 65 @here` fails the run with
+//! `BFIError::AssertionFailed` unless cell 2 holds 65 once the command before
+//! the line has been executed, but not when a loop skips the line or jumps
+//! back over it. The cell is given by index or by name, see `symbols`, the
+//! comparison is one of `==`, `!=`, `<`, `<=`, `>`, and `>=`, and the value is
+//! a number from -128 to 255 or an ASCII character like `'A'`. A negative
+//! value compares the cell as signed, others as unsigned. An optional
+//! `@label` names the assertion in the error.
+//!
+//! ```
+//! use bfi::{BFIError, BFI};
+//!
+//! let mut bfi = BFI::new("++++\n;;assert cell[0] == 4\n>+\n;;assert cell[1] > 1 @second\n".to_string());
+//! let err = bfi.interpret(&mut std::io::empty(), &mut Vec::new()).unwrap_err();
+//! assert!(matches!(err, BFIError::AssertionFailed(_)));
+//! assert_eq!(err.to_string(), "Assertion @second failed at line 4: cell[1] > 1, but cell 1 is 1");
+//! ```
+
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::symbols::{self, Symbols};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+/// Condition on a cell, to hold whenever the execution passes its line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assertion {
+    /// Condition as written, like `cell[2] == 65`.
+    pub condition: String,
+    pub label: Option<String>,
+    /// 1-based line of the annotation.
+    pub line: usize,
+    cell: usize,
+    comparison: Comparison,
+    value: i16,
+}
+
+impl Assertion {
+    /// Index of the cell the condition is on.
+    pub fn cell(&self) -> usize {
+        self.cell
+    }
+
+    /// Converts `cell` the way the value of the condition is written.
+    fn read(&self, cell: i8) -> i16 {
+        if self.value < 0 {
+            i16::from(cell)
+        } else {
+            i16::from(cell as u8)
+        }
+    }
+
+    /// Whether the condition holds when the cell is `cell`.
+    pub fn holds(&self, cell: i8) -> bool {
+        let cell = self.read(cell);
+        match self.comparison {
+            Comparison::Equal => cell == self.value,
+            Comparison::NotEqual => cell != self.value,
+            Comparison::Less => cell < self.value,
+            Comparison::LessOrEqual => cell <= self.value,
+            Comparison::Greater => cell > self.value,
+            Comparison::GreaterOrEqual => cell >= self.value,
+        }
+    }
+}
+
+/// An assertion that did not hold, and the value of its cell then.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssertionFailure {
+    pub assertion: Assertion,
+    pub cell: i8,
+}
+
+impl fmt::Display for AssertionFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let assertion = &self.assertion;
+        write!(f, "Assertion ")?;
+        if let Some(ref label) = assertion.label {
+            write!(f, "@{} ", label)?;
+        }
+        write!(
+            f,
+            "failed at line {}: {}, but cell {} is {}",
+            assertion.line,
+            assertion.condition,
+            assertion.cell,
+            assertion.read(self.cell)
+        )
+    }
+}
+
+impl From<AssertionFailure> for crate::BFIError {
+    fn from(failure: AssertionFailure) -> crate::BFIError {
+        crate::BFIError::AssertionFailed(Box::new(failure))
+    }
+}
+
+/// Text of the assertion in the annotation `text`, after the `assert`.
+pub(crate) fn assertion_text(text: &[u8]) -> Option<&[u8]> {
+    let rest = text.trim_ascii_start().strip_prefix(b"assert")?;
+    if rest.is_empty() || rest[0].is_ascii_whitespace() {
+        Some(rest)
+    } else {
+        None
+    }
+}
+
+/// Lists the assertions of the annotations of `code` with the byte offset of
+/// their `;;`, and the offsets of the `assert` annotations that are invalid.
+pub(crate) fn parse(code: &[u8]) -> (Vec<(usize, Assertion)>, Vec<usize>) {
+    let mut symbols = None;
+    let mut assertions = Vec::new();
+    let mut invalid = Vec::new();
+    let mut start = 0;
+    for (index, line) in code.split(|&byte| byte == b'\n').enumerate() {
+        let offset = start + line.iter().take_while(|&&byte| byte == b' ' || byte == b'\t').count();
+        start += line.len() + 1;
+        let rest = match symbols::annotation(line).and_then(assertion_text) {
+            Some(rest) => rest,
+            None => continue,
+        };
+        // Names may be given anywhere in the source.
+        let symbols = symbols.get_or_insert_with(|| Symbols::parse(code));
+        match core::str::from_utf8(rest).ok().and_then(|text| parse_assertion(text, index + 1, symbols)) {
+            Some(assertion) => assertions.push((offset, assertion)),
+            None => invalid.push(offset),
+        }
+    }
+    (assertions, invalid)
+}
+
+/// Parses `cell[CELL] COMPARISON VALUE`, optionally followed by `@label`.
+fn parse_assertion(text: &str, line: usize, symbols: &Symbols) -> Option<Assertion> {
+    let (condition, label) = match text.rsplit_once('@') {
+        Some((condition, label)) => {
+            let label = label.trim();
+            if label.is_empty() || label.contains(char::is_whitespace) {
+                return None;
+            }
+            (condition.trim(), Some(label.to_string()))
+        }
+        None => (text.trim(), None),
+    };
+    let (cell, rest) = condition.strip_prefix("cell[")?.split_once(']')?;
+    let cell = cell.trim();
+    let cell = match cell.parse() {
+        Ok(index) => index,
+        Err(_) => symbols.index(cell)?,
+    };
+    let rest = rest.trim_start();
+    let (comparison, value) = [
+        ("==", Comparison::Equal),
+        ("!=", Comparison::NotEqual),
+        ("<=", Comparison::LessOrEqual),
+        (">=", Comparison::GreaterOrEqual),
+        ("<", Comparison::Less),
+        (">", Comparison::Greater),
+    ]
+    .iter()
+    .find_map(|&(operator, comparison)| rest.strip_prefix(operator).map(|value| (comparison, value.trim())))?;
+    let value = match value.as_bytes() {
+        [b'\'', c, b'\''] if c.is_ascii() => i16::from(*c),
+        _ => value.parse().ok().filter(|value| (-128..=255).contains(value))?,
+    };
+    Some(Assertion {
+        condition: condition.to_string(),
+        label,
+        line,
+        cell,
+        comparison,
+        value,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assertion::parse;
+
+    #[test]
+    fn test_parse() {
+        let (assertions, invalid) = parse(
+            b";; cell 3 = sum\n\
+              +\n  ;;assert cell[2] == 65 @here\n\
+              ;; assert cell[sum]>='A'\n\
+              ;; assert cell[0] != -1\n\
+              ;; assertion of something else\n\
+              ;; assert cell[x] == 1\n\
+              ;; assert cell[0] == 256\n\
+              ;; assert cell[0] == 1 @two words\n",
+        );
+        assert_eq!(assertions.len(), 3);
+        let (offset, ref here) = assertions[0];
+        assert_eq!((offset, here.line, here.cell(), here.label.as_deref()), (20, 3, 2, Some("here")));
+        assert_eq!(here.condition, "cell[2] == 65");
+        assert!(here.holds(65) && !here.holds(64));
+        let sum = &assertions[1].1;
+        assert_eq!((sum.cell(), sum.label.as_deref()), (3, None));
+        assert!(sum.holds(65) && sum.holds(-1) && !sum.holds(64));
+        let signed = &assertions[2].1;
+        assert!(!signed.holds(-1) && signed.holds(127));
+        assert_eq!(invalid.len(), 3);
+    }
+}
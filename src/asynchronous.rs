@@ -0,0 +1,145 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! Asynchronous execution on tokio, enabled by the `async` feature.
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::BFIError;
+use crate::BFI;
+
+/// Number of executed `]` between two voluntary yields to the runtime.
+const YIELD_INTERVAL: usize = 1024;
+
+impl BFI {
+    /// Same as `interpret` but awaits `,` and `.`, and periodically yields at loop
+    /// back-edges so that long-running programs do not starve other tasks.
+    ///
+    /// `writer` is flushed at the end of the program, even if it failed.
+    pub async fn interpret_async<R, W>(&mut self, reader: &mut R, writer: &mut W) -> Result<(), BFIError>
+    where
+        R: AsyncRead + Unpin + ?Sized,
+        W: AsyncWrite + Unpin + ?Sized,
+    {
+        self.check_syntax()?;
+
+        self.restart();
+        let mut result = self.run_async(reader, writer).await;
+        if let Err(err) = writer.flush().await {
+            result = result.and(Err(err.into()));
+        }
+        self.trace_finished(&result);
+        result
+    }
+
+    async fn run_async<R, W>(&mut self, reader: &mut R, writer: &mut W) -> Result<(), BFIError>
+    where
+        R: AsyncRead + Unpin + ?Sized,
+        W: AsyncWrite + Unpin + ?Sized,
+    {
+        let mut back_edges = 0;
+        if self.steps == 0 {
+            self.check_assertions(self.pc)?;
+        }
+        while let Some(command) = self.current_command() {
+            match command {
+                b',' => {
+                    self.count_step()?;
+                    let mut buf = [0u8; 1];
+                    let len = reader.read(&mut buf).await?;
+                    self.store_input(if len == 0 { None } else { Some(buf[0]) });
+                    self.advance(self.pc + 1)?;
+                }
+                b'.' => {
+                    self.count_step()?;
+                    self.count_output()?;
+                    writer.write_all(&[self.x.read(self.p) as u8]).await?;
+                    self.advance(self.pc + 1)?;
+                }
+                b']' => {
+                    self.step(&mut std::io::empty(), &mut std::io::sink())?;
+                    back_edges += 1;
+                    if back_edges % YIELD_INTERVAL == 0 {
+                        tokio::task::yield_now().await;
+                    }
+                }
+                _ => {
+                    self.step(&mut std::io::empty(), &mut std::io::sink())?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use crate::BFIError;
+    use crate::BFI;
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(future)
+    }
+
+    #[test]
+    fn test_interpret_async() {
+        let mut bfi = BFI::new(",>,<>[-<+>]<.".to_string());
+        let mut reader: &[u8] = &[1, 2];
+        let mut writer = Vec::new();
+        block_on(bfi.interpret_async(&mut reader, &mut writer)).unwrap();
+        assert_eq!(writer, [3]);
+
+        let mut bfi = BFI::new(",[.,]".to_string());
+        let mut reader: &[u8] = b"cat";
+        let mut writer = Vec::new();
+        block_on(bfi.interpret_async(&mut reader, &mut writer)).unwrap();
+        assert_eq!(writer, b"cat");
+
+        // Assertions after `,` and `.` are checked as well.
+        let mut bfi = BFI::new(",\n;;assert cell[0] == 'a'\n".to_string());
+        let mut reader: &[u8] = b"b";
+        let result = block_on(bfi.interpret_async(&mut reader, &mut Vec::new()));
+        assert!(matches!(result, Err(BFIError::AssertionFailed(_))));
+    }
+
+    #[test]
+    fn test_interpret_async_duplex() {
+        block_on(async {
+            let (mut client, mut server) = tokio::io::duplex(1);
+            let session = tokio::spawn(async move {
+                let (mut reader, mut writer) = tokio::io::split(&mut server);
+                let mut bfi = BFI::new(",+.,+.".to_string());
+                bfi.interpret_async(&mut reader, &mut writer).await
+            });
+
+            let mut buf = [0u8; 1];
+            client.write_all(b"a").await.unwrap();
+            client.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"b");
+            client.write_all(b"x").await.unwrap();
+            client.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"y");
+            session.await.unwrap().unwrap();
+        });
+    }
+}
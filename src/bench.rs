@@ -0,0 +1,55 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! Measurement of executions, for benchmarks of the interpreter.
+//!
+//! ```
+//! let hello = bfi::bench::run_counted("++++++++[>++++++++<-]>+.", b"").unwrap();
+//! assert_eq!(hello.output, b"A");
+//! assert_eq!(hello.steps, 116);
+//! ```
+
+use std::time::{Duration, Instant};
+
+use crate::BFIError;
+use crate::BFI;
+
+/// Result of [`run_counted`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Measurement {
+    /// Commands executed, counted as by `Limits::max_steps`.
+    pub steps: u64,
+    pub output: Vec<u8>,
+    /// Time spent in `BFI::interpret`, syntax check included.
+    pub elapsed: Duration,
+}
+
+/// Runs `code` over `input` and returns its output, step count, and time.
+pub fn run_counted(code: &str, input: &[u8]) -> Result<Measurement, BFIError> {
+    let mut bfi = BFI::new(code.to_string());
+    let mut output = Vec::new();
+    let start = Instant::now();
+    bfi.interpret(&mut { input }, &mut output)?;
+    let elapsed = start.elapsed();
+    Ok(Measurement {
+        steps: bfi.steps(),
+        output,
+        elapsed,
+    })
+}
@@ -0,0 +1,105 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! Static analyses of programs.
+
+use bfi::termination::{termination, Semantics, Termination};
+use bfi::{BFIError, BFI};
+
+use crate::fingerprint::COMMANDS;
+use crate::run::{self, Options};
+use crate::size;
+use crate::symex::position;
+
+const USAGE: &str = "\
+Usage: bfi analyze --termination [OPTIONS] FILE
+
+Tries to prove that FILE halts on every input, possibly with an error, and
+prints either that it halts or the first loop with no proof. A loop has a
+proof if its body halts and either moves the pointer by a constant, or
+changes the cell of the loop by a constant that reaches zero or overflows.
+
+Options:
+    --termination   Analyze termination
+    --wrapping      Assume that bytes wrap around instead of overflowing
+    --growable      Assume a memory that grows without a ceiling";
+
+/// Describes the termination of `bfi`.
+fn describe(bfi: &BFI, semantics: Semantics) -> Result<String, BFIError> {
+    let command = match termination(&size::program(bfi)?, semantics) {
+        Termination::Halts => return Ok("halts on every input".to_string()),
+        Termination::Unknown { command } => command,
+    };
+    let offset = bfi
+        .commands()
+        .filter(|(_, byte)| COMMANDS.contains(byte))
+        .nth(command)
+        .map_or(0, |(offset, _)| offset);
+    let (line, column) = position(bfi.code(), offset);
+    Ok(format!(
+        "unknown, no proof that the loop at line {}, column {} halts",
+        line, column
+    ))
+}
+
+pub fn main<I: Iterator<Item = String>>(args: I) -> Result<(), BFIError> {
+    let mut semantics = Semantics::default();
+    let mut analyses = false;
+    let mut file = None;
+    for arg in args {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            "--termination" => analyses = true,
+            "--wrapping" => semantics.wrapping = true,
+            "--growable" => semantics.unbounded = true,
+            _ if arg.starts_with('-') => crate::usage_error(&format!("Unknown option '{}'", arg), USAGE),
+            _ if file.is_none() => file = Some(arg),
+            _ => crate::usage_error("Give a single program", USAGE),
+        }
+    }
+    if !analyses {
+        crate::usage_error("Give the analysis to run", USAGE);
+    }
+    let file = file.unwrap_or_else(|| crate::usage_error("Give the program", USAGE));
+    let bfi = run::load(&file, &Options::default(), None)?;
+    println!("{}: {}", file, describe(&bfi, semantics)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bfi::termination::Semantics;
+    use bfi::BFI;
+
+    use crate::analyze::describe;
+
+    #[test]
+    fn test_describe() {
+        let bfi = BFI::new("Read: ,[-]\nCopy: ,[>+<]".to_string());
+        assert_eq!(
+            describe(&bfi, Semantics::default()).unwrap(),
+            "unknown, no proof that the loop at line 2, column 8 halts"
+        );
+        let bfi = BFI::new(",[-]".to_string());
+        assert_eq!(describe(&bfi, Semantics::default()).unwrap(), "halts on every input");
+    }
+}
@@ -0,0 +1,152 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! Recordings of the playback in the asciinema format (version 2), to embed
+//! in slides and web pages.
+
+use std::io::{self, Write};
+
+use bfi::BFI;
+
+use crate::json::Json;
+use crate::observer::Observer;
+use crate::play::{Progress, Speed};
+
+/// Records a frame before each command, `1 / speed` seconds apart, and
+/// writes the recording of every program to `out` at the end.
+pub struct Recorder<W: Write> {
+    out: W,
+    speed: Speed,
+    frames: u64,
+    width: usize,
+    height: usize,
+    events: Vec<Json>,
+    progress: Progress,
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(out: W, speed: Speed) -> Self {
+        Self {
+            out,
+            speed,
+            frames: 0,
+            width: 1,
+            height: 1,
+            events: Vec::new(),
+            progress: Progress::default(),
+        }
+    }
+
+    fn record(&mut self, frame: &str) {
+        for line in frame.lines() {
+            let visible = line.replace("\x1b[7m", "").replace("\x1b[0m", "");
+            self.width = self.width.max(visible.chars().count());
+        }
+        self.height = self.height.max(frame.lines().count());
+        let time = self.frames as f64 / self.speed.0;
+        // The terminal is raw: lines end with a carriage return.
+        let data = format!("\x1b[H\x1b[2J{}", frame.replace('\n', "\r\n"));
+        self.events.push(Json::Array(vec![Json::Number(time), Json::from("o"), Json::from(data)]));
+        self.frames += 1;
+    }
+
+    /// Formats the recording so far, a JSON header and a line per frame.
+    fn cast(&self) -> String {
+        let header = Json::object(vec![
+            ("version", Json::from(2u64)),
+            ("width", Json::from(self.width)),
+            ("height", Json::from(self.height)),
+        ]);
+        let mut cast = header.to_string() + "\n";
+        for event in &self.events {
+            cast += &event.to_string();
+            cast.push('\n');
+        }
+        cast
+    }
+
+    /// Records the end of `bfi`, after which further programs extend the
+    /// recording.
+    pub fn finish(&mut self, bfi: &BFI) {
+        let status = format!("{} steps, end", self.progress.steps);
+        let frame = self.progress.frame(bfi, &status);
+        self.record(&frame);
+        self.progress = Progress::default();
+    }
+
+    /// Writes the recording once every program has run.
+    pub fn save(&mut self) -> io::Result<()> {
+        let cast = self.cast();
+        self.out.write_all(cast.as_bytes())?;
+        self.out.flush()
+    }
+}
+
+impl<W: Write> Observer for Recorder<W> {
+    fn start(&mut self, bfi: &BFI) {
+        self.progress.start(bfi);
+    }
+
+    fn before(&mut self, bfi: &BFI, _command: u8) {
+        let status = format!("{} steps, {}", self.progress.steps, self.speed);
+        let frame = self.progress.frame(bfi, &status);
+        self.record(&frame);
+    }
+
+    fn after(&mut self, bfi: &BFI, command: u8) {
+        self.progress.update(bfi, command);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use bfi::BFI;
+
+    use crate::cast::Recorder;
+    use crate::json::Json;
+    use crate::observer;
+    use crate::play::Speed;
+
+    #[test]
+    fn test_recorder() {
+        let mut recorder = Recorder::new(Vec::new(), Speed(4.0));
+        let mut bfi = BFI::new("+.".to_string());
+        observer::interpret(&mut bfi, &mut Cursor::new(Vec::new()), &mut Vec::new(), &mut [&mut recorder])
+            .unwrap();
+        recorder.finish(&bfi);
+        recorder.save().unwrap();
+
+        let cast = String::from_utf8(recorder.out).unwrap();
+        let lines: Vec<Json> = cast.lines().map(|line| Json::parse(line).unwrap()).collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0].get("version").and_then(Json::as_u64), Some(2));
+        assert_eq!(lines[0].get("height").and_then(Json::as_u64), Some(5));
+        let event = |i: usize| match lines[i] {
+            Json::Array(ref event) => event.clone(),
+            _ => panic!("not an event"),
+        };
+        assert_eq!(event(1)[0], Json::Number(0.0));
+        assert_eq!(event(3)[0], Json::Number(0.5));
+        assert_eq!(event(1)[1], Json::from("o"));
+        let data = event(3)[2].as_str().unwrap().to_string();
+        assert!(data.starts_with("\x1b[H\x1b[2J+.\r\n\r\nMemory: [1]\r\nOutput: \\u{1}\r\n2 steps, end\r\n"));
+    }
+}
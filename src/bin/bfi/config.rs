@@ -0,0 +1,223 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! Defaults of `bfi run` read from `bfi.toml`.
+//!
+//! ```toml
+//! profile = "classic"      # applied first, like --profile
+//! wrap = true              # like --wrap
+//! eof = "unchanged"        # zero, minus-one, or unchanged
+//! tape_size = 30000        # number of cells, at most 32768
+//! strictness = "strict"    # lenient, strict (like --strict), or pure
+//! deterministic = false    # like --deterministic
+//!
+//! [limits]
+//! max_steps = 100000000
+//! max_output = 1048576
+//! max_cells = 1000000      # growable memory, like --max-cells
+//! max_depth = 1024
+//! max_time = 10            # seconds
+//! max_input = 1048576
+//! ```
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use bfi::{BFIError, Strictness};
+
+use crate::json::Json;
+use crate::run::{Options, Profile};
+use crate::toml;
+
+pub const FILE_NAME: &str = "bfi.toml";
+
+/// Returns the configuration file of the current directory if any, otherwise
+/// the one in `$XDG_CONFIG_HOME/bfi` (by default `~/.config/bfi`).
+pub fn find() -> Option<PathBuf> {
+    let local = PathBuf::from(FILE_NAME);
+    if local.is_file() {
+        return Some(local);
+    }
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config_home.join("bfi").join(FILE_NAME)).filter(|user| user.is_file())
+}
+
+fn table<'a>(key: &str, value: &'a Json) -> Result<&'a [(String, Json)], String> {
+    match *value {
+        Json::Object(ref members) => Ok(members),
+        _ => Err(format!("'{}' must be a table", key)),
+    }
+}
+
+fn number(key: &str, value: &Json) -> Result<u64, String> {
+    value
+        .as_u64()
+        .ok_or_else(|| format!("'{}' must be a non-negative integer", key))
+}
+
+fn boolean(key: &str, value: &Json) -> Result<bool, String> {
+    match *value {
+        Json::Bool(value) => Ok(value),
+        _ => Err(format!("'{}' must be a boolean", key)),
+    }
+}
+
+fn string<'a>(key: &str, value: &'a Json) -> Result<&'a str, String> {
+    value
+        .as_str()
+        .ok_or_else(|| format!("'{}' must be a string", key))
+}
+
+fn strictness(key: &str, value: &Json) -> Result<Strictness, String> {
+    match string(key, value)? {
+        "lenient" => Ok(Strictness::Lenient),
+        "strict" => Ok(Strictness::Strict),
+        "pure" => Ok(Strictness::Pure),
+        _ => Err(format!("'{}': expected lenient, strict, or pure", key)),
+    }
+}
+
+/// Applies the settings of the configuration `text` to `options`.
+pub fn apply(text: &str, options: &mut Options) -> Result<(), String> {
+    let document = toml::parse(text)?;
+    let settings = table("", &document)?;
+    // The other settings override the profile wherever it is written.
+    if let Some((key, value)) = settings.iter().find(|(key, _)| key == "profile") {
+        string(key, value)?
+            .parse::<Profile>()
+            .map_err(|err| format!("'{}': {}", key, err))?
+            .apply(options);
+    }
+    for (key, value) in settings {
+        match key.as_str() {
+            "profile" => (),
+            "wrap" => options.wrapping = boolean(key, value)?,
+            "eof" => {
+                options.eof = string(key, value)?
+                    .parse()
+                    .map_err(|err| format!("'{}': {}", key, err))?
+            }
+            "tape_size" => {
+                options.limits.max_cells = Some(number(key, value)? as usize);
+                options.growable = false;
+            }
+            "strictness" => options.strictness = strictness(key, value)?,
+            "deterministic" => options.deterministic = boolean(key, value)?,
+            "limits" => {
+                for (key, value) in table(key, value)? {
+                    match key.as_str() {
+                        "max_steps" => options.limits.max_steps = Some(number(key, value)?),
+                        "max_output" => {
+                            options.limits.max_output = Some(number(key, value)? as usize)
+                        }
+                        "max_cells" => {
+                            options.limits.max_cells = Some(number(key, value)? as usize);
+                            options.growable = true;
+                        }
+                        "max_depth" => options.max_depth = number(key, value)? as usize,
+                        "max_time" => {
+                            options.max_time = Some(Duration::from_secs(number(key, value)?))
+                        }
+                        "max_input" => options.max_input = Some(number(key, value)?),
+                        _ => return Err(format!("unknown key 'limits.{}'", key)),
+                    }
+                }
+            }
+            _ => return Err(format!("unknown key '{}'", key)),
+        }
+    }
+    Ok(())
+}
+
+/// Returns the options configured by the file found by `find`.
+pub fn load() -> Result<Options, BFIError> {
+    let mut options = Options::default();
+    if let Some(path) = find() {
+        let text = fs::read_to_string(&path)?;
+        apply(&text, &mut options).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), err),
+            )
+        })?;
+    }
+    Ok(options)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bfi::{EofPolicy, Limits, Strictness};
+
+    use crate::config::apply;
+    use crate::run::Options;
+
+    #[test]
+    fn test_apply() {
+        let mut options = Options::default();
+        let text = "eof = \"minus-one\"\ntape_size = 100\n[limits]\nmax_steps = 5\n";
+        apply(text, &mut options).unwrap();
+        assert_eq!(options.eof, EofPolicy::MinusOne);
+        assert_eq!(
+            options.limits,
+            Limits {
+                max_steps: Some(5),
+                max_cells: Some(100),
+                max_output: None,
+            }
+        );
+
+        // The profile is applied first.
+        let mut options = Options::default();
+        let text = "wrap = false\nstrictness = \"pure\"\nprofile = \"standard\"\n\
+                    [limits]\nmax_cells = 1000\nmax_depth = 8\nmax_time = 2\nmax_input = 3\n";
+        apply(text, &mut options).unwrap();
+        assert!(!options.wrapping);
+        assert!(options.growable);
+        assert_eq!(options.eof, EofPolicy::Unchanged);
+        assert_eq!(options.strictness, Strictness::Pure);
+        assert_eq!(options.limits.max_cells, Some(1000));
+        assert_eq!(options.max_depth, 8);
+        assert_eq!(options.max_time, Some(Duration::from_secs(2)));
+        assert_eq!(options.max_input, Some(3));
+
+        assert_eq!(
+            apply("dialect = \"ook\"", &mut options).unwrap_err(),
+            "unknown key 'dialect'"
+        );
+        assert_eq!(
+            apply("[limits]\nmax_seconds = 1", &mut options).unwrap_err(),
+            "unknown key 'limits.max_seconds'"
+        );
+        // A `bfi.toml` of a cloned repository must not load native code.
+        assert!(apply("plugin = \"libdialect.so\"", &mut options).is_err());
+        assert!(apply("wrap = 1", &mut options).is_err());
+        assert!(apply("profile = \"fast\"", &mut options).is_err());
+        assert!(apply("eof = \"eof\"", &mut options).is_err());
+        assert!(apply("tape_size = -1", &mut options).is_err());
+        assert!(apply("limits = 1", &mut options).is_err());
+    }
+}
@@ -0,0 +1,174 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! Differences of the memory between two points of an execution, showing only
+//! the cells that changed.
+
+use std::io::Write;
+use std::num::NonZeroU64;
+
+use bfi::symbols::Symbols;
+use bfi::BFI;
+
+use crate::observer::Observer;
+
+/// Memory and pointer at the last checkpoint.
+pub struct Checkpoint {
+    /// Cells up to the last non-zero one.
+    cells: Vec<i16>,
+    pointer: usize,
+}
+
+impl Checkpoint {
+    /// Checkpoint of a fresh interpreter.
+    pub fn new() -> Self {
+        Self {
+            cells: Vec::new(),
+            pointer: 0,
+        }
+    }
+
+    /// Describes the changes of the pointer and of the cells before `end`
+    /// since the checkpoint, like `cell 1: 0→64` or `sum: 0→64` for a named
+    /// cell, and moves the checkpoint to the current state. Returns `None`
+    /// when nothing changed.
+    pub fn diff(&mut self, bfi: &BFI, end: usize, symbols: &Symbols) -> Option<String> {
+        let end = end.max(self.cells.len());
+        let mut cells: Vec<i16> = (0..end).map_while(|i| bfi.cell_value(i)).collect();
+        let mut changes = Vec::new();
+        if bfi.pointer() != self.pointer {
+            changes.push(format!("pointer {}\u{2192}{}", self.pointer, bfi.pointer()));
+        }
+        for (i, &new) in cells.iter().enumerate() {
+            let old = self.cells.get(i).copied().unwrap_or(0);
+            if new != old {
+                changes.push(format!("{}: {}\u{2192}{}", symbols.label(i), old, new));
+            }
+        }
+        let used = cells.iter().rposition(|&cell| cell != 0).map_or(0, |last| last + 1);
+        cells.truncate(used);
+        self.cells = cells;
+        self.pointer = bfi.pointer();
+        if changes.is_empty() {
+            None
+        } else {
+            Some(changes.join(", "))
+        }
+    }
+}
+
+/// Writes the changes of the memory every `every` steps.
+pub struct DiffEvery<W: Write> {
+    out: W,
+    every: NonZeroU64,
+    steps: u64,
+    /// Highest cell the pointer has reached; the cells after it are zero.
+    high: usize,
+    checkpoint: Checkpoint,
+    symbols: Symbols,
+}
+
+impl<W: Write> DiffEvery<W> {
+    pub fn new(out: W, every: NonZeroU64) -> Self {
+        Self {
+            out,
+            every,
+            steps: 0,
+            high: 0,
+            checkpoint: Checkpoint::new(),
+            symbols: Symbols::default(),
+        }
+    }
+
+    fn write(&mut self, bfi: &BFI) {
+        if let Some(diff) = self.checkpoint.diff(bfi, self.high + 1, &self.symbols) {
+            // Diffs are best effort and never stop the program.
+            let _ = writeln!(self.out, "step {}: {}", self.steps, diff);
+        }
+    }
+
+    /// Writes the changes since the last checkpoint, also after a failed run.
+    pub fn finish(&mut self, bfi: &BFI) {
+        if !self.steps.is_multiple_of(self.every.get()) {
+            self.write(bfi);
+        }
+    }
+}
+
+impl<W: Write> Observer for DiffEvery<W> {
+    fn start(&mut self, bfi: &BFI) {
+        self.symbols = Symbols::parse(bfi.code());
+    }
+
+    fn after(&mut self, bfi: &BFI, _command: u8) {
+        self.steps += 1;
+        self.high = self.high.max(bfi.pointer());
+        if self.steps.is_multiple_of(self.every.get()) {
+            self.write(bfi);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::num::NonZeroU64;
+
+    use bfi::symbols::Symbols;
+    use bfi::BFI;
+
+    use crate::diff::{Checkpoint, DiffEvery};
+    use crate::observer;
+
+    #[test]
+    fn test_checkpoint() {
+        let mut bfi = BFI::new("++>+++>-".to_string());
+        let mut checkpoint = Checkpoint::new();
+        let symbols = Symbols::parse(b";; cell 1 = sum");
+        assert_eq!(checkpoint.diff(&bfi, usize::MAX, &symbols), None);
+        bfi.interpret(&mut Cursor::new(Vec::new()), &mut Vec::new()).unwrap();
+        assert_eq!(
+            checkpoint.diff(&bfi, usize::MAX, &symbols).unwrap(),
+            "pointer 0\u{2192}2, cell 0: 0\u{2192}2, sum: 0\u{2192}3, cell 2: 0\u{2192}-1"
+        );
+        assert_eq!(checkpoint.diff(&bfi, usize::MAX, &symbols), None);
+
+        // Cells cleared since the checkpoint are reported as well.
+        bfi.set_code("[+]<[-]".to_string());
+        bfi.interpret(&mut Cursor::new(Vec::new()), &mut Vec::new()).unwrap();
+        assert_eq!(
+            checkpoint.diff(&bfi, 1, &Symbols::default()).unwrap(),
+            "pointer 2\u{2192}1, cell 1: 3\u{2192}0, cell 2: -1\u{2192}0"
+        );
+    }
+
+    #[test]
+    fn test_diff_every() {
+        let mut bfi = BFI::new("+++[>++<-]".to_string());
+        let mut diff = DiffEvery::new(Vec::new(), NonZeroU64::new(8).unwrap());
+        observer::interpret(&mut bfi, &mut Cursor::new(Vec::new()), &mut Vec::new(), &mut [&mut diff]).unwrap();
+        diff.finish(&bfi);
+        assert_eq!(
+            String::from_utf8(diff.out).unwrap(),
+            "step 8: cell 0: 0\u{2192}3, cell 1: 0\u{2192}2\n\
+             step 16: cell 0: 3\u{2192}1, cell 1: 2\u{2192}4\n\
+             step 24: cell 0: 1\u{2192}0, cell 1: 4\u{2192}6\n"
+        );
+    }
+}
@@ -0,0 +1,91 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! Digests of executions, telling whether two runs behaved the same without
+//! storing their traces.
+
+use bfi::BFI;
+
+use crate::observer::Observer;
+use crate::sha256::Sha256;
+
+/// SHA-256 of the executed commands, each `,` and `.` followed by the byte it
+/// read or wrote, then of the memory up to its last non-zero cell and of the
+/// final pointer.
+pub struct ExecutionDigest(Sha256);
+
+impl ExecutionDigest {
+    pub fn new() -> Self {
+        ExecutionDigest(Sha256::new())
+    }
+
+    /// Hashes the final state of `bfi`, also after a failed run.
+    pub fn finish(mut self, bfi: &BFI) -> [u8; 32] {
+        let cells: Vec<u8> = (0..).map_while(|i| bfi.cell(i)).map(|cell| cell as u8).collect();
+        let used = cells.iter().rposition(|&cell| cell != 0).map_or(0, |last| last + 1);
+        self.0.update(&(used as u64).to_be_bytes());
+        self.0.update(&cells[..used]);
+        self.0.update(&(bfi.pointer() as u64).to_be_bytes());
+        self.0.finish()
+    }
+}
+
+impl Observer for ExecutionDigest {
+    fn before(&mut self, _bfi: &BFI, command: u8) {
+        self.0.update(&[command]);
+    }
+
+    fn after(&mut self, bfi: &BFI, command: u8) {
+        if command == b',' || command == b'.' {
+            self.0.update(&[bfi.cell(bfi.pointer()).unwrap_or(0) as u8]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use bfi::BFI;
+
+    use crate::digest::ExecutionDigest;
+    use crate::observer;
+
+    fn digest(code: &str, input: &[u8], growable: bool) -> [u8; 32] {
+        let mut bfi = BFI::new(code.to_string());
+        bfi.set_growable(growable);
+        let mut digest = ExecutionDigest::new();
+        let _ = observer::interpret(&mut bfi, &mut Cursor::new(input.to_vec()), &mut Vec::new(), &mut [&mut digest]);
+        digest.finish(&bfi)
+    }
+
+    #[test]
+    fn test_digest() {
+        let cat = ",[.,]";
+        assert_eq!(digest(cat, b"ab", false), digest("read , [write . read ,]", b"ab", true));
+        assert_ne!(digest(cat, b"ab", false), digest(cat, b"ba", false));
+        assert_ne!(digest(cat, b"ab", false), digest(cat, b"abc", false));
+
+        // Same memory and pointer, but different commands.
+        assert_ne!(digest("+>-", b"", false), digest("++->-", b"", false));
+
+        // The failing command is hashed as well.
+        assert_ne!(digest("<", b"", false), digest("", b"", false));
+    }
+}
@@ -0,0 +1,292 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! Bounded equivalence checks of two programs.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process;
+
+use bfi::{BFIError, BFI};
+
+use crate::run::{self, Options};
+use crate::serve;
+
+const USAGE: &str = "\
+Usage: bfi equiv [OPTIONS] FILE OTHER
+
+Runs both programs on the same inputs and prints the first input on which
+their output or error differs. Without --inputs, tries every input of at most
+--max-length bytes of the alphabet, shortest first. Exits with status 8 if the
+programs differ.
+
+Options:
+    --inputs PATH       Use the file PATH, or every file of the directory PATH,
+                        as the inputs
+    --max-length N      Bytes of the longest tried input (default: 2)
+    --alphabet BYTES    Bytes of the tried inputs (default: all 256)
+    --max-steps N       Steps of each run, after which the input is left
+                        undecided (default: 100000)";
+
+/// Exit status once the programs have differed.
+pub const EXIT_DIFFERENT: i32 = 8;
+
+const DEFAULT_MAX_LENGTH: usize = 2;
+const DEFAULT_MAX_STEPS: u64 = 100_000;
+
+/// How a run ended.
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    /// The output, and the kind of the error that ended the run, if any.
+    Ended(Vec<u8>, Option<&'static str>),
+    /// The step limit was exceeded, so the run may have ended later.
+    Undecided,
+}
+
+impl Outcome {
+    fn describe(&self, max_steps: u64) -> String {
+        match *self {
+            Outcome::Ended(ref output, None) => format!("\"{}\"", output.escape_ascii()),
+            Outcome::Ended(ref output, Some(kind)) => format!("\"{}\", then {}", output.escape_ascii(), kind),
+            Outcome::Undecided => format!("no end within {} steps", max_steps),
+        }
+    }
+}
+
+fn outcome(mut bfi: BFI, input: &[u8]) -> Outcome {
+    let mut output = Vec::new();
+    match bfi.interpret(&mut { input }, &mut output) {
+        Ok(()) => Outcome::Ended(output, None),
+        Err(BFIError::StepLimitExceeded) => Outcome::Undecided,
+        Err(err) => Outcome::Ended(output, Some(serve::error_kind(&err))),
+    }
+}
+
+/// Every input of at most `max_length` bytes of `alphabet`, shortest first.
+struct Exhaustive {
+    alphabet: Vec<u8>,
+    max_length: usize,
+    /// Indices in `alphabet` of the bytes of the next input.
+    next: Option<Vec<usize>>,
+}
+
+impl Exhaustive {
+    fn new(alphabet: Vec<u8>, max_length: usize) -> Self {
+        Self {
+            alphabet,
+            max_length,
+            next: Some(Vec::new()),
+        }
+    }
+}
+
+impl Iterator for Exhaustive {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Vec<u8>> {
+        let mut digits = self.next.take()?;
+        let input = digits.iter().map(|&digit| self.alphabet[digit]).collect();
+        // Counts in base `alphabet.len()`, with one more digit once all wrap.
+        let mut carry = true;
+        for digit in digits.iter_mut().rev() {
+            *digit += 1;
+            if *digit < self.alphabet.len() {
+                carry = false;
+                break;
+            }
+            *digit = 0;
+        }
+        if carry && digits.len() < self.max_length && !self.alphabet.is_empty() {
+            digits.push(0);
+            carry = false;
+        }
+        if !carry {
+            self.next = Some(digits);
+        }
+        Some(input)
+    }
+}
+
+/// Inputs of the file `path`, or of each file of the directory `path` in
+/// the order of their names, with the names.
+fn corpus(path: &str) -> io::Result<Vec<(String, Vec<u8>)>> {
+    if !Path::new(path).is_dir() {
+        return Ok(vec![(path.to_string(), fs::read(path)?)]);
+    }
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let path = entry?.path();
+        if path.is_file() {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    paths
+        .into_iter()
+        .map(|path| Ok((path.display().to_string(), fs::read(&path)?)))
+        .collect()
+}
+
+/// Result of running two programs on the same inputs.
+#[derive(Debug, PartialEq)]
+struct Comparison {
+    /// Inputs on which both runs ended the same way.
+    agreed: usize,
+    /// Inputs on which a run exceeded the step limit.
+    undecided: usize,
+    /// The first input on which the runs differed, with the outcomes.
+    difference: Option<(Vec<u8>, Outcome, Outcome)>,
+}
+
+/// Runs the programs made by `load` and `load_other` on `inputs` until they
+/// differ.
+fn compare<F, G, I>(load: F, load_other: G, inputs: I) -> Result<Comparison, BFIError>
+where
+    F: Fn() -> Result<BFI, BFIError>,
+    G: Fn() -> Result<BFI, BFIError>,
+    I: IntoIterator<Item = Vec<u8>>,
+{
+    let mut comparison = Comparison {
+        agreed: 0,
+        undecided: 0,
+        difference: None,
+    };
+    for input in inputs {
+        let outcome = outcome(load()?, &input);
+        let other = self::outcome(load_other()?, &input);
+        if outcome == Outcome::Undecided || other == Outcome::Undecided {
+            comparison.undecided += 1;
+        } else if outcome == other {
+            comparison.agreed += 1;
+        } else {
+            comparison.difference = Some((input, outcome, other));
+            break;
+        }
+    }
+    Ok(comparison)
+}
+
+pub fn main<I: Iterator<Item = String>>(args: I) -> Result<(), BFIError> {
+    let mut args = args;
+    let mut inputs = None;
+    let mut max_length = DEFAULT_MAX_LENGTH;
+    let mut alphabet: Vec<u8> = (0..=255).collect();
+    let mut options = Options::default();
+    options.limits.max_steps = Some(DEFAULT_MAX_STEPS);
+    let mut files = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            "--inputs" => inputs = Some(crate::option_value::<String>(&arg, args.next(), USAGE)),
+            "--max-length" => max_length = crate::option_value(&arg, args.next(), USAGE),
+            "--alphabet" => alphabet = crate::option_value::<String>(&arg, args.next(), USAGE).into_bytes(),
+            "--max-steps" => options.limits.max_steps = Some(crate::option_value(&arg, args.next(), USAGE)),
+            _ if arg.starts_with('-') => crate::usage_error(&format!("Unknown option '{}'", arg), USAGE),
+            _ => files.push(arg),
+        }
+    }
+    let (file, other) = match files.as_slice() {
+        [file, other] => (file.as_str(), other.as_str()),
+        _ => crate::usage_error("Give the two programs", USAGE),
+    };
+    alphabet.sort_unstable();
+    alphabet.dedup();
+    // Loading each program once first reports its syntax errors once.
+    run::load(file, &options, None)?;
+    run::load(other, &options, None)?;
+    let load = || run::load(file, &options, None);
+    let load_other = || run::load(other, &options, None);
+    let (comparison, names) = match inputs {
+        Some(path) => {
+            let (names, inputs): (Vec<String>, Vec<Vec<u8>>) = corpus(&path)?.into_iter().unzip();
+            (compare(load, load_other, inputs)?, Some(names))
+        }
+        None => (compare(load, load_other, Exhaustive::new(alphabet, max_length))?, None),
+    };
+    let max_steps = options.limits.max_steps.unwrap_or_default();
+    let undecided = match comparison.undecided {
+        0 => String::new(),
+        undecided => format!(", {} undecided within {} steps", undecided, max_steps),
+    };
+    // Index of the input on which the programs differ, if they do.
+    let index = comparison.agreed + comparison.undecided;
+    match comparison.difference {
+        Some((input, outcome, other_outcome)) => {
+            let name = names.map_or(String::new(), |names| format!("{} ", names[index]));
+            println!("{} and {} differ on {}\"{}\":", file, other, name, input.escape_ascii());
+            println!("    {}: {}", file, outcome.describe(max_steps));
+            println!("    {}: {}", other, other_outcome.describe(max_steps));
+            eprintln!("{} inputs agreed before{}", comparison.agreed, undecided);
+            process::exit(EXIT_DIFFERENT);
+        }
+        None => println!("{} and {} agree on {} inputs{}", file, other, comparison.agreed, undecided),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bfi::{Limits, BFI};
+
+    use crate::equiv::{compare, Exhaustive, Outcome};
+
+    fn load(code: &'static str) -> impl Fn() -> Result<BFI, bfi::BFIError> {
+        move || {
+            let mut bfi = BFI::new(code.to_string());
+            bfi.set_limits(Limits { max_steps: Some(1000), ..Limits::default() });
+            Ok(bfi)
+        }
+    }
+
+    #[test]
+    fn test_exhaustive() {
+        let inputs: Vec<Vec<u8>> = Exhaustive::new(b"ab".to_vec(), 2).collect();
+        assert_eq!(inputs, vec![&b""[..], b"a", b"b", b"aa", b"ab", b"ba", b"bb"]);
+        assert_eq!(Exhaustive::new(Vec::new(), 3).count(), 1);
+        assert_eq!(Exhaustive::new((0..=255).collect(), 2).count(), 1 + 256 + 256 * 256);
+    }
+
+    #[test]
+    fn test_compare() {
+        // The second one echoes at most two bytes.
+        let comparison = compare(load(",[.,]"), load(",[.,[.,[-]]]"), Exhaustive::new(b"a".to_vec(), 5)).unwrap();
+        assert_eq!((comparison.agreed, comparison.undecided), (3, 0));
+        assert_eq!(
+            comparison.difference,
+            Some((b"aaa".to_vec(), Outcome::Ended(b"aaa".to_vec(), None), Outcome::Ended(b"aa".to_vec(), None)))
+        );
+
+        let comparison = compare(load(",[.,]"), load(",[.,]+-"), Exhaustive::new(b"xy".to_vec(), 2)).unwrap();
+        assert_eq!((comparison.agreed, comparison.undecided, comparison.difference), (7, 0, None));
+
+        // Runs that may not end are left undecided.
+        let comparison = compare(load("+[]"), load("."), vec![Vec::new()]).unwrap();
+        assert_eq!((comparison.agreed, comparison.undecided, comparison.difference), (0, 1, None));
+
+        let comparison = compare(load("<"), load(""), vec![Vec::new()]).unwrap();
+        assert_eq!(
+            comparison.difference,
+            Some((Vec::new(), Outcome::Ended(Vec::new(), Some("out_of_memory")), Outcome::Ended(Vec::new(), None)))
+        );
+    }
+}
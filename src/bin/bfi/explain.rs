@@ -0,0 +1,191 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! Explanations of each executed command, for teaching.
+
+use std::io::Write;
+
+use bfi::symbols::Symbols;
+use bfi::BFI;
+
+use crate::observer::Observer;
+
+/// Cells shown around the pointer once the memory in use gets wider.
+const TAPE_WINDOW: usize = 8;
+
+/// Writes a line per executed command: what it did and the memory after it.
+pub struct Explainer<W: Write> {
+    out: W,
+    steps: u64,
+    /// Highest cell the pointer has reached; the cells after it are zero.
+    high: usize,
+    previous: Option<u8>,
+    symbols: Symbols,
+}
+
+impl<W: Write> Explainer<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            steps: 0,
+            high: 0,
+            previous: None,
+            symbols: Symbols::default(),
+        }
+    }
+}
+
+/// Describes `command` just executed by `bfi` after `previous`.
+fn explain(bfi: &BFI, command: u8, previous: Option<u8>, symbols: &Symbols) -> String {
+    let p = bfi.pointer();
+    let cell = symbols.label(p);
+    let value = bfi.cell_value(p).unwrap_or(0);
+    match command {
+        b'>' | b'<' => format!("move the pointer to {}", cell),
+        b'+' => format!("increment {} to {}", cell, value),
+        b'-' => format!("decrement {} to {}", cell, value),
+        b'.' => match value as u8 {
+            byte @ 0x20..=0x7e => format!("write {} ({:?})", byte, byte as char),
+            byte => format!("write {}", byte),
+        },
+        b',' => format!("read {} into {}", value as u8, cell),
+        // `]` always jumps back to its `[`, which tests the cell again.
+        b'[' if previous == Some(b']') && value == 0 => format!("leave the loop because {} == 0", cell),
+        b'[' if previous == Some(b']') => format!("repeat the loop because {} == {}", cell, value),
+        b'[' if value == 0 => format!("skip the loop because {} == 0", cell),
+        b'[' => format!("enter the loop because {} == {}", cell, value),
+        b']' => "jump back to the start of the loop".to_string(),
+        _ => format!("run the extension, {} is {}", cell, value),
+    }
+}
+
+/// Formats the value of a cell, prefixed with its name if it has one, in
+/// brackets if it is under the pointer.
+pub fn cell(symbols: &Symbols, index: usize, value: i16, pointer: bool) -> String {
+    let text = match symbols.name(index) {
+        Some(name) => format!("{}={}", name, value),
+        None => value.to_string(),
+    };
+    if pointer {
+        format!("[{}]", text)
+    } else {
+        text
+    }
+}
+
+/// Formats the cells up to `high`, or only those around the pointer once they
+/// are too many, putting the cell under the pointer in brackets.
+pub fn tape(bfi: &BFI, high: usize, symbols: &Symbols) -> String {
+    let p = bfi.pointer();
+    let (start, end) = if high < 2 * TAPE_WINDOW {
+        (0, high + 1)
+    } else {
+        let start = p.saturating_sub(TAPE_WINDOW);
+        (start, (start + 2 * TAPE_WINDOW).min(high + 1))
+    };
+    let mut cells: Vec<String> = (start..end)
+        .map(|i| cell(symbols, i, bfi.cell_value(i).unwrap_or(0), i == p))
+        .collect();
+    if start > 0 {
+        cells.insert(0, "...".to_string());
+    }
+    if end <= high {
+        cells.push("...".to_string());
+    }
+    cells.join(" ")
+}
+
+impl<W: Write> Observer for Explainer<W> {
+    fn start(&mut self, bfi: &BFI) {
+        self.symbols = Symbols::parse(bfi.code());
+    }
+
+    fn after(&mut self, bfi: &BFI, command: u8) {
+        self.steps += 1;
+        self.high = self.high.max(bfi.pointer());
+        // Explanations are best effort and never stop the program.
+        let _ = writeln!(
+            self.out,
+            "{:>6}  {}  {:<40} {}",
+            self.steps,
+            command as char,
+            explain(bfi, command, self.previous, &self.symbols),
+            tape(bfi, self.high, &self.symbols)
+        );
+        self.previous = Some(command);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use bfi::symbols::Symbols;
+    use bfi::BFI;
+
+    use crate::explain::{tape, Explainer};
+    use crate::observer;
+
+    #[test]
+    fn test_explainer() {
+        let mut bfi = BFI::new("+[>,.<-]".to_string());
+        let mut explainer = Explainer::new(Vec::new());
+        observer::interpret(&mut bfi, &mut Cursor::new(b"A".to_vec()), &mut Vec::new(), &mut [&mut explainer])
+            .unwrap();
+        let text = String::from_utf8(explainer.out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 9);
+        assert_eq!(lines[0], format!("{:>6}  +  {:<40} [1]", 1, "increment cell 0 to 1"));
+        assert!(lines[1].contains("enter the loop because cell 0 == 1"));
+        assert!(lines[2].ends_with(&format!("{:<40} 1 [0]", "move the pointer to cell 1")));
+        assert!(lines[3].contains("read 65 into cell 1"));
+        assert!(lines[4].contains("write 65 ('A')"));
+        assert!(lines[7].contains("jump back to the start of the loop"));
+        assert!(lines[8].contains("leave the loop because cell 0 == 0"));
+
+        // Annotated cells are called by their names.
+        let mut bfi = BFI::new(";; cell 1 = sum\n>+".to_string());
+        let mut explainer = Explainer::new(Vec::new());
+        observer::interpret(&mut bfi, &mut Cursor::new(Vec::new()), &mut Vec::new(), &mut [&mut explainer]).unwrap();
+        let text = String::from_utf8(explainer.out).unwrap();
+        assert!(text.contains("move the pointer to sum"));
+        assert!(text.ends_with(&format!("{:<40} 0 [sum=1]\n", "increment sum to 1")));
+
+        // Wrapping cells are unsigned.
+        let mut bfi = BFI::new("-".to_string());
+        bfi.set_wrapping(true);
+        let mut explainer = Explainer::new(Vec::new());
+        observer::interpret(&mut bfi, &mut Cursor::new(Vec::new()), &mut Vec::new(), &mut [&mut explainer]).unwrap();
+        let text = String::from_utf8(explainer.out).unwrap();
+        assert!(text.ends_with(&format!("{:<40} [255]\n", "decrement cell 0 to 255")));
+    }
+
+    #[test]
+    fn test_tape() {
+        let mut bfi = BFI::new(">".repeat(20) + "+>" + "+".repeat(3).as_str());
+        bfi.interpret(&mut Cursor::new(Vec::new()), &mut Vec::new()).unwrap();
+        let symbols = Symbols::default();
+        assert_eq!(tape(&bfi, 21, &symbols), "... 0 0 0 0 0 0 0 1 [3]");
+        assert_eq!(tape(&bfi, 30, &symbols), "... 0 0 0 0 0 0 0 1 [3] 0 0 0 0 0 0 0 ...");
+        assert_eq!(tape(&BFI::new(String::new()), 0, &symbols), "[0]");
+
+        let symbols = Symbols::parse(b";; cell 20..22 = x, y");
+        assert_eq!(tape(&bfi, 21, &symbols), "... 0 0 0 0 0 0 0 x=1 [y=3]");
+    }
+}
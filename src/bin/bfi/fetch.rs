@@ -0,0 +1,135 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! Programs and inputs given as `http://` or `https://` URLs.
+
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read};
+
+pub fn is_url(source: &str) -> bool {
+    source.starts_with("http://") || source.starts_with("https://")
+}
+
+/// Error of a `Limited` reader, a limit like `--max-input` or `--max-download`.
+#[derive(Debug)]
+pub struct SizeLimitExceeded {
+    what: &'static str,
+    limit: u64,
+}
+
+impl fmt::Display for SizeLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} exceeds {} bytes", self.what, self.limit)
+    }
+}
+
+impl Error for SizeLimitExceeded {}
+
+/// Tells whether `err` is a `SizeLimitExceeded`.
+pub fn is_size_limit(err: &io::Error) -> bool {
+    err.get_ref().is_some_and(|err| err.is::<SizeLimitExceeded>())
+}
+
+/// Reader failing once more than `limit` bytes have been read, unlike
+/// `Read::take`, which would end silently. `what` names the bytes in the error.
+pub struct Limited<R: Read> {
+    inner: R,
+    remaining: u64,
+    limit: u64,
+    what: &'static str,
+}
+
+impl<R: Read> Limited<R> {
+    pub fn new(inner: R, limit: u64, what: &'static str) -> Self {
+        Self {
+            inner,
+            remaining: limit,
+            limit,
+            what,
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+}
+
+impl<R: Read> Read for Limited<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Reads one byte more than allowed to tell exactly `limit` bytes from
+        // more.
+        let max = (buf.len() as u64).min(self.remaining.saturating_add(1)) as usize;
+        let len = self.inner.read(&mut buf[..max])?;
+        if len as u64 > self.remaining {
+            return Err(io::Error::other(SizeLimitExceeded {
+                what: self.what,
+                limit: self.limit,
+            }));
+        }
+        self.remaining -= len as u64;
+        Ok(len)
+    }
+}
+
+#[cfg(feature = "http")]
+pub fn get(url: &str, limit: u64) -> io::Result<Box<dyn Read>> {
+    let response = ureq::get(url).call().map_err(io::Error::other)?;
+    Ok(Box::new(Limited::new(response.into_reader(), limit, "Download")))
+}
+
+#[cfg(not(feature = "http"))]
+pub fn get(_url: &str, _limit: u64) -> io::Result<Box<dyn Read>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "bfi was built without the http feature",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use bfi::BFIError;
+
+    use crate::fetch::{is_size_limit, is_url, Limited};
+
+    #[test]
+    fn test_is_url() {
+        assert!(is_url("https://example.com/hello.b"));
+        assert!(is_url("http://localhost:8000/hello.b"));
+        assert!(!is_url("hello.b"));
+        assert!(!is_url("file:///hello.b"));
+    }
+
+    #[test]
+    fn test_limited() {
+        let mut data = String::new();
+        Limited::new(&b"+++."[..], 4, "Download")
+            .read_to_string(&mut data)
+            .unwrap();
+        assert_eq!(data, "+++.");
+        let err = Limited::new(&b"+++.."[..], 4, "Download")
+            .read_to_string(&mut data)
+            .unwrap_err();
+        assert!(is_size_limit(&err));
+        assert_eq!(err.to_string(), "Download exceeds 4 bytes");
+        assert_eq!(crate::exit_code(&BFIError::from(err)), 4);
+    }
+}
@@ -0,0 +1,121 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! Fingerprints telling which exact program produced a result.
+
+use std::fmt;
+
+use bfi::BFI;
+
+use crate::json::Json;
+use crate::sha256::{hex, sha256};
+
+pub const COMMANDS: &[u8; 8] = b"><+-.,[]";
+
+/// Hash of the program stripped of everything but the eight commands, so
+/// that comments, annotations, and formatting do not change it.
+#[derive(Debug, PartialEq)]
+pub struct Fingerprint {
+    pub sha256: [u8; 32],
+    /// Bytes of the source.
+    pub size: usize,
+    /// Number of each command, in the order of `COMMANDS`.
+    pub counts: [usize; 8],
+}
+
+impl Fingerprint {
+    pub fn of(bfi: &BFI) -> Self {
+        let mut stripped = Vec::new();
+        let mut counts = [0; 8];
+        for (_, byte) in bfi.commands() {
+            if let Some(i) = COMMANDS.iter().position(|&command| command == byte) {
+                stripped.push(byte);
+                counts[i] += 1;
+            }
+        }
+        Self {
+            sha256: sha256(&stripped),
+            size: bfi.code().len(),
+            counts,
+        }
+    }
+
+    pub fn instructions(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    pub fn to_json(&self) -> Json {
+        let counts = COMMANDS
+            .iter()
+            .zip(self.counts.iter())
+            .map(|(&command, &count)| ((command as char).to_string(), Json::from(count)))
+            .collect();
+        Json::object(vec![
+            ("sha256", Json::from(hex(&self.sha256))),
+            ("size", Json::from(self.size)),
+            ("instructions", Json::from(self.instructions())),
+            ("counts", Json::Object(counts)),
+        ])
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "sha256:{} ({} bytes, {} instructions:",
+            hex(&self.sha256),
+            self.size,
+            self.instructions()
+        )?;
+        for (&command, &count) in COMMANDS.iter().zip(self.counts.iter()) {
+            write!(f, " {}{}", command as char, count)?;
+        }
+        f.write_str(")")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bfi::BFI;
+
+    use crate::fingerprint::Fingerprint;
+    use crate::json::Json;
+    use crate::sha256::{hex, sha256};
+
+    #[test]
+    fn test_fingerprint() {
+        let fingerprint = Fingerprint::of(&BFI::new("Add one:\n  +[->+<] .\n;; cell 0..2 = a, b".to_string()));
+        assert_eq!(fingerprint.sha256, Fingerprint::of(&BFI::new("+[->+<].".to_string())).sha256);
+        assert_eq!(fingerprint.sha256, sha256(b"+[->+<]."));
+        assert_eq!(fingerprint.size, 40);
+        assert_eq!(fingerprint.counts, [1, 1, 2, 1, 1, 0, 1, 1]);
+        assert_eq!(
+            fingerprint.to_string(),
+            format!(
+                "sha256:{} (40 bytes, 8 instructions: >1 <1 +2 -1 .1 ,0 [1 ]1)",
+                hex(&sha256(b"+[->+<]."))
+            )
+        );
+
+        let json = fingerprint.to_json();
+        assert_eq!(json.get("instructions").and_then(Json::as_u64), Some(8));
+        assert_eq!(json.get("counts").and_then(|counts| counts.get("+")).and_then(Json::as_u64), Some(2));
+    }
+}
@@ -0,0 +1,427 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! `bfi grade`: runs programs on the cases of a TOML spec and reports which
+//! ones pass.
+//!
+//! ```toml
+//! profile = "standard"   # memory and EOF as --profile, and the defaults of
+//! max_steps = 1000000    # max_steps, max_output, max_cells, and max_time
+//!
+//! [[case]]
+//! name = "empty"
+//! output = "0\n"
+//!
+//! [[case]]
+//! input = "12 30\n"
+//! output = "42\n"
+//! max_steps = 5000       # overrides the default
+//!
+//! [[case]]
+//! input = "x"
+//! error = "assertion_failed"
+//! ```
+
+use std::fs;
+use std::io;
+use std::process;
+use std::time::Duration;
+
+use bfi::{BFIError, BFI};
+
+use crate::fingerprint::Fingerprint;
+use crate::json::Json;
+use crate::run::{self, Options, Profile};
+use crate::serve;
+use crate::toml;
+
+const USAGE: &str = "\
+Usage: bfi grade PROGRAM... CASES
+
+Runs each PROGRAM on every case of the TOML file CASES, and prints a JSON
+report per program on stdout and a summary on stderr. Exits with status 7 if
+a case failed.
+
+Keys of CASES, at the top for every case or in a [[case]] table:
+    max_steps, max_output, max_cells
+                Limits of the run, see 'bfi run --help'
+    max_time    Seconds of wall-clock time a case may take
+    trim        Ignore whitespace at the end of the output (default: false)
+A [[case]] has an optional name, input, and expected output, and the
+expected error kind, like \"step_limit_exceeded\", of a program that must
+fail. The top also takes profile and eof, see 'bfi run --help'.";
+
+/// Exit status once a case has failed.
+pub const EXIT_FAILED: i32 = 7;
+
+/// Settings of a case, inherited from the top of the spec.
+#[derive(Debug, Clone, PartialEq)]
+struct Settings {
+    options: Options,
+    max_time: Option<Duration>,
+    trim: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Case {
+    name: String,
+    input: Vec<u8>,
+    output: Option<Vec<u8>>,
+    error: Option<String>,
+    settings: Settings,
+}
+
+fn string<'a>(key: &str, value: &'a Json) -> Result<&'a str, String> {
+    value.as_str().ok_or_else(|| format!("'{}' must be a string", key))
+}
+
+fn number(key: &str, value: &Json) -> Result<u64, String> {
+    value
+        .as_u64()
+        .ok_or_else(|| format!("'{}' must be a non-negative integer", key))
+}
+
+/// Applies the setting `key` to `settings`, returning `false` if there is no
+/// such setting.
+fn set(settings: &mut Settings, key: &str, value: &Json) -> Result<bool, String> {
+    let limits = &mut settings.options.limits;
+    match key {
+        "max_steps" => limits.max_steps = Some(number(key, value)?),
+        "max_output" => limits.max_output = Some(number(key, value)? as usize),
+        "max_cells" => limits.max_cells = Some(number(key, value)? as usize),
+        "max_time" => {
+            // Also too large numbers, which `Duration` cannot hold.
+            let max_time = match *value {
+                Json::Number(seconds) if seconds > 0.0 => Duration::try_from_secs_f64(seconds).ok(),
+                _ => None,
+            };
+            let max_time = max_time.ok_or_else(|| format!("'{}' must be a positive number of seconds", key))?;
+            settings.max_time = Some(max_time);
+        }
+        "trim" => match *value {
+            Json::Bool(trim) => settings.trim = trim,
+            _ => return Err(format!("'{}' must be a boolean", key)),
+        },
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+/// Parses the cases of the spec `text`.
+pub fn parse(text: &str) -> Result<Vec<Case>, String> {
+    let document = toml::parse(text)?;
+    let members = match document {
+        Json::Object(members) => members,
+        _ => return Err("the spec must be a table".to_string()),
+    };
+    let mut settings = Settings {
+        options: Options::default(),
+        max_time: None,
+        trim: false,
+    };
+    // The profile comes first, as the other keys override it.
+    if let Some((key, value)) = members.iter().find(|(key, _)| key == "profile") {
+        let profile: Profile = string(key, value)?.parse().map_err(|err| format!("'{}': {}", key, err))?;
+        profile.apply(&mut settings.options);
+    }
+    let mut tables = &[][..];
+    for (key, value) in &members {
+        match key.as_str() {
+            "profile" => {}
+            "eof" => {
+                settings.options.eof = string(key, value)?.parse().map_err(|err| format!("'{}': {}", key, err))?
+            }
+            "case" => match *value {
+                Json::Array(ref cases) => tables = cases,
+                _ => return Err("'case' must be an array of tables".to_string()),
+            },
+            _ => {
+                if !set(&mut settings, key, value)? {
+                    return Err(format!("unknown key '{}'", key));
+                }
+            }
+        }
+    }
+    if tables.is_empty() {
+        return Err("no [[case]]".to_string());
+    }
+    tables
+        .iter()
+        .enumerate()
+        .map(|(index, table)| {
+            let mut case = Case {
+                name: format!("case {}", index + 1),
+                input: Vec::new(),
+                output: None,
+                error: None,
+                settings: settings.clone(),
+            };
+            let members = match *table {
+                Json::Object(ref members) => members,
+                _ => return Err("'case' must be an array of tables".to_string()),
+            };
+            for (key, value) in members {
+                match key.as_str() {
+                    "name" => case.name = string(key, value)?.to_string(),
+                    "input" => case.input = string(key, value)?.as_bytes().to_vec(),
+                    "output" => case.output = Some(string(key, value)?.as_bytes().to_vec()),
+                    "error" => case.error = Some(string(key, value)?.to_string()),
+                    _ => {
+                        if !set(&mut case.settings, key, value)? {
+                            return Err(format!("unknown key 'case.{}' in {}", key, case.name));
+                        }
+                    }
+                }
+            }
+            Ok(case)
+        })
+        .collect()
+}
+
+/// Kind and message of an error.
+type Failure = (String, String);
+
+fn failure(err: &BFIError) -> Failure {
+    (serve::error_kind(err).to_string(), err.to_string())
+}
+
+/// Runs `bfi` on `input` like `BFI::interpret`, giving up after `max_time`.
+fn execute(bfi: &mut BFI, input: &[u8], output: &mut Vec<u8>, max_time: Option<Duration>) -> Option<Failure> {
+    let mut input = input;
+    match serve::interpret_until(bfi, &mut input, output, max_time) {
+        Ok(true) => None,
+        Ok(false) => Some(("time_limit_exceeded".to_string(), "Time limit exceeded".to_string())),
+        Err(err) => Some(failure(&err)),
+    }
+}
+
+fn trim_end(bytes: &[u8]) -> &[u8] {
+    let end = bytes.iter().rposition(|byte| !byte.is_ascii_whitespace()).map_or(0, |last| last + 1);
+    &bytes[..end]
+}
+
+/// Why the run of `case` failed, or `None` if it passed.
+fn verdict(case: &Case, output: &[u8], error: &Option<Failure>) -> Option<String> {
+    match (&case.error, error) {
+        (Some(expected), None) => return Some(format!("expected the error '{}', but the program ended", expected)),
+        (Some(expected), Some((kind, _))) if kind != expected => {
+            return Some(format!("expected the error '{}', but got '{}'", expected, kind))
+        }
+        (None, Some((_, message))) => return Some(message.clone()),
+        _ => (),
+    }
+    let expected = case.output.as_deref()?;
+    let matches = if case.settings.trim {
+        trim_end(output) == trim_end(expected)
+    } else {
+        output == expected
+    };
+    if matches {
+        None
+    } else {
+        Some("wrong output".to_string())
+    }
+}
+
+/// Runs the program `file` on `case` and reports the result.
+fn grade(file: &str, case: &Case) -> Json {
+    let mut output = Vec::new();
+    match run::load(file, &case.settings.options, None) {
+        Ok(mut bfi) => {
+            let error = execute(&mut bfi, &case.input, &mut output, case.settings.max_time);
+            result(case, &output, bfi.steps(), error)
+        }
+        Err(err) => result(case, &output, 0, Some(failure(&err))),
+    }
+}
+
+/// Runs the program `code` on `case` and reports the result, failing after
+/// `max_steps` unless the case has its own limit.
+pub fn grade_code(code: String, case: &Case, max_steps: Option<u64>) -> Json {
+    let mut options = case.settings.options.clone();
+    options.limits.max_steps = options.limits.max_steps.or(max_steps);
+    let mut bfi = BFI::new(code);
+    run::configure(&mut bfi, &options);
+    let mut output = Vec::new();
+    let error = execute(&mut bfi, &case.input, &mut output, case.settings.max_time);
+    result(case, &output, bfi.steps(), error)
+}
+
+/// Whether the result of a case reports that it passed.
+pub fn succeeded(result: &Json) -> bool {
+    result.get("passed") == Some(&Json::Bool(true))
+}
+
+/// Reports the run of `case` that wrote `output` in `steps`.
+fn result(case: &Case, output: &[u8], steps: u64, error: Option<Failure>) -> Json {
+    let reason = verdict(case, output, &error);
+    let lossy = |bytes: &[u8]| Json::from(String::from_utf8_lossy(bytes).into_owned());
+    Json::object(vec![
+        ("name", Json::from(case.name.as_str())),
+        ("passed", Json::from(reason.is_none())),
+        ("reason", reason.map_or(Json::Null, Json::from)),
+        ("output", lossy(output)),
+        ("expected_output", case.output.as_deref().map_or(Json::Null, lossy)),
+        ("steps", Json::from(steps)),
+        (
+            "error",
+            error.map_or(Json::Null, |(kind, message)| {
+                Json::object(vec![("kind", Json::from(kind)), ("message", Json::from(message))])
+            }),
+        ),
+    ])
+}
+
+/// Grades `file` on `cases`, printing the failures to stderr. Returns the
+/// report and whether every case passed.
+fn report(file: &str, cases: &[Case]) -> (Json, bool) {
+    // Loading once first reports the syntax errors once.
+    let (fingerprint, results): (Json, Vec<Json>) = match run::load(file, &Options::default(), None) {
+        Ok(bfi) => (
+            Fingerprint::of(&bfi).to_json(),
+            cases.iter().map(|case| grade(file, case)).collect(),
+        ),
+        Err(err) => (
+            Json::Null,
+            cases.iter().map(|case| result(case, b"", 0, Some(failure(&err)))).collect(),
+        ),
+    };
+    let mut passed = 0;
+    for result in &results {
+        if succeeded(result) {
+            passed += 1;
+        } else {
+            let name = result.get("name").and_then(Json::as_str).unwrap_or_default();
+            let reason = result.get("reason").and_then(Json::as_str).unwrap_or_default();
+            eprintln!("{}: {}: {}", file, name, reason);
+        }
+    }
+    eprintln!("{}: {} of {} cases passed", file, passed, cases.len());
+    let report = Json::object(vec![
+        ("program", Json::from(file)),
+        ("fingerprint", fingerprint),
+        ("passed", Json::from(passed)),
+        ("failed", Json::from(cases.len() - passed)),
+        ("cases", Json::Array(results)),
+    ]);
+    (report, passed == cases.len())
+}
+
+pub fn main<I: Iterator<Item = String>>(args: I) -> Result<(), BFIError> {
+    let mut files: Vec<String> = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            _ if arg.starts_with('-') => crate::usage_error(&format!("Unknown option '{}'", arg), USAGE),
+            _ => files.push(arg),
+        }
+    }
+    let spec = match files.pop() {
+        Some(spec) if !files.is_empty() => spec,
+        _ => crate::usage_error("Give the programs and the cases", USAGE),
+    };
+    let text = fs::read_to_string(&spec)?;
+    let cases = parse(&text)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", spec, err)))?;
+    let mut all_passed = true;
+    for file in &files {
+        let (report, passed) = report(file, &cases);
+        println!("{}", report);
+        all_passed &= passed;
+    }
+    if !all_passed {
+        process::exit(EXIT_FAILED);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bfi::{EofPolicy, BFI};
+
+    use crate::grade::{execute, parse, verdict};
+
+    #[test]
+    fn test_parse() {
+        let cases = parse(
+            "eof = \"minus-one\"\nmax_steps = 100\ntrim = true\n\
+             [[case]]\ninput = \"ab\"\noutput = \"ba\"\n\
+             [[case]]\nname = \"slow\"\nmax_steps = 5\nmax_time = 0.5\nerror = \"step_limit_exceeded\"\n",
+        )
+        .unwrap();
+        assert_eq!(cases.len(), 2);
+        assert_eq!(cases[0].name, "case 1");
+        assert_eq!((cases[0].input.as_slice(), cases[0].output.as_deref()), (&b"ab"[..], Some(&b"ba"[..])));
+        assert_eq!(cases[0].settings.options.eof, EofPolicy::MinusOne);
+        assert_eq!(cases[0].settings.options.limits.max_steps, Some(100));
+        assert!(cases[0].settings.trim);
+        assert_eq!(cases[1].name, "slow");
+        assert_eq!(cases[1].settings.options.limits.max_steps, Some(5));
+        assert_eq!(cases[1].settings.max_time, Some(Duration::from_millis(500)));
+        assert_eq!(cases[1].error.as_deref(), Some("step_limit_exceeded"));
+
+        // The profile applies first, whatever its position.
+        let cases = parse("max_cells = 10\nprofile = \"lenient\"\n[[case]]\n").unwrap();
+        assert_eq!(cases[0].settings.options.limits.max_cells, Some(10));
+        assert!(cases[0].settings.options.growable);
+
+        assert_eq!(parse("max_steps = 1").unwrap_err(), "no [[case]]");
+        assert_eq!(parse("[[case]]\nexpected = \"\"").unwrap_err(), "unknown key 'case.expected' in case 1");
+        assert!(parse("trim = 1\n[[case]]").is_err());
+        assert!(parse("[[case]]\nmax_time = 0").is_err());
+        assert!(parse("[[case]]\nmax_time = 1e30").is_err());
+    }
+
+    #[test]
+    fn test_verdict() {
+        let cases = parse(
+            "[[case]]\noutput = \"a\"\n\
+             [[case]]\noutput = \"a\"\ntrim = true\n\
+             [[case]]\nerror = \"out_of_memory\"\n",
+        )
+        .unwrap();
+        let failure = Some(("out_of_memory".to_string(), "Pointer moved to out of range of memory".to_string()));
+        assert_eq!(verdict(&cases[0], b"a", &None), None);
+        assert_eq!(verdict(&cases[0], b"a\n", &None).as_deref(), Some("wrong output"));
+        assert_eq!(verdict(&cases[1], b"a\n", &None), None);
+        assert_eq!(verdict(&cases[0], b"a", &failure).as_deref(), Some("Pointer moved to out of range of memory"));
+        assert_eq!(verdict(&cases[2], b"", &failure), None);
+        assert_eq!(
+            verdict(&cases[2], b"", &None).as_deref(),
+            Some("expected the error 'out_of_memory', but the program ended")
+        );
+    }
+
+    #[test]
+    fn test_execute() {
+        let mut bfi = BFI::new("+[]".to_string());
+        let error = execute(&mut bfi, b"", &mut Vec::new(), Some(Duration::from_millis(10)));
+        assert_eq!(error.map(|(kind, _)| kind).as_deref(), Some("time_limit_exceeded"));
+
+        let mut bfi = BFI::new(",.".to_string());
+        let mut output = Vec::new();
+        assert_eq!(execute(&mut bfi, b"x", &mut output, None), None);
+        assert_eq!(output, b"x");
+    }
+}
@@ -0,0 +1,360 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! `bfi grpc`: gRPC execution service described by `proto/bfi.proto`.
+
+use std::cell::RefCell;
+use std::io;
+use std::mem;
+
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
+use tonic::{Request, Response, Status, Streaming};
+
+use bfi::io::{ByteIn, ByteOut};
+use bfi::BFIError;
+use bfi::Limits;
+use bfi::BFI;
+
+use crate::fingerprint::Fingerprint;
+use crate::serve;
+use crate::sha256::hex;
+
+mod proto {
+    tonic::include_proto!("bfi");
+}
+
+use proto::interpreter_server::{Interpreter, InterpreterServer};
+use proto::session_request::Request as SessionInput;
+use proto::session_response::Response as SessionOutput;
+
+const DEFAULT_LISTEN: &str = "127.0.0.1:50051";
+/// Output is sent once this many bytes are pending, or when the program
+/// waits for input.
+const OUTPUT_CHUNK_BYTES: usize = 4096;
+/// Number of input and output messages queued per session.
+const QUEUE_LENGTH: usize = 16;
+
+pub const USAGE: &str = "\
+Usage: bfi grpc [--listen ADDR] [--max-steps N] [--max-cells N] [--max-output BYTES]
+
+Options:
+    --listen ADDR       Address to listen on (default: 127.0.0.1:50051)
+    --max-steps N       Maximum commands executed per call (default: 10000000)
+    --max-cells N       Maximum memory cells per call (default: 32768)
+    --max-output BYTES  Maximum output bytes per call (default: 1048576)";
+
+/// Returns the requested limits bounded by `allowed`.
+fn limits(requested: Option<proto::Limits>, allowed: &Limits) -> Limits {
+    let requested = requested.unwrap_or_default();
+    Limits {
+        max_steps: serve::clamp(requested.max_steps, allowed.max_steps),
+        max_cells: serve::clamp(requested.max_cells.map(|n| n as usize), allowed.max_cells),
+        max_output: serve::clamp(requested.max_output.map(|n| n as usize), allowed.max_output),
+    }
+}
+
+fn outcome(result: Result<(), BFIError>, bfi: &BFI) -> proto::Outcome {
+    proto::Outcome {
+        stats: Some(proto::Stats {
+            steps: bfi.steps(),
+            output_bytes: bfi.output_len() as u64,
+            fingerprint: hex(&Fingerprint::of(bfi).sha256),
+        }),
+        error: result.err().map(|err| proto::Error {
+            kind: serve::error_kind(&err).to_string(),
+            message: err.to_string(),
+        }),
+    }
+}
+
+type Responses = mpsc::Sender<Result<proto::SessionResponse, Status>>;
+
+/// Output of a session, sent in chunks.
+struct Outbox {
+    responses: Responses,
+    pending: Vec<u8>,
+}
+
+impl Outbox {
+    fn send(&mut self, response: SessionOutput) -> io::Result<()> {
+        let response = proto::SessionResponse {
+            response: Some(response),
+        };
+        self.responses
+            .blocking_send(Ok(response))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "client disconnected"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.pending.is_empty() {
+            Ok(())
+        } else {
+            let output = mem::take(&mut self.pending);
+            self.send(SessionOutput::Output(output))
+        }
+    }
+}
+
+struct Output<'a>(&'a RefCell<Outbox>);
+
+impl ByteOut for Output<'_> {
+    fn write_byte(&mut self, byte: u8) -> Result<(), io::Error> {
+        let mut outbox = self.0.borrow_mut();
+        outbox.pending.push(byte);
+        if outbox.pending.len() >= OUTPUT_CHUNK_BYTES {
+            outbox.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Input of a session. Pending output is sent before waiting for input.
+struct Input<'a> {
+    inputs: mpsc::Receiver<Vec<u8>>,
+    chunk: std::vec::IntoIter<u8>,
+    outbox: &'a RefCell<Outbox>,
+}
+
+impl ByteIn for Input<'_> {
+    fn read_byte(&mut self) -> Result<Option<u8>, io::Error> {
+        loop {
+            if let Some(byte) = self.chunk.next() {
+                return Ok(Some(byte));
+            }
+            self.outbox.borrow_mut().flush()?;
+            match self.inputs.blocking_recv() {
+                Some(chunk) => self.chunk = chunk.into_iter(),
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+/// Runs `start` over `inputs`, blocking the current thread.
+fn session(
+    start: proto::Start,
+    allowed: &Limits,
+    inputs: mpsc::Receiver<Vec<u8>>,
+    responses: Responses,
+) {
+    let mut bfi = BFI::new(start.code);
+    bfi.set_limits(limits(start.limits, allowed));
+    let outbox = RefCell::new(Outbox {
+        responses,
+        pending: Vec::new(),
+    });
+    let mut input = Input {
+        inputs,
+        chunk: Vec::new().into_iter(),
+        outbox: &outbox,
+    };
+    let result = bfi.interpret(&mut input, &mut Output(&outbox));
+    let mut outbox = outbox.into_inner();
+    // Nothing more can be sent once the client has disconnected.
+    let _ = outbox.flush();
+    let _ = outbox.send(SessionOutput::Outcome(outcome(result, &bfi)));
+}
+
+struct Service {
+    allowed: Limits,
+}
+
+#[tonic::async_trait]
+impl Interpreter for Service {
+    async fn run(
+        &self,
+        request: Request<proto::RunRequest>,
+    ) -> Result<Response<proto::RunResponse>, Status> {
+        let request = request.into_inner();
+        let limits = limits(request.limits, &self.allowed);
+        let response = tokio::task::spawn_blocking(move || {
+            let mut bfi = BFI::new(request.code);
+            bfi.set_limits(limits);
+            let mut output = Vec::new();
+            let result = bfi.interpret(&mut &request.input[..], &mut output);
+            proto::RunResponse {
+                output,
+                outcome: Some(outcome(result, &bfi)),
+            }
+        });
+        response
+            .await
+            .map(Response::new)
+            .map_err(|err| Status::internal(err.to_string()))
+    }
+
+    type SessionStream = ReceiverStream<Result<proto::SessionResponse, Status>>;
+
+    async fn session(
+        &self,
+        request: Request<Streaming<proto::SessionRequest>>,
+    ) -> Result<Response<Self::SessionStream>, Status> {
+        let mut requests = request.into_inner();
+        let start = match requests.message().await? {
+            Some(proto::SessionRequest {
+                request: Some(SessionInput::Start(start)),
+            }) => start,
+            _ => {
+                return Err(Status::invalid_argument(
+                    "The first request must be `start`",
+                ))
+            }
+        };
+
+        let (input_sender, inputs) = mpsc::channel(QUEUE_LENGTH);
+        let (responses, receiver) = mpsc::channel(QUEUE_LENGTH);
+        tokio::spawn(async move {
+            // The input ends with the request stream, or at the first invalid request.
+            while let Ok(Some(proto::SessionRequest {
+                request: Some(SessionInput::Input(chunk)),
+            })) = requests.message().await
+            {
+                if input_sender.send(chunk).await.is_err() {
+                    break;
+                }
+            }
+        });
+        let allowed = self.allowed;
+        tokio::task::spawn_blocking(move || session(start, &allowed, inputs, responses));
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+}
+
+async fn serve(listener: TcpListener, allowed: Limits) -> Result<(), tonic::transport::Error> {
+    tonic::transport::Server::builder()
+        .add_service(InterpreterServer::new(Service { allowed }))
+        .serve_with_incoming(TcpListenerStream::new(listener))
+        .await
+}
+
+pub fn main<I: Iterator<Item = String>>(args: I) -> Result<(), BFIError> {
+    let (listen, allowed) = serve::parse_options(args, DEFAULT_LISTEN, USAGE, |_, _| false);
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(async {
+        let listener = TcpListener::bind(&listen).await?;
+        eprintln!("Listening on {}", listener.local_addr()?);
+        serve(listener, allowed).await.map_err(io::Error::other)
+    })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+    use tokio_stream::StreamExt;
+    use tonic::transport::Endpoint;
+
+    use bfi::Limits;
+
+    use crate::grpc::proto::interpreter_client::InterpreterClient;
+    use crate::grpc::proto::{self, session_request, session_response};
+    use crate::grpc::{limits, serve};
+    use crate::sha256::{hex, sha256};
+
+    #[test]
+    fn test_limits() {
+        let allowed = Limits {
+            max_steps: Some(100),
+            max_cells: None,
+            max_output: Some(10),
+        };
+        let requested = proto::Limits {
+            max_steps: Some(1000),
+            max_cells: Some(5),
+            max_output: None,
+        };
+        assert_eq!(
+            limits(Some(requested), &allowed),
+            Limits {
+                max_steps: Some(100),
+                max_cells: Some(5),
+                max_output: Some(10),
+            }
+        );
+        assert_eq!(limits(None, &allowed), allowed);
+    }
+
+    #[test]
+    fn test_service() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            let allowed = Limits {
+                max_steps: Some(1000),
+                ..Limits::default()
+            };
+            tokio::spawn(serve(listener, allowed));
+            let channel = Endpoint::from_shared(url).unwrap().connect().await.unwrap();
+            let mut client = InterpreterClient::new(channel);
+
+            let response = client
+                .run(proto::RunRequest {
+                    code: ",[+.,]".to_string(),
+                    input: b"HAL".to_vec(),
+                    limits: None,
+                })
+                .await
+                .unwrap()
+                .into_inner();
+            assert_eq!(response.output, b"IBM");
+            let outcome = response.outcome.unwrap();
+            let stats = outcome.stats.unwrap();
+            assert_eq!(stats.output_bytes, 3);
+            assert_eq!(stats.fingerprint, hex(&sha256(b",[+.,]")));
+            assert_eq!(outcome.error, None);
+
+            let response = client
+                .run(proto::RunRequest {
+                    code: "+[]".to_string(),
+                    ..proto::RunRequest::default()
+                })
+                .await
+                .unwrap()
+                .into_inner();
+            let error = response.outcome.unwrap().error.unwrap();
+            assert_eq!(error.kind, "step_limit_exceeded");
+
+            let requests = vec![
+                session_request::Request::Start(proto::Start {
+                    code: ",[.,]".to_string(),
+                    limits: None,
+                }),
+                session_request::Request::Input(b"ab".to_vec()),
+                session_request::Request::Input(b"c".to_vec()),
+            ];
+            let requests = tokio_stream::iter(requests).map(|request| proto::SessionRequest {
+                request: Some(request),
+            });
+            let mut responses = client.session(requests).await.unwrap().into_inner();
+            let mut output = Vec::new();
+            let mut outcome = None;
+            while let Some(response) = responses.next().await {
+                match response.unwrap().response.unwrap() {
+                    session_response::Response::Output(chunk) => output.extend(chunk),
+                    session_response::Response::Outcome(o) => outcome = Some(o),
+                }
+            }
+            assert_eq!(output, b"abc");
+            assert_eq!(outcome.unwrap().stats.unwrap().steps, 14);
+        });
+    }
+}
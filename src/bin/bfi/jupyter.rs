@@ -0,0 +1,1203 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! Jupyter kernel speaking the messaging protocol 5.3 over ZMTP.
+//!
+//! Every cell runs against the memory and the pointer left by the previous
+//! cells, and the output of `.` is streamed to the notebook as it is written.
+//! A runtime error skips the rest of its cell only, keeping the memory and the
+//! pointer as they were when the failing command was reached.
+
+use std::cell::RefCell;
+use std::collections::hash_map::RandomState;
+use std::collections::VecDeque;
+use std::env;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bfi::io::{ByteIn, ByteOut};
+use bfi::symbols::Symbols;
+use bfi::{BFIError, BFI};
+
+use crate::diff::Checkpoint;
+use crate::explain;
+use crate::json::Json;
+use crate::serve;
+use crate::sha256::{hex, sha256};
+use crate::zmtp::Connection;
+
+const USAGE: &str = "\
+Usage: bfi jupyter-kernel CONNECTION_FILE
+       bfi jupyter-kernel --install
+
+Runs a Jupyter kernel for the notebook that wrote CONNECTION_FILE. A line
+'%tape' in a cell prints the memory after the cell has run, '%diff' only the
+cells that changed since the previous '%diff', and '%reset' clears the
+memory and the pointer. Submitting an empty line when the
+program asks for input ends the input of the cell.
+
+Options:
+    --install    Register the kernel as 'brainfuck' for the current user";
+
+const PROTOCOL_VERSION: &str = "5.3";
+const DELIMITER: &[u8] = b"<IDS|MSG>";
+/// Output is sent at every newline or once this many bytes are pending.
+const STREAM_CHUNK_BYTES: usize = 1024;
+
+/// HMAC-SHA256 (RFC 2104) of the concatenation of `parts`.
+fn hmac_sha256(key: &[u8], parts: &[&[u8]]) -> [u8; 32] {
+    let mut block = [0u8; 64];
+    if key.len() > block.len() {
+        block[..32].copy_from_slice(&sha256(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let mut inner: Vec<u8> = block.iter().map(|b| b ^ 0x36).collect();
+    for part in parts {
+        inner.extend_from_slice(part);
+    }
+    let mut outer: Vec<u8> = block.iter().map(|b| b ^ 0x5c).collect();
+    outer.extend_from_slice(&sha256(&inner));
+    sha256(&outer)
+}
+
+/// Returns a random UUID (version 4) for the message and session ids.
+fn uuid() -> String {
+    let state = RandomState::new();
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let mut bytes = [0u8; 16];
+    for (i, chunk) in bytes.chunks_mut(8).enumerate() {
+        let mut hasher = state.build_hasher();
+        hasher.write_usize(i);
+        hasher.write_u128(nanos);
+        chunk.copy_from_slice(&hasher.finish().to_be_bytes());
+    }
+    bytes[6] = bytes[6] & 0x0f | 0x40;
+    bytes[8] = bytes[8] & 0x3f | 0x80;
+    format!(
+        "{}-{}-{}-{}-{}",
+        hex(&bytes[..4]),
+        hex(&bytes[4..6]),
+        hex(&bytes[6..8]),
+        hex(&bytes[8..10]),
+        hex(&bytes[10..])
+    )
+}
+
+/// Formats the time `since_epoch` in ISO 8601, e.g. `2018-06-01T12:00:00.000000Z`.
+fn date(since_epoch: Duration) -> String {
+    let secs = since_epoch.as_secs();
+    // Converts the days to the civil date of the proleptic Gregorian calendar.
+    let z = (secs / 86400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+        year,
+        month,
+        day,
+        secs / 3600 % 24,
+        secs / 60 % 60,
+        secs % 60,
+        since_epoch.subsec_micros()
+    )
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// A message received from the notebook.
+struct Message {
+    /// Routing prefix of the message, sent back with the replies.
+    identities: Vec<Vec<u8>>,
+    header: Json,
+    content: Json,
+}
+
+impl Message {
+    fn msg_type(&self) -> &str {
+        self.header
+            .get("msg_type")
+            .and_then(Json::as_str)
+            .unwrap_or("")
+    }
+}
+
+/// Signs and verifies messages with the key of the connection file.
+struct Signer {
+    key: Vec<u8>,
+}
+
+impl Signer {
+    fn sign(&self, parts: &[&[u8]]) -> String {
+        if self.key.is_empty() {
+            String::new()
+        } else {
+            hex(&hmac_sha256(&self.key, parts))
+        }
+    }
+
+    fn parse(&self, mut frames: Vec<Vec<u8>>) -> Result<Message, String> {
+        let split = frames
+            .iter()
+            .position(|frame| frame == DELIMITER)
+            .ok_or("missing <IDS|MSG> delimiter")?;
+        if frames.len() < split + 6 {
+            return Err("incomplete message".to_string());
+        }
+        let parts: Vec<&[u8]> = frames[split + 2..split + 6]
+            .iter()
+            .map(Vec::as_slice)
+            .collect();
+        let expected = self.sign(&parts);
+        let signature = &frames[split + 1];
+        // Compared in constant time not to reveal the matching prefix.
+        if expected.len() != signature.len()
+            || expected
+                .bytes()
+                .zip(signature.iter())
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                != 0
+        {
+            return Err("invalid signature".to_string());
+        }
+
+        let json = |frame: &[u8]| {
+            std::str::from_utf8(frame)
+                .map_err(|_| "message is not UTF-8".to_string())
+                .and_then(Json::parse)
+        };
+        let header = json(&frames[split + 2])?;
+        let content = json(&frames[split + 5])?;
+        frames.truncate(split);
+        Ok(Message {
+            identities: frames,
+            header,
+            content,
+        })
+    }
+
+    fn encode(
+        &self,
+        identities: Vec<Vec<u8>>,
+        header: &Json,
+        parent: &Json,
+        content: &Json,
+    ) -> Vec<Vec<u8>> {
+        let parts = [
+            header.to_string().into_bytes(),
+            parent.to_string().into_bytes(),
+            b"{}".to_vec(),
+            content.to_string().into_bytes(),
+        ];
+        let signature = self.sign(&[&parts[0], &parts[1], &parts[2], &parts[3]]);
+        let mut frames = identities;
+        frames.push(DELIMITER.to_vec());
+        frames.push(signature.into_bytes());
+        frames.extend(parts.iter().cloned());
+        frames
+    }
+}
+
+/// State shared by the threads serving the sockets.
+struct Kernel {
+    signer: Signer,
+    session: String,
+    iopub: Mutex<Vec<TcpStream>>,
+    stdin: Mutex<Vec<Arc<Connection>>>,
+    interrupted: AtomicBool,
+}
+
+impl Kernel {
+    fn frames(
+        &self,
+        identities: Vec<Vec<u8>>,
+        msg_type: &str,
+        parent: &Message,
+        content: &Json,
+    ) -> Vec<Vec<u8>> {
+        let since_epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let header = Json::object(vec![
+            ("msg_id", Json::from(uuid())),
+            ("session", Json::from(self.session.as_str())),
+            ("username", Json::from("kernel")),
+            ("date", Json::from(date(since_epoch))),
+            ("msg_type", Json::from(msg_type)),
+            ("version", Json::from(PROTOCOL_VERSION)),
+        ]);
+        self.signer
+            .encode(identities, &header, &parent.header, content)
+    }
+
+    fn reply(
+        &self,
+        connection: &Connection,
+        request: &Message,
+        msg_type: &str,
+        content: Json,
+    ) -> io::Result<()> {
+        connection.send(&self.frames(request.identities.clone(), msg_type, request, &content))
+    }
+
+    /// Broadcasts a message to the subscribers of the iopub socket.
+    fn publish(&self, parent: &Message, msg_type: &str, content: Json) {
+        let topic = format!("kernel.{}.{}", self.session, msg_type).into_bytes();
+        let message = crate::zmtp::encode(&self.frames(vec![topic], msg_type, parent, &content));
+        self.iopub
+            .lock()
+            .unwrap()
+            .retain(|stream| io::Write::write_all(&mut { stream }, &message).is_ok());
+    }
+
+    fn status(&self, parent: &Message, state: &str) {
+        let content = Json::object(vec![("execution_state", Json::from(state))]);
+        self.publish(parent, "status", content);
+    }
+
+    /// Returns the stdin connection of the client with `identity`, falling
+    /// back to the last connected one.
+    fn stdin_peer(&self, identity: &[u8]) -> Option<Arc<Connection>> {
+        let peers = self.stdin.lock().unwrap();
+        peers
+            .iter()
+            .rev()
+            .find(|peer| !identity.is_empty() && peer.identity == identity)
+            .or_else(|| peers.last())
+            .cloned()
+    }
+
+    fn forget_stdin_peer(&self, peer: &Arc<Connection>) {
+        self.stdin
+            .lock()
+            .unwrap()
+            .retain(|other| !Arc::ptr_eq(other, peer));
+    }
+}
+
+fn kernel_info() -> Json {
+    Json::object(vec![
+        ("status", Json::from("ok")),
+        ("protocol_version", Json::from(PROTOCOL_VERSION)),
+        ("implementation", Json::from("bfi")),
+        (
+            "implementation_version",
+            Json::from(env!("CARGO_PKG_VERSION")),
+        ),
+        (
+            "language_info",
+            Json::object(vec![
+                ("name", Json::from("brainfuck")),
+                ("version", Json::from("")),
+                ("mimetype", Json::from("text/x-brainfuck")),
+                ("file_extension", Json::from(".b")),
+            ]),
+        ),
+        ("banner", Json::from("bfi (Brainfuck Interpreter by Rust)")),
+        ("help_links", Json::Array(Vec::new())),
+    ])
+}
+
+fn shutdown(kernel: &Kernel, connection: &Connection, request: &Message) -> ! {
+    let restart = request
+        .content
+        .get("restart")
+        .cloned()
+        .unwrap_or(Json::Bool(false));
+    let content = Json::object(vec![("status", Json::from("ok")), ("restart", restart)]);
+    if let Err(err) = kernel.reply(connection, request, "shutdown_reply", content) {
+        eprintln!("{}", err);
+    }
+    process::exit(0);
+}
+
+/// Returns the length of the incomplete UTF-8 sequence at the end of `bytes`.
+fn incomplete_tail(bytes: &[u8]) -> usize {
+    for len in 1..=bytes.len().min(3) {
+        let byte = bytes[bytes.len() - len];
+        if byte & 0xc0 != 0x80 {
+            let needed = match byte {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                0xf0..=0xf7 => 4,
+                _ => 1,
+            };
+            return if needed > len { len } else { 0 };
+        }
+    }
+    0
+}
+
+/// Input and output of the cell being executed.
+struct CellIo<'a> {
+    kernel: &'a Kernel,
+    request: &'a Message,
+    silent: bool,
+    stdin: Option<Arc<Connection>>,
+    input: VecDeque<u8>,
+    eof: bool,
+    output: Vec<u8>,
+}
+
+impl<'a> CellIo<'a> {
+    /// Sends the pending output, keeping an incomplete character unless `all`.
+    fn flush(&mut self, all: bool) {
+        let end = if all {
+            self.output.len()
+        } else {
+            self.output.len() - incomplete_tail(&self.output)
+        };
+        if end == 0 {
+            return;
+        }
+        let text = String::from_utf8_lossy(&self.output[..end]).into_owned();
+        self.output.drain(..end);
+        if !self.silent {
+            let content = Json::object(vec![
+                ("name", Json::from("stdout")),
+                ("text", Json::from(text)),
+            ]);
+            self.kernel.publish(self.request, "stream", content);
+        }
+    }
+
+    /// Asks the notebook for a line. Returns `None` for an empty line.
+    fn request_line(&mut self) -> io::Result<Option<String>> {
+        let peer = match self.stdin {
+            Some(ref peer) => Arc::clone(peer),
+            None => return Ok(None),
+        };
+        let content = Json::object(vec![
+            ("prompt", Json::from("")),
+            ("password", Json::from(false)),
+        ]);
+        if let Err(err) = self
+            .kernel
+            .reply(&peer, self.request, "input_request", content)
+        {
+            self.kernel.forget_stdin_peer(&peer);
+            return Err(err);
+        }
+        while let Some(frames) = peer.receive()? {
+            match self.kernel.signer.parse(frames) {
+                Ok(ref reply) if reply.msg_type() == "input_reply" => {
+                    let value = reply
+                        .content
+                        .get("value")
+                        .and_then(Json::as_str)
+                        .unwrap_or("");
+                    return Ok(Some(value.to_string()).filter(|value| !value.is_empty()));
+                }
+                Ok(_) => (),
+                Err(err) => eprintln!("stdin: {}", err),
+            }
+        }
+        self.kernel.forget_stdin_peer(&peer);
+        Ok(None)
+    }
+}
+
+struct Input<'a, 'b>(&'b RefCell<CellIo<'a>>);
+
+impl<'a, 'b> ByteIn for Input<'a, 'b> {
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut io = self.0.borrow_mut();
+        if io.input.is_empty() && !io.eof {
+            io.flush(true);
+            match io.request_line()? {
+                Some(line) => {
+                    io.input.extend(line.bytes());
+                    io.input.push_back(b'\n');
+                }
+                None => io.eof = true,
+            }
+        }
+        Ok(io.input.pop_front())
+    }
+}
+
+struct Output<'a, 'b>(&'b RefCell<CellIo<'a>>);
+
+impl<'a, 'b> ByteOut for Output<'a, 'b> {
+    fn write_byte(&mut self, byte: u8) -> io::Result<()> {
+        let mut io = self.0.borrow_mut();
+        io.output.push(byte);
+        if byte == b'\n' || io.output.len() >= STREAM_CHUNK_BYTES {
+            io.flush(false);
+        }
+        Ok(())
+    }
+}
+
+/// Splits the lines of a cell starting with `%` from the program, leaving
+/// them empty so that errors are reported at the lines of the cell.
+fn split_magics(cell: &str) -> (String, Vec<&str>) {
+    let mut program = String::new();
+    let mut magics = Vec::new();
+    for line in cell.lines() {
+        match line.trim_start().strip_prefix('%') {
+            Some(magic) => magics.push(magic.trim()),
+            None => program.push_str(line),
+        }
+        program.push('\n');
+    }
+    (program, magics)
+}
+
+/// Formats the cells up to the last non-zero one and the pointer, putting
+/// the cell under the pointer in brackets.
+fn tape(bfi: &BFI, symbols: &Symbols) -> String {
+    let pointer = bfi.pointer();
+    let mut len = pointer + 1;
+    let mut index = len;
+    while let Some(value) = bfi.cell_value(index) {
+        index += 1;
+        if value != 0 {
+            len = index;
+        }
+    }
+    let cells: Vec<String> = (0..len)
+        .map(|i| explain::cell(symbols, i, bfi.cell_value(i).unwrap_or(0), i == pointer))
+        .collect();
+    cells.join(" ") + "\n"
+}
+
+/// Name and description of the error reported for a cell.
+type Failure = (&'static str, String);
+
+fn failure(err: BFIError) -> Failure {
+    (serve::error_kind(&err), err.to_string())
+}
+
+/// Describes `err` raised by the command at the program counter of `bfi`,
+/// with the line and column of the command. Failed assertions give their own
+/// line.
+fn runtime_failure(bfi: &BFI, err: BFIError) -> Failure {
+    if matches!(err, BFIError::AssertionFailed(_)) {
+        return failure(err);
+    }
+    let (kind, message) = failure(err);
+    let offset = match bfi.offset() {
+        Some(offset) => offset,
+        None => return (kind, message),
+    };
+    let before = &bfi.code()[..offset];
+    let line_start = before.iter().rposition(|&byte| byte == b'\n').map_or(0, |i| i + 1);
+    let line = before.iter().filter(|&&byte| byte == b'\n').count() + 1;
+    // UTF-8 continuation bytes do not start a character.
+    let column = before[line_start..].iter().filter(|&&byte| byte & 0xc0 != 0x80).count() + 1;
+    (kind, format!("{} at line {}, column {}", message, line, column))
+}
+
+/// Runs the cells on the interpreter kept across them.
+struct Executor {
+    kernel: Arc<Kernel>,
+    bfi: BFI,
+    /// State of the memory at the last `%diff`.
+    checkpoint: Checkpoint,
+    /// Cell names of the annotations of the cells run so far.
+    symbols: Symbols,
+    execution_count: u64,
+}
+
+impl Executor {
+    fn handle(&mut self, connection: &Connection, request: &Message) -> io::Result<()> {
+        let kernel = Arc::clone(&self.kernel);
+        kernel.status(request, "busy");
+        let ok = || vec![("status", Json::from("ok"))];
+        let result = match request.msg_type() {
+            "kernel_info_request" => {
+                kernel.reply(connection, request, "kernel_info_reply", kernel_info())
+            }
+            "execute_request" => self.execute(connection, request),
+            "is_complete_request" => {
+                let code = request
+                    .content
+                    .get("code")
+                    .and_then(Json::as_str)
+                    .unwrap_or("");
+                let depth = code.chars().try_fold(0i64, |depth, c| match c {
+                    '[' => Some(depth + 1),
+                    ']' if depth == 0 => None,
+                    ']' => Some(depth - 1),
+                    _ => Some(depth),
+                });
+                let status = match depth {
+                    Some(0) => "complete",
+                    Some(_) => "incomplete",
+                    None => "invalid",
+                };
+                let mut content = vec![("status", Json::from(status))];
+                if status == "incomplete" {
+                    content.push(("indent", Json::from("")));
+                }
+                kernel.reply(
+                    connection,
+                    request,
+                    "is_complete_reply",
+                    Json::object(content),
+                )
+            }
+            "complete_request" => {
+                let cursor = request
+                    .content
+                    .get("cursor_pos")
+                    .cloned()
+                    .unwrap_or(Json::Null);
+                let mut content = ok();
+                content.push(("matches", Json::Array(Vec::new())));
+                content.push(("cursor_start", cursor.clone()));
+                content.push(("cursor_end", cursor));
+                content.push(("metadata", Json::object::<&str>(Vec::new())));
+                kernel.reply(connection, request, "complete_reply", Json::object(content))
+            }
+            "inspect_request" => {
+                let mut content = ok();
+                content.push(("found", Json::from(false)));
+                content.push(("data", Json::object::<&str>(Vec::new())));
+                content.push(("metadata", Json::object::<&str>(Vec::new())));
+                kernel.reply(connection, request, "inspect_reply", Json::object(content))
+            }
+            "history_request" => {
+                let mut content = ok();
+                content.push(("history", Json::Array(Vec::new())));
+                kernel.reply(connection, request, "history_reply", Json::object(content))
+            }
+            "comm_info_request" => {
+                let mut content = ok();
+                content.push(("comms", Json::object::<&str>(Vec::new())));
+                kernel.reply(
+                    connection,
+                    request,
+                    "comm_info_reply",
+                    Json::object(content),
+                )
+            }
+            "shutdown_request" => shutdown(&kernel, connection, request),
+            _ => Ok(()),
+        };
+        kernel.status(request, "idle");
+        result
+    }
+
+    fn execute(&mut self, connection: &Connection, request: &Message) -> io::Result<()> {
+        let kernel = Arc::clone(&self.kernel);
+        let code = request
+            .content
+            .get("code")
+            .and_then(Json::as_str)
+            .unwrap_or("");
+        let silent = request.content.get("silent") == Some(&Json::Bool(true));
+        let allow_stdin = request.content.get("allow_stdin") != Some(&Json::Bool(false));
+        if !silent {
+            self.execution_count += 1;
+            let content = Json::object(vec![
+                ("code", Json::from(code)),
+                ("execution_count", Json::from(self.execution_count)),
+            ]);
+            kernel.publish(request, "execute_input", content);
+        }
+
+        let io = RefCell::new(CellIo {
+            kernel: &kernel,
+            request,
+            silent,
+            stdin: if allow_stdin {
+                kernel.stdin_peer(&connection.identity)
+            } else {
+                None
+            },
+            input: VecDeque::new(),
+            eof: false,
+            output: Vec::new(),
+        });
+        let (program, magics) = split_magics(code);
+        kernel.interrupted.store(false, Ordering::Relaxed);
+        let mut result = self.run(program, &io);
+        for magic in magics {
+            if result.is_err() {
+                break;
+            }
+            result = self.magic(magic, &io);
+        }
+        io.borrow_mut().flush(true);
+
+        let execution_count = ("execution_count", Json::from(self.execution_count));
+        let content = match result {
+            Ok(()) => Json::object(vec![
+                ("status", Json::from("ok")),
+                execution_count,
+                ("user_expressions", Json::object::<&str>(Vec::new())),
+                ("payload", Json::Array(Vec::new())),
+            ]),
+            Err((ename, evalue)) => {
+                let error = vec![
+                    ("ename", Json::from(ename)),
+                    ("evalue", Json::from(evalue.as_str())),
+                    (
+                        "traceback",
+                        Json::Array(vec![Json::from(format!("{}: {}", ename, evalue))]),
+                    ),
+                ];
+                if !silent {
+                    kernel.publish(request, "error", Json::object(error.clone()));
+                }
+                let mut content = vec![("status", Json::from("error")), execution_count];
+                content.extend(error);
+                Json::object(content)
+            }
+        };
+        kernel.reply(connection, request, "execute_reply", content)
+    }
+
+    fn run(&mut self, program: String, io: &RefCell<CellIo>) -> Result<(), Failure> {
+        self.symbols.add(program.as_bytes());
+        self.bfi.set_code(program);
+        self.bfi.check_syntax().map_err(failure)?;
+        let mut input = Input(io);
+        let mut output = Output(io);
+        while self
+            .bfi
+            .step(&mut input, &mut output)
+            .map_err(|err| runtime_failure(&self.bfi, err))?
+        {
+            if self.kernel.interrupted.load(Ordering::Relaxed) {
+                return Err(("interrupted", "Interrupted".to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    fn magic(&mut self, magic: &str, io: &RefCell<CellIo>) -> Result<(), Failure> {
+        match magic {
+            "tape" | "diff" => {
+                let text = if magic == "tape" {
+                    tape(&self.bfi, &self.symbols)
+                } else {
+                    let diff = self.checkpoint.diff(&self.bfi, usize::MAX, &self.symbols);
+                    diff.unwrap_or_else(|| "no change".to_string()) + "\n"
+                };
+                let mut io = io.borrow_mut();
+                io.flush(true);
+                io.output.extend_from_slice(text.as_bytes());
+                io.flush(true);
+            }
+            "reset" => {
+                self.bfi = BFI::new(String::new());
+                self.checkpoint = Checkpoint::new();
+                self.symbols = Symbols::default();
+            }
+            _ => return Err(("unknown_magic", format!("Unknown magic '%{}'", magic))),
+        }
+        Ok(())
+    }
+}
+
+fn shell(
+    kernel: Arc<Kernel>,
+    connection: Connection,
+    executor: Sender<(Arc<Connection>, Message)>,
+) -> io::Result<()> {
+    let connection = Arc::new(connection);
+    while let Some(frames) = connection.receive()? {
+        match kernel.signer.parse(frames) {
+            Ok(request) => {
+                if executor.send((Arc::clone(&connection), request)).is_err() {
+                    break;
+                }
+            }
+            Err(err) => eprintln!("shell: {}", err),
+        }
+    }
+    Ok(())
+}
+
+fn control(kernel: Arc<Kernel>, connection: Connection) -> io::Result<()> {
+    while let Some(frames) = connection.receive()? {
+        let request = match kernel.signer.parse(frames) {
+            Ok(request) => request,
+            Err(err) => {
+                eprintln!("control: {}", err);
+                continue;
+            }
+        };
+        match request.msg_type() {
+            "kernel_info_request" => {
+                kernel.reply(&connection, &request, "kernel_info_reply", kernel_info())?
+            }
+            "interrupt_request" => {
+                kernel.interrupted.store(true, Ordering::Relaxed);
+                let content = Json::object(vec![("status", Json::from("ok"))]);
+                kernel.reply(&connection, &request, "interrupt_reply", content)?;
+            }
+            "shutdown_request" => shutdown(&kernel, &connection, &request),
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+fn iopub(kernel: Arc<Kernel>, connection: Connection) -> io::Result<()> {
+    kernel
+        .iopub
+        .lock()
+        .unwrap()
+        .push(connection.stream.try_clone()?);
+    // Subscriptions are ignored: every subscriber receives every message.
+    while connection.receive()?.is_some() {}
+    Ok(())
+}
+
+fn stdin(kernel: Arc<Kernel>, connection: Connection) -> io::Result<()> {
+    kernel.stdin.lock().unwrap().push(Arc::new(connection));
+    Ok(())
+}
+
+fn heartbeat(_kernel: Arc<Kernel>, connection: Connection) -> io::Result<()> {
+    while let Some(frames) = connection.receive()? {
+        connection.send(&frames)?;
+    }
+    Ok(())
+}
+
+/// Accepts the connections of `listener` as a socket of `socket_type`,
+/// serving each of them with `handler` on its own thread.
+fn listen<F>(kernel: &Arc<Kernel>, listener: TcpListener, socket_type: &'static str, handler: F)
+where
+    F: Fn(Arc<Kernel>, Connection) -> io::Result<()> + Clone + Send + 'static,
+{
+    let kernel = Arc::clone(kernel);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let kernel = Arc::clone(&kernel);
+            let handler = handler.clone();
+            let result = stream.map(|stream| {
+                thread::spawn(move || {
+                    let result = Connection::accept(stream, socket_type)
+                        .and_then(|connection| handler(kernel, connection));
+                    if let Err(err) = result {
+                        eprintln!("{}: {}", socket_type, err);
+                    }
+                });
+            });
+            if let Err(err) = result {
+                eprintln!("{}", err);
+            }
+        }
+    });
+}
+
+/// Listening sockets of the kernel.
+struct Sockets {
+    shell: TcpListener,
+    iopub: TcpListener,
+    stdin: TcpListener,
+    control: TcpListener,
+    heartbeat: TcpListener,
+}
+
+fn serve(sockets: Sockets, key: Vec<u8>) {
+    let kernel = Arc::new(Kernel {
+        signer: Signer { key },
+        session: uuid(),
+        iopub: Mutex::new(Vec::new()),
+        stdin: Mutex::new(Vec::new()),
+        interrupted: AtomicBool::new(false),
+    });
+    let (sender, receiver) = mpsc::channel();
+    listen(
+        &kernel,
+        sockets.shell,
+        "ROUTER",
+        move |kernel, connection| shell(kernel, connection, sender.clone()),
+    );
+    listen(&kernel, sockets.control, "ROUTER", control);
+    listen(&kernel, sockets.iopub, "PUB", iopub);
+    listen(&kernel, sockets.stdin, "ROUTER", stdin);
+    listen(&kernel, sockets.heartbeat, "REP", heartbeat);
+
+    let mut executor = Executor {
+        kernel,
+        bfi: BFI::new(String::new()),
+        checkpoint: Checkpoint::new(),
+        symbols: Symbols::default(),
+        execution_count: 0,
+    };
+    for (connection, request) in receiver {
+        if let Err(err) = executor.handle(&connection, &request) {
+            eprintln!("shell: {}", err);
+        }
+    }
+}
+
+/// Writes the kernel spec to `$JUPYTER_DATA_DIR/kernels/brainfuck` (by
+/// default `~/.local/share/jupyter/kernels/brainfuck`).
+fn install() -> Result<(), BFIError> {
+    let data_dir = env::var_os("JUPYTER_DATA_DIR")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/share/jupyter")))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "HOME is not set"))?;
+    let dir = data_dir.join("kernels").join("brainfuck");
+    let argv = vec![
+        Json::from(env::current_exe()?.to_string_lossy().into_owned()),
+        Json::from("jupyter-kernel"),
+        Json::from("{connection_file}"),
+    ];
+    let spec = Json::object(vec![
+        ("argv", Json::Array(argv)),
+        ("display_name", Json::from("Brainfuck")),
+        ("language", Json::from("brainfuck")),
+        ("interrupt_mode", Json::from("message")),
+    ]);
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join("kernel.json"), spec.to_string())?;
+    eprintln!("Installed the kernel spec in {}", dir.display());
+    Ok(())
+}
+
+pub fn main<I: Iterator<Item = String>>(mut args: I) -> Result<(), BFIError> {
+    let path = match args.next() {
+        Some(ref arg) if arg == "--install" => return install(),
+        Some(ref arg) if arg == "-h" || arg == "--help" => {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+        Some(arg) if !arg.starts_with('-') => arg,
+        Some(arg) => crate::usage_error(&format!("Unknown option '{}'", arg), USAGE),
+        None => crate::usage_error("Missing connection file", USAGE),
+    };
+    if let Some(arg) = args.next() {
+        crate::usage_error(&format!("Unexpected argument '{}'", arg), USAGE);
+    }
+
+    let text = fs::read_to_string(&path)?;
+    let info = Json::parse(&text).map_err(|err| invalid_data(format!("{}: {}", path, err)))?;
+    let field = |name: &str| info.get(name).and_then(Json::as_str);
+    if field("transport").is_some_and(|transport| transport != "tcp") {
+        return Err(invalid_data(format!("{}: only the tcp transport is supported", path)).into());
+    }
+    let key = field("key").unwrap_or("").as_bytes().to_vec();
+    if !key.is_empty() && field("signature_scheme").is_some_and(|scheme| scheme != "hmac-sha256") {
+        return Err(invalid_data(format!(
+            "{}: only hmac-sha256 signatures are supported",
+            path
+        ))
+        .into());
+    }
+    let ip = field("ip").unwrap_or("127.0.0.1");
+    let bind = |name: &str| -> io::Result<TcpListener> {
+        let port = info
+            .get(name)
+            .and_then(Json::as_u64)
+            .filter(|&port| port <= u16::MAX as u64)
+            .ok_or_else(|| invalid_data(format!("{}: missing {}", path, name)))?;
+        TcpListener::bind((ip, port as u16))
+    };
+    let sockets = Sockets {
+        shell: bind("shell_port")?,
+        iopub: bind("iopub_port")?,
+        stdin: bind("stdin_port")?,
+        control: bind("control_port")?,
+        heartbeat: bind("hb_port")?,
+    };
+    serve(sockets, key);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+    use std::time::Duration;
+
+    use bfi::symbols::Symbols;
+    use bfi::BFI;
+
+    use crate::json::Json;
+    use crate::jupyter::{
+        date, hmac_sha256, incomplete_tail, serve, split_magics, tape, Message, Signer, Sockets,
+    };
+    use crate::sha256::hex;
+    use crate::zmtp::Connection;
+
+    #[test]
+    fn test_hmac_sha256() {
+        // RFC 4231 test cases 2 and 6.
+        assert_eq!(
+            hex(&hmac_sha256(
+                b"Jefe",
+                &[b"what do ya want ", b"for nothing?"]
+            )),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_eq!(
+            hex(&hmac_sha256(
+                &[0xaa; 131],
+                &[b"Test Using Larger Than Block-Size Key - Hash Key First"]
+            )),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+    }
+
+    #[test]
+    fn test_date() {
+        assert_eq!(date(Duration::from_secs(0)), "1970-01-01T00:00:00.000000Z");
+        assert_eq!(
+            date(Duration::new(951_868_799, 123_456_000)),
+            "2000-02-29T23:59:59.123456Z"
+        );
+    }
+
+    #[test]
+    fn test_signer() {
+        let signer = Signer {
+            key: b"secret".to_vec(),
+        };
+        let header = Json::object(vec![("msg_type", Json::from("kernel_info_request"))]);
+        let frames = signer.encode(
+            vec![b"id".to_vec()],
+            &header,
+            &Json::object::<&str>(Vec::new()),
+            &Json::Null,
+        );
+        let message = signer.parse(frames.clone()).unwrap();
+        assert_eq!(message.identities, vec![b"id".to_vec()]);
+        assert_eq!(message.msg_type(), "kernel_info_request");
+        assert_eq!(message.content, Json::Null);
+
+        let mut tampered = frames.clone();
+        tampered[5] = b"{\"x\":1}".to_vec();
+        assert!(signer.parse(tampered).is_err());
+        assert!(Signer {
+            key: b"other".to_vec()
+        }
+        .parse(frames)
+        .is_err());
+        assert!(signer.parse(vec![b"<IDS|MSG>".to_vec()]).is_err());
+    }
+
+    #[test]
+    fn test_cell_helpers() {
+        let (program, magics) = split_magics("+>+\n  %tape\n%reset \n.");
+        assert_eq!(program, "+>+\n\n\n.\n");
+        assert_eq!(magics, vec!["tape", "reset"]);
+
+        let mut bfi = BFI::new("+>>--<".to_string());
+        bfi.interpret(&mut &b""[..], &mut Vec::new()).unwrap();
+        assert_eq!(tape(&bfi, &Symbols::default()), "1 [0] -2\n");
+        assert_eq!(tape(&BFI::new(String::new()), &Symbols::default()), "[0]\n");
+        assert_eq!(tape(&bfi, &Symbols::parse(b";; cell 1..3 = x, y")), "1 [x=0] y=-2\n");
+
+        assert_eq!(incomplete_tail(b"abc"), 0);
+        assert_eq!(incomplete_tail("\u{e9}".as_bytes()), 0);
+        assert_eq!(incomplete_tail(&"\u{1f600}".as_bytes()[..3]), 3);
+        assert_eq!(incomplete_tail(b"a\xe3\x81"), 2);
+    }
+
+    fn request(signer: &Signer, connection: &Connection, msg_type: &str, content: Json) {
+        let header = Json::object(vec![
+            ("msg_id", Json::from(msg_type)),
+            ("msg_type", Json::from(msg_type)),
+        ]);
+        let frames = signer.encode(
+            Vec::new(),
+            &header,
+            &Json::object::<&str>(Vec::new()),
+            &content,
+        );
+        connection.send(&frames).unwrap();
+    }
+
+    fn receive(signer: &Signer, connection: &Connection) -> Message {
+        signer
+            .parse(connection.receive().unwrap().unwrap())
+            .unwrap()
+    }
+
+    /// Returns the text of the `stream` messages until the kernel is idle.
+    fn streamed(signer: &Signer, iopub: &Connection) -> String {
+        let mut text = String::new();
+        loop {
+            let message = receive(signer, iopub);
+            match message.msg_type() {
+                "stream" => text += message.content.get("text").and_then(Json::as_str).unwrap(),
+                "status" if message.content.get("execution_state") == Some(&Json::from("idle")) => {
+                    return text
+                }
+                _ => (),
+            }
+        }
+    }
+
+    #[test]
+    fn test_kernel() {
+        let bind = || TcpListener::bind("127.0.0.1:0").unwrap();
+        let sockets = Sockets {
+            shell: bind(),
+            iopub: bind(),
+            stdin: bind(),
+            control: bind(),
+            heartbeat: bind(),
+        };
+        let addresses: Vec<_> = [
+            &sockets.shell,
+            &sockets.iopub,
+            &sockets.stdin,
+            &sockets.heartbeat,
+        ]
+        .iter()
+        .map(|listener| listener.local_addr().unwrap())
+        .collect();
+        thread::spawn(move || serve(sockets, b"key".to_vec()));
+        let connect = |index: usize, socket_type| {
+            let stream = TcpStream::connect(addresses[index]).unwrap();
+            Connection::accept(stream, socket_type).unwrap()
+        };
+        let shell = connect(0, "DEALER");
+        let iopub = connect(1, "SUB");
+        let stdin = connect(2, "DEALER");
+        let heartbeat = connect(3, "REQ");
+        let signer = Signer {
+            key: b"key".to_vec(),
+        };
+
+        heartbeat.send(&[&b""[..], b"ping"]).unwrap();
+        assert_eq!(
+            heartbeat.receive().unwrap().unwrap(),
+            vec![b"".to_vec(), b"ping".to_vec()]
+        );
+
+        // Like a client, retries kernel_info until iopub is subscribed.
+        iopub
+            .stream
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        loop {
+            request(&signer, &shell, "kernel_info_request", Json::Null);
+            let reply = receive(&signer, &shell);
+            assert_eq!(reply.msg_type(), "kernel_info_reply");
+            if let Ok(Some(_)) = iopub.receive() {
+                break;
+            }
+        }
+        iopub.stream.set_read_timeout(None).unwrap();
+        streamed(&signer, &iopub);
+
+        let execute = |code: &str| {
+            let content = Json::object(vec![
+                ("code", Json::from(code)),
+                ("allow_stdin", Json::from(true)),
+            ]);
+            request(&signer, &shell, "execute_request", content);
+        };
+        execute("++++++++[>++++++++<-]>+.\n%tape");
+        assert_eq!(streamed(&signer, &iopub), "A0 [65]\n");
+        let reply = receive(&signer, &shell);
+        assert_eq!(reply.content.get("status"), Some(&Json::from("ok")));
+        assert_eq!(
+            reply.content.get("execution_count").and_then(Json::as_u64),
+            Some(1)
+        );
+
+        // The memory is kept across cells, and `,` asks for a line.
+        execute("+.,.,.,.");
+        let input_request = receive(&signer, &stdin);
+        assert_eq!(input_request.msg_type(), "input_request");
+        let content = Json::object(vec![("value", Json::from("x"))]);
+        request(&signer, &stdin, "input_reply", content);
+        assert_eq!(receive(&signer, &stdin).msg_type(), "input_request");
+        request(
+            &signer,
+            &stdin,
+            "input_reply",
+            Json::object(vec![("value", Json::from(""))]),
+        );
+        assert_eq!(streamed(&signer, &iopub), "Bx\n\0");
+        assert_eq!(
+            receive(&signer, &shell).content.get("status"),
+            Some(&Json::from("ok"))
+        );
+
+        // A runtime error skips the rest of the cell but keeps the memory.
+        execute("+\n%tape\n é<<.");
+        assert_eq!(streamed(&signer, &iopub), "");
+        let reply = receive(&signer, &shell);
+        assert_eq!(reply.content.get("ename"), Some(&Json::from("out_of_memory")));
+        assert_eq!(
+            reply.content.get("evalue"),
+            Some(&Json::from("Pointer moved to out of range of memory at line 3, column 4"))
+        );
+        execute("%tape");
+        assert_eq!(streamed(&signer, &iopub), "[0] 1\n");
+        assert_eq!(
+            receive(&signer, &shell).content.get("status"),
+            Some(&Json::from("ok"))
+        );
+
+        // The first `%diff` compares with the memory of a fresh kernel.
+        execute(">+\n%diff\n%diff");
+        assert_eq!(
+            streamed(&signer, &iopub),
+            "pointer 0\u{2192}1, cell 1: 0\u{2192}2\nno change\n"
+        );
+        assert_eq!(
+            receive(&signer, &shell).content.get("status"),
+            Some(&Json::from("ok"))
+        );
+
+        execute("]");
+        assert_eq!(streamed(&signer, &iopub), "");
+        let reply = receive(&signer, &shell);
+        assert_eq!(reply.content.get("status"), Some(&Json::from("error")));
+        assert_eq!(
+            reply.content.get("ename"),
+            Some(&Json::from("missing_opening_brackets"))
+        );
+
+        request(
+            &signer,
+            &shell,
+            "is_complete_request",
+            Json::object(vec![("code", Json::from("[["))]),
+        );
+        let reply = receive(&signer, &shell);
+        assert_eq!(reply.content.get("status"), Some(&Json::from("incomplete")));
+    }
+}
@@ -0,0 +1,368 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! `bfi lint`: reports syntax issues, loops that never run, and moves of the
+//! pointer out of the memory, as text or as SARIF.
+
+use std::process;
+
+use bfi::{BFIError, Strictness, SyntaxIssueKind, BFI};
+
+use crate::json::Json;
+use crate::run::{self, Options};
+use crate::symex::position;
+
+const USAGE: &str = "\
+Usage: bfi lint [OPTIONS] FILE...
+
+Reports the syntax issues of each FILE, the loops that start on a zero cell
+and never run, and the moves of the pointer out of the memory while its
+position is known. Exits with 2 if a FILE has a syntax issue.
+
+Options:
+    --strict    Also report commands between two words
+    --pure      Also report everything but commands and whitespace
+    --sarif     Print the findings as a SARIF 2.1.0 log";
+
+/// Identifiers and descriptions of the rules, in the order of `Rule`.
+const RULES: &[(&str, &str)] = &[
+    ("unmatched-bracket", "A bracket without its pair"),
+    ("nesting-too-deep", "A loop nested deeper than the maximum depth"),
+    ("forbidden-input", "A ',' in a deterministic program"),
+    (
+        "stray-character",
+        "A command between two words, or a character that is not a command",
+    ),
+    ("invalid-assertion", "An assertion that cannot be parsed"),
+    ("dead-loop", "A loop that starts on a zero cell and never runs"),
+    ("pointer-range", "A move of the pointer out of the memory"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rule {
+    UnmatchedBracket,
+    NestingTooDeep,
+    ForbiddenInput,
+    StrayCharacter,
+    InvalidAssertion,
+    DeadLoop,
+    PointerRange,
+}
+
+impl Rule {
+    fn of(kind: SyntaxIssueKind) -> Self {
+        match kind {
+            SyntaxIssueKind::UnmatchedClosing | SyntaxIssueKind::Unclosed => Rule::UnmatchedBracket,
+            SyntaxIssueKind::TooDeep => Rule::NestingTooDeep,
+            SyntaxIssueKind::Input => Rule::ForbiddenInput,
+            SyntaxIssueKind::Stray(_) | SyntaxIssueKind::Unexpected(_) => Rule::StrayCharacter,
+            SyntaxIssueKind::InvalidAssertion => Rule::InvalidAssertion,
+        }
+    }
+
+    pub fn id(self) -> &'static str {
+        RULES[self as usize].0
+    }
+
+    /// Returns whether the program cannot run, rather than possibly misbehave.
+    pub fn is_error(self) -> bool {
+        !matches!(self, Rule::DeadLoop | Rule::PointerRange)
+    }
+}
+
+/// A finding, located by 1-based line and column.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub rule: Rule,
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Returns the index of the `]` closing the `[` at `open` of `commands`.
+fn closing(commands: &[(usize, u8)], open: usize) -> usize {
+    let mut depth = 0;
+    for (i, &(_, byte)) in commands.iter().enumerate().skip(open) {
+        match byte {
+            b'[' => depth += 1,
+            b']' if depth == 1 => return i,
+            b']' => depth -= 1,
+            _ => (),
+        }
+    }
+    commands.len()
+}
+
+/// Returns the findings of `bfi` in order of position.
+///
+/// The loops and the pointer are only analyzed if the brackets match. The
+/// pointer is followed through the first iteration of each loop, and is
+/// still known after a loop if the loop leaves it where it was.
+pub fn lint(bfi: &BFI) -> Vec<Finding> {
+    let issues = bfi.syntax_issues();
+    let mut findings: Vec<Finding> = issues
+        .iter()
+        .map(|issue| Finding {
+            rule: Rule::of(issue.kind),
+            message: issue.kind.to_string(),
+            line: issue.line,
+            column: issue.column,
+        })
+        .collect();
+    if issues.iter().any(|issue| {
+        matches!(
+            issue.kind,
+            SyntaxIssueKind::UnmatchedClosing | SyntaxIssueKind::Unclosed | SyntaxIssueKind::TooDeep
+        )
+    }) {
+        return findings;
+    }
+
+    let commands: Vec<(usize, u8)> = bfi.commands().collect();
+    let mut report = |offset: usize, rule: Rule, message: &str| {
+        let (line, column) = position(bfi.code(), offset);
+        findings.push(Finding {
+            rule,
+            message: message.to_string(),
+            line,
+            column,
+        });
+    };
+    // Every cell is zero until a command changes one, and so is the cell
+    // left by a loop.
+    let mut untouched = true;
+    let mut closed = false;
+    let mut pointer = Some(0);
+    // The pointer at each open loop.
+    let mut entries: Vec<Option<usize>> = Vec::new();
+    let mut i = 0;
+    while i < commands.len() {
+        let (offset, byte) = commands[i];
+        match byte {
+            b'[' if untouched || closed => {
+                report(offset, Rule::DeadLoop, "Loop never runs");
+                i = closing(&commands, i) + 1;
+                closed = true;
+                continue;
+            }
+            b'[' => entries.push(pointer),
+            b']' => {
+                if entries.pop().flatten() != pointer {
+                    pointer = None;
+                }
+            }
+            b'+' | b'-' | b',' => untouched = false,
+            b'<' => {
+                pointer = match pointer {
+                    Some(0) => {
+                        report(offset, Rule::PointerRange, "Pointer moves left of the first cell");
+                        None
+                    }
+                    pointer => pointer.map(|p| p - 1),
+                }
+            }
+            b'>' => {
+                pointer = match pointer.map(|p| p + 1) {
+                    Some(p) if bfi.cell(p).is_none() => {
+                        report(offset, Rule::PointerRange, "Pointer moves past the last cell");
+                        None
+                    }
+                    pointer => pointer,
+                }
+            }
+            // Commands of a dialect may change the cell.
+            b'.' => (),
+            _ => untouched = false,
+        }
+        closed = byte == b']';
+        i += 1;
+    }
+    findings.sort_by_key(|finding| (finding.line, finding.column));
+    findings
+}
+
+/// Returns the SARIF log of the findings of each file.
+pub fn sarif(files: &[(String, Vec<Finding>)]) -> Json {
+    let rules = RULES
+        .iter()
+        .map(|&(id, description)| {
+            Json::object(vec![
+                ("id", Json::from(id)),
+                (
+                    "shortDescription",
+                    Json::object(vec![("text", Json::from(description))]),
+                ),
+            ])
+        })
+        .collect();
+    let mut results = Vec::new();
+    for (file, findings) in files {
+        for finding in findings {
+            let region = Json::object(vec![
+                ("startLine", Json::from(finding.line)),
+                ("startColumn", Json::from(finding.column)),
+            ]);
+            let location = Json::object(vec![(
+                "physicalLocation",
+                Json::object(vec![
+                    (
+                        "artifactLocation",
+                        Json::object(vec![("uri", Json::from(file.as_str()))]),
+                    ),
+                    ("region", region),
+                ]),
+            )]);
+            results.push(Json::object(vec![
+                ("ruleId", Json::from(finding.rule.id())),
+                ("ruleIndex", Json::from(finding.rule as usize)),
+                (
+                    "level",
+                    Json::from(if finding.rule.is_error() { "error" } else { "warning" }),
+                ),
+                (
+                    "message",
+                    Json::object(vec![("text", Json::from(finding.message.as_str()))]),
+                ),
+                ("locations", Json::Array(vec![location])),
+            ]));
+        }
+    }
+    let driver = Json::object(vec![
+        ("name", Json::from("bfi")),
+        ("version", Json::from(env!("CARGO_PKG_VERSION"))),
+        ("rules", Json::Array(rules)),
+    ]);
+    Json::object(vec![
+        ("version", Json::from("2.1.0")),
+        (
+            "$schema",
+            Json::from("https://json.schemastore.org/sarif-2.1.0.json"),
+        ),
+        (
+            "runs",
+            Json::Array(vec![Json::object(vec![
+                ("tool", Json::object(vec![("driver", driver)])),
+                // Columns count characters, as in the messages of bfi.
+                ("columnKind", Json::from("unicodeCodePoints")),
+                ("results", Json::Array(results)),
+            ])]),
+        ),
+    ])
+}
+
+pub fn main<I: Iterator<Item = String>>(args: I) -> Result<(), BFIError> {
+    let mut options = Options::default();
+    let mut as_sarif = false;
+    let mut files = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            "--strict" => options.strictness = options.strictness.max(Strictness::Strict),
+            "--pure" => options.strictness = Strictness::Pure,
+            "--sarif" => as_sarif = true,
+            _ if arg.starts_with('-') => crate::usage_error(&format!("Unknown option '{}'", arg), USAGE),
+            _ => files.push(arg),
+        }
+    }
+    if files.is_empty() {
+        crate::usage_error("Give the programs to lint", USAGE);
+    }
+    let mut linted = Vec::new();
+    for file in files {
+        let findings = lint(&run::read(&file, &options, None)?);
+        linted.push((file, findings));
+    }
+    if as_sarif {
+        println!("{}", sarif(&linted));
+    } else {
+        for (file, findings) in &linted {
+            for finding in findings {
+                println!(
+                    "{}: {} at line {}, column {} [{}]",
+                    file,
+                    finding.message,
+                    finding.line,
+                    finding.column,
+                    finding.rule.id()
+                );
+            }
+        }
+    }
+    if linted.iter().flat_map(|(_, findings)| findings).any(|finding| finding.rule.is_error()) {
+        process::exit(crate::EXIT_SYNTAX);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bfi::BFI;
+
+    use crate::json::Json;
+    use crate::lint::{lint, sarif, Rule};
+
+    fn rules(code: &str) -> Vec<(Rule, usize, usize)> {
+        lint(&BFI::new(code.to_string()))
+            .into_iter()
+            .map(|finding| (finding.rule, finding.line, finding.column))
+            .collect()
+    }
+
+    #[test]
+    fn test_lint() {
+        assert_eq!(rules("+[->+<]>."), []);
+        // The first loop starts on an untouched cell, the last one after a loop.
+        assert_eq!(
+            rules("[.]+[-]\n[-]"),
+            [(Rule::DeadLoop, 1, 1), (Rule::DeadLoop, 2, 1)]
+        );
+        // The loop keeps the pointer, so the move after it is known.
+        assert_eq!(rules("+[>+<-]<"), [(Rule::PointerRange, 1, 8)]);
+        // Not after a loop moving the pointer.
+        assert_eq!(rules("+[>]<<"), []);
+        assert_eq!(rules(">".repeat(40_000).as_str())[0].0, Rule::PointerRange);
+        assert_eq!(
+            rules("+[<]]"),
+            [(Rule::UnmatchedBracket, 1, 5)]
+        );
+    }
+
+    #[test]
+    fn test_sarif() {
+        let log = sarif(&[("a.b".to_string(), lint(&BFI::new("+<]".to_string())))]);
+        let run = match log.get("runs") {
+            Some(Json::Array(runs)) => &runs[0],
+            runs => panic!("{:?}", runs),
+        };
+        let result = match run.get("results") {
+            Some(Json::Array(results)) => &results[0],
+            results => panic!("{:?}", results),
+        };
+        assert_eq!(result.get("ruleId").and_then(Json::as_str), Some("unmatched-bracket"));
+        assert_eq!(result.get("level").and_then(Json::as_str), Some("error"));
+        let region = match result.get("locations") {
+            Some(Json::Array(locations)) => locations[0].get("physicalLocation").and_then(|l| l.get("region")),
+            locations => panic!("{:?}", locations),
+        };
+        assert_eq!(region.and_then(|r| r.get("startColumn")).and_then(Json::as_u64), Some(3));
+    }
+}
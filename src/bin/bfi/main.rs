@@ -0,0 +1,220 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ *
+ *
+ *  The Original Code is http://esoteric.sange.fi/brainfuck/impl/interp/BFI.java
+ *  The Initial Developer of the Original Code is Thomas Cort.
+ *  Portions created by the Initial Developer are Copyright (C) 2003
+ *  the Initial Developer under GPL.
+ */
+
+use std::env;
+use std::fmt::Display;
+use std::process;
+use std::str::FromStr;
+
+use bfi::BFIError;
+
+mod analyze;
+mod base64;
+mod cast;
+mod config;
+mod diff;
+mod digest;
+mod equiv;
+mod explain;
+mod fetch;
+mod fingerprint;
+mod grade;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod json;
+mod jupyter;
+mod lint;
+mod mutate;
+mod observer;
+mod play;
+mod plugin;
+mod reduce;
+mod run;
+mod sandbox;
+mod serve;
+mod sha256;
+mod size;
+mod slice;
+mod symex;
+mod toml;
+#[cfg(feature = "websocket")]
+mod websocket;
+mod worker;
+mod zmtp;
+
+const USAGE: &str = "\
+Usage: bfi [run] [OPTIONS] FILE...
+       bfi grade PROGRAM... CASES
+       bfi size [--json] FILE [OTHER]
+       bfi equiv [OPTIONS] FILE OTHER
+       bfi symex [OPTIONS] FILE
+       bfi analyze --termination [OPTIONS] FILE
+       bfi lint [--sarif] FILE...
+       bfi slice [OPTIONS] FILE
+       bfi reduce --check COMMAND FILE
+       bfi mutate PROGRAM CASES
+       bfi serve [OPTIONS]
+       bfi worker --in DIR --out DIR [OPTIONS]
+       bfi grpc [OPTIONS]
+       bfi jupyter-kernel CONNECTION_FILE
+
+Commands:
+    run     Run the program files in order (default)
+    grade   Run programs on the cases of a TOML file (see 'bfi grade --help')
+    size    Print code-golf metrics of a program, or compare two
+    equiv   Look for an input on which two programs differ (see
+            'bfi equiv --help')
+    symex   Look for inputs that make a program fail or reach a command
+            (experimental, see 'bfi symex --help')
+    analyze Try to prove that a program halts on every input
+    lint    Report syntax issues, loops that never run, and moves of the
+            pointer out of the memory, optionally as SARIF
+    slice   Locate the part of a program that writes some of its output
+    reduce  Shrink a program while a check command still fails on it
+    mutate  Score how many mutants of a program the cases of 'bfi grade' catch
+    serve   Run the HTTP execution service
+    worker  Run the jobs dropped in a directory (see 'bfi worker --help')
+    grpc    Run the gRPC execution service (grpc feature)
+    jupyter-kernel
+            Run a Jupyter kernel (see 'bfi jupyter-kernel --help')
+
+Exit status:
+    0  Success
+    1  Invalid command line
+    2  Syntax error, including an invalid assertion, input in a
+       --deterministic program, or a stray character in a --strict
+       program
+    3  Runtime error: the pointer left the memory, a byte overflowed, or
+       the programs given to --link deadlocked
+    4  Limit exceeded: steps, output, memory, time, input, or download
+    5  I/O error
+    6  An assertion of the program failed
+    7  A case of 'bfi grade' failed
+    8  The programs given to 'bfi equiv' differ";
+
+/// Exit status of a syntax error.
+pub const EXIT_SYNTAX: i32 = 2;
+/// Exit status of a runtime error.
+pub const EXIT_RUNTIME: i32 = 3;
+/// Exit status of an exceeded limit.
+pub const EXIT_LIMIT: i32 = 4;
+/// Exit status of an I/O error.
+pub const EXIT_IO: i32 = 5;
+/// Exit status of a failed assertion.
+pub const EXIT_ASSERTION: i32 = 6;
+
+/// Returns the documented exit status for `err`.
+pub fn exit_code(err: &BFIError) -> i32 {
+    match *err {
+        BFIError::MissingClosingBrackets
+        | BFIError::MissingOpeningBrackets
+        | BFIError::NestingTooDeep
+        | BFIError::InputForbidden
+        | BFIError::StrayCharacters
+        | BFIError::InvalidAssertion => EXIT_SYNTAX,
+        BFIError::OutOfMemory | BFIError::ArithmeticOverflow => EXIT_RUNTIME,
+        BFIError::StepLimitExceeded | BFIError::OutputLimitExceeded | BFIError::MemoryLimitExceeded => {
+            EXIT_LIMIT
+        }
+        BFIError::Io(ref err) if fetch::is_size_limit(err) => EXIT_LIMIT,
+        BFIError::Io(_) => EXIT_IO,
+        BFIError::AssertionFailed(_) => EXIT_ASSERTION,
+    }
+}
+
+/// Prints `message` and `usage` to stderr and exits with failure.
+pub fn usage_error(message: &str, usage: &str) -> ! {
+    eprintln!("{}\n\n{}", message, usage);
+    process::exit(1);
+}
+
+/// Parses the value given to `option`, exiting with the usage on failure.
+pub fn option_value<T>(option: &str, value: Option<String>, usage: &str) -> T
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match value {
+        Some(value) => value.parse().unwrap_or_else(|err| {
+            usage_error(
+                &format!("Invalid value '{}' for {}: {}", value, option, err),
+                usage,
+            )
+        }),
+        None => usage_error(&format!("Missing value for {}", option), usage),
+    }
+}
+
+fn main() {
+    if let Err(err) = dispatch() {
+        eprintln!("Error: {}", err);
+        process::exit(exit_code(&err));
+    }
+}
+
+fn dispatch() -> Result<(), BFIError> {
+    let mut args = env::args().skip(1).peekable();
+    match args.peek().map(String::as_str) {
+        Some("run") => run::main(args.skip(1)),
+        Some("grade") => grade::main(args.skip(1)),
+        Some("size") => size::main(args.skip(1)),
+        Some("equiv") => equiv::main(args.skip(1)),
+        Some("symex") => symex::main(args.skip(1)),
+        Some("analyze") => analyze::main(args.skip(1)),
+        Some("lint") => lint::main(args.skip(1)),
+        Some("slice") => slice::main(args.skip(1)),
+        Some("reduce") => reduce::main(args.skip(1)),
+        Some("mutate") => mutate::main(args.skip(1)),
+        Some("serve") => serve::main(args.skip(1)),
+        Some("worker") => worker::main(args.skip(1)),
+        #[cfg(feature = "grpc")]
+        Some("grpc") => grpc::main(args.skip(1)),
+        Some("jupyter-kernel") => jupyter::main(args.skip(1)),
+        Some("-h") | Some("--help") => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => run::main(args),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use bfi::BFIError;
+
+    use crate::exit_code;
+
+    #[test]
+    fn test_exit_code() {
+        assert_eq!(exit_code(&BFIError::MissingClosingBrackets), 2);
+        assert_eq!(exit_code(&BFIError::NestingTooDeep), 2);
+        assert_eq!(exit_code(&BFIError::ArithmeticOverflow), 3);
+        assert_eq!(exit_code(&BFIError::MemoryLimitExceeded), 4);
+        assert_eq!(exit_code(&BFIError::Io(io::ErrorKind::NotFound.into())), 5);
+        assert_eq!(exit_code(&BFIError::InvalidAssertion), 2);
+    }
+}
@@ -0,0 +1,244 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! Mutation testing: how many altered programs the cases of a spec catch.
+
+use std::fs;
+use std::io;
+use std::process;
+
+use bfi::BFIError;
+
+use crate::fingerprint::COMMANDS;
+use crate::grade::{self, Case};
+use crate::json::Json;
+use crate::run::{self, Options};
+use crate::symex::position;
+
+const USAGE: &str = "\
+Usage: bfi mutate PROGRAM CASES
+
+Runs the cases of the TOML file CASES, see 'bfi grade --help', on every mutant
+of PROGRAM, which must pass them all, and prints the mutants passing every
+case too, then the share of mutants failing one: the mutation score.
+
+Mutations:
+    flip    Turn a '+' into '-', or a '-' into '+'
+    drop    Remove a command other than a bracket
+    swap    Swap the bodies of two loops in a row
+
+On a case without a step limit, a mutant fails after ten times the steps
+PROGRAM took, plus 1000, so that mutants running forever are caught. Exits
+with status 7, like 'bfi grade', if PROGRAM itself fails a case.";
+
+/// Steps a mutant may take per step of the program, on cases without a limit.
+const STEP_FACTOR: u64 = 10;
+/// Steps a mutant may take on top of those.
+const STEP_MARGIN: u64 = 1000;
+
+/// A program altered by one mutation.
+#[derive(Debug, PartialEq)]
+struct Mutant {
+    description: String,
+    code: Vec<u8>,
+}
+
+/// Mutants of the source `code`, whose commands are `commands` with their
+/// offsets.
+fn mutants(code: &[u8], commands: &[(usize, u8)]) -> Vec<Mutant> {
+    let location = |offset| {
+        let (line, column) = position(code, offset);
+        format!("line {}, column {}", line, column)
+    };
+    let mut mutants = Vec::new();
+    for &(offset, byte) in commands {
+        let flipped: &[u8] = match byte {
+            b'+' => b"-",
+            b'-' => b"+",
+            _ => continue,
+        };
+        mutants.push(Mutant {
+            description: format!("flip '{}' at {}", byte as char, location(offset)),
+            code: [&code[..offset], flipped, &code[offset + 1..]].concat(),
+        });
+    }
+    for &(offset, byte) in commands {
+        if byte != b'[' && byte != b']' {
+            mutants.push(Mutant {
+                description: format!("drop '{}' at {}", byte as char, location(offset)),
+                code: [&code[..offset], &code[offset + 1..]].concat(),
+            });
+        }
+    }
+    // Index of the matching bracket of each bracket.
+    let mut jumps = vec![0; commands.len()];
+    let mut open = Vec::new();
+    for (index, &(_, byte)) in commands.iter().enumerate() {
+        match byte {
+            b'[' => open.push(index),
+            b']' => {
+                if let Some(start) = open.pop() {
+                    jumps[start] = index;
+                    jumps[index] = start;
+                }
+            }
+            _ => (),
+        }
+    }
+    for index in 1..commands.len() {
+        if (commands[index - 1].1, commands[index].1) != (b']', b'[') {
+            continue;
+        }
+        let (first, between, second) = (commands[jumps[index - 1]].0, commands[index - 1].0, commands[index].0);
+        let end = commands[jumps[index]].0;
+        let (body, other) = (&code[first + 1..between], &code[second + 1..end]);
+        if body != other {
+            mutants.push(Mutant {
+                description: format!("swap the bodies of the loops at {} and {}", location(first), location(second)),
+                code: [&code[..=first], other, &code[between..=second], body, &code[end..]].concat(),
+            });
+        }
+    }
+    mutants
+}
+
+/// Runs the mutants of `code` on `cases`, each with its step limit. Returns
+/// the number of mutants failing a case, and those passing them all.
+fn test(code: &[u8], commands: &[(usize, u8)], cases: &[(&Case, u64)]) -> (usize, Vec<Mutant>) {
+    let mut killed = 0;
+    let mut survivors = Vec::new();
+    for mutant in mutants(code, commands) {
+        let source = String::from_utf8_lossy(&mutant.code).into_owned();
+        let caught = cases
+            .iter()
+            .any(|&(case, max_steps)| !grade::succeeded(&grade::grade_code(source.clone(), case, Some(max_steps))));
+        if caught {
+            killed += 1;
+        } else {
+            survivors.push(mutant);
+        }
+    }
+    (killed, survivors)
+}
+
+pub fn main<I: Iterator<Item = String>>(args: I) -> Result<(), BFIError> {
+    let mut files = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            _ if arg.starts_with('-') => crate::usage_error(&format!("Unknown option '{}'", arg), USAGE),
+            _ => files.push(arg),
+        }
+    }
+    let (program, spec) = match files.as_slice() {
+        [program, spec] => (program.as_str(), spec.as_str()),
+        _ => crate::usage_error("Give the program and the cases", USAGE),
+    };
+    let text = fs::read_to_string(spec)?;
+    let cases = grade::parse(&text)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", spec, err)))?;
+    let bfi = run::load(program, &Options::default(), None)?;
+    let code = bfi.code().to_vec();
+    let commands: Vec<(usize, u8)> = bfi.commands().filter(|(_, byte)| COMMANDS.contains(byte)).collect();
+    let source = String::from_utf8_lossy(&code).into_owned();
+    let mut limited = Vec::new();
+    for case in &cases {
+        let result = grade::grade_code(source.clone(), case, None);
+        if !grade::succeeded(&result) {
+            let name = result.get("name").and_then(Json::as_str).unwrap_or_default();
+            eprintln!("{}: {}: the program must pass every case", program, name);
+            process::exit(grade::EXIT_FAILED);
+        }
+        let steps = result.get("steps").and_then(Json::as_u64).unwrap_or_default();
+        limited.push((case, steps.saturating_mul(STEP_FACTOR).saturating_add(STEP_MARGIN)));
+    }
+    let (killed, survivors) = test(&code, &commands, &limited);
+    for survivor in &survivors {
+        println!("{}: survived: {}", program, survivor.description);
+    }
+    let total = killed + survivors.len();
+    if total == 0 {
+        println!("{}: no mutants", program);
+    } else {
+        println!(
+            "{}: {} of {} mutants killed, mutation score {:.1}%",
+            program,
+            killed,
+            total,
+            killed as f64 * 100.0 / total as f64
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bfi::BFI;
+
+    use crate::grade::{self, Case};
+    use crate::mutate::{mutants, test};
+
+    fn commands(code: &str) -> Vec<(usize, u8)> {
+        BFI::new(code.to_string()).commands().collect()
+    }
+
+    #[test]
+    fn test_mutants() {
+        let code = "+[-] [.]";
+        let mutants = mutants(code.as_bytes(), &commands(code));
+        let described: Vec<(&str, String)> = mutants
+            .iter()
+            .map(|mutant| (mutant.description.as_str(), String::from_utf8_lossy(&mutant.code).into_owned()))
+            .collect();
+        assert_eq!(
+            described,
+            vec![
+                ("flip '+' at line 1, column 1", "-[-] [.]".to_string()),
+                ("flip '-' at line 1, column 3", "+[+] [.]".to_string()),
+                ("drop '+' at line 1, column 1", "[-] [.]".to_string()),
+                ("drop '-' at line 1, column 3", "+[] [.]".to_string()),
+                ("drop '.' at line 1, column 7", "+[-] []".to_string()),
+                (
+                    "swap the bodies of the loops at line 1, column 2 and line 1, column 6",
+                    "+[.] [-]".to_string()
+                ),
+            ]
+        );
+    }
+
+    fn limited(cases: &[Case]) -> Vec<(&Case, u64)> {
+        cases.iter().map(|case| (case, 100)).collect()
+    }
+
+    #[test]
+    fn test_test() {
+        // Prints the input plus one; only checking that something is printed
+        // misses the flipped '+'.
+        let code = ",+.";
+        let weak = grade::parse("[[case]]\ninput = \"a\"\n").unwrap();
+        let strict = grade::parse("[[case]]\ninput = \"a\"\noutput = \"b\"\n").unwrap();
+        let (killed, survivors) = test(code.as_bytes(), &commands(code), &limited(&strict));
+        assert_eq!((killed, survivors.len()), (4, 0));
+        let (killed, survivors) = test(code.as_bytes(), &commands(code), &limited(&weak));
+        assert_eq!((killed, survivors.len()), (0, 4));
+    }
+}
@@ -0,0 +1,67 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! Hooks into the execution of a program, command by command.
+
+use bfi::io::{ByteIn, ByteOut};
+use bfi::{BFIError, BFI};
+
+/// Watches the commands executed by `interpret`.
+pub trait Observer {
+    /// Called once the syntax has been checked, before the first command.
+    fn start(&mut self, _bfi: &BFI) {}
+
+    /// Called before executing `command`, the byte at `bfi.offset()`.
+    fn before(&mut self, _bfi: &BFI, _command: u8) {}
+
+    /// Called once `command` has been executed without error.
+    fn after(&mut self, _bfi: &BFI, _command: u8) {}
+}
+
+/// Runs `bfi` like `BFI::interpret`, calling `observers` around each command.
+pub fn interpret(
+    bfi: &mut BFI,
+    reader: &mut dyn ByteIn,
+    writer: &mut dyn ByteOut,
+    observers: &mut [&mut dyn Observer],
+) -> Result<(), BFIError> {
+    bfi.check_syntax()?;
+    for observer in observers.iter_mut() {
+        observer.start(bfi);
+    }
+    let mut result = Ok(());
+    while let Some(offset) = bfi.offset() {
+        let command = bfi.code()[offset];
+        for observer in observers.iter_mut() {
+            observer.before(bfi, command);
+        }
+        if let Err(err) = bfi.step(reader, writer) {
+            result = Err(err);
+            break;
+        }
+        for observer in observers.iter_mut() {
+            observer.after(bfi, command);
+        }
+    }
+    // Flushes on failure as well, keeping the error of the program.
+    if let Err(err) = writer.flush_bytes() {
+        result = result.and(Err(err.into()));
+    }
+    result
+}
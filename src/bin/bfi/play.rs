@@ -0,0 +1,400 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! Animated playback of a program in the terminal, for presentations.
+//!
+//! Each frame shows the source around the next command, highlighted, the
+//! memory, and the output so far. Space pauses and resumes, the right arrow
+//! executes a single command, the up and down arrows double and halve the
+//! speed, and `q` skips to the end.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bfi::symbols::Symbols;
+use bfi::BFI;
+
+use crate::explain;
+use crate::observer::Observer;
+
+/// Lines of the source shown before and after the line of the next command.
+const CONTEXT_LINES: usize = 4;
+const HELP: &str = "space pause, \u{2192} step, \u{2191}/\u{2193} speed, q skip to the end";
+/// Bytes of the output shown under the memory.
+const OUTPUT_TAIL: usize = 60;
+
+/// Commands executed per second, given like `20` or `20ips`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Speed(pub f64);
+
+impl Speed {
+    /// Bounds of the speed, so that the delay between commands stays a valid `Duration`.
+    const MIN: f64 = 0.01;
+    const MAX: f64 = 1e6;
+
+    /// Doubles the speed, up to `MAX`.
+    fn faster(self) -> Self {
+        Speed((self.0 * 2.0).min(Self::MAX))
+    }
+
+    /// Halves the speed, down to `MIN`.
+    fn slower(self) -> Self {
+        Speed((self.0 / 2.0).max(Self::MIN))
+    }
+}
+
+impl Default for Speed {
+    fn default() -> Self {
+        Speed(10.0)
+    }
+}
+
+impl FromStr for Speed {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_suffix("ips").unwrap_or(s).parse() {
+            Ok(speed) if (Self::MIN..=Self::MAX).contains(&speed) => Ok(Speed(speed)),
+            _ => Err("expected 0.01 to 1000000 instructions per second, like 20ips"),
+        }
+    }
+}
+
+impl fmt::Display for Speed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}ips", self.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Space,
+    Right,
+    Up,
+    Down,
+    Quit,
+}
+
+/// Returns the key starting `bytes` read from the terminal, and its length.
+fn parse_key(bytes: &[u8]) -> Option<(Option<Key>, usize)> {
+    match bytes {
+        [] => None,
+        [b' ', ..] => Some((Some(Key::Space), 1)),
+        [b'q', ..] => Some((Some(Key::Quit), 1)),
+        [0x1b] | [0x1b, b'['] => None,
+        [0x1b, b'[', arrow, ..] => Some((
+            match arrow {
+                b'C' => Some(Key::Right),
+                b'A' => Some(Key::Up),
+                b'B' => Some(Key::Down),
+                _ => None,
+            },
+            3,
+        )),
+        _ => Some((None, 1)),
+    }
+}
+
+/// Reads the keys pressed on the terminal on a thread of its own. The
+/// channel is closed if there is no terminal.
+pub fn keys() -> Receiver<Key> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || -> io::Result<()> {
+        let mut tty = File::open("/dev/tty")?;
+        let mut pending = Vec::new();
+        let mut buf = [0; 16];
+        loop {
+            let n = tty.read(&mut buf)?;
+            if n == 0 {
+                return Ok(());
+            }
+            pending.extend_from_slice(&buf[..n]);
+            while let Some((key, len)) = parse_key(&pending) {
+                pending.drain(..len);
+                if let Some(key) = key {
+                    if sender.send(key).is_err() {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    });
+    receiver
+}
+
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(File::open("/dev/tty")?)
+        .stderr(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other("stty failed"));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Passes keys to the program as they are pressed, without echoing them,
+/// until dropped.
+pub struct RawMode {
+    saved: String,
+}
+
+impl RawMode {
+    /// Returns `None` if there is no terminal.
+    pub fn enable() -> Option<Self> {
+        let saved = stty(&["-g"]).ok()?;
+        stty(&["-icanon", "-echo", "min", "1"]).ok()?;
+        Some(Self { saved })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+/// Progress of the run shown by the frames.
+#[derive(Default)]
+pub struct Progress {
+    pub steps: u64,
+    /// Highest cell the pointer has reached; the cells after it are zero.
+    high: usize,
+    output: Vec<u8>,
+    symbols: Symbols,
+}
+
+impl Progress {
+    /// Takes the cell names from the annotations of `bfi`.
+    pub fn start(&mut self, bfi: &BFI) {
+        self.symbols = Symbols::parse(bfi.code());
+    }
+
+    /// Accounts for `command` just executed by `bfi`.
+    pub fn update(&mut self, bfi: &BFI, command: u8) {
+        self.steps += 1;
+        self.high = self.high.max(bfi.pointer());
+        if command == b'.' {
+            self.output.push(bfi.cell(bfi.pointer()).unwrap_or(0) as u8);
+        }
+    }
+
+    pub fn frame(&self, bfi: &BFI, status: &str) -> String {
+        frame(bfi, self.high, &self.output, &self.symbols, status)
+    }
+}
+
+/// Renders a frame before each command and waits for its turn.
+pub struct Player<'a, W: Write> {
+    out: W,
+    keys: Option<&'a Receiver<Key>>,
+    speed: Speed,
+    paused: bool,
+    /// Set once `q` was pressed, to run the rest without rendering.
+    skipped: bool,
+    progress: Progress,
+}
+
+impl<'a, W: Write> Player<'a, W> {
+    pub fn new(out: W, keys: Option<&'a Receiver<Key>>, speed: Speed) -> Self {
+        Self {
+            out,
+            keys,
+            speed,
+            paused: false,
+            skipped: false,
+            progress: Progress::default(),
+        }
+    }
+
+    /// Renders the state at the end of the program.
+    pub fn finish(&mut self, bfi: &BFI) {
+        self.skipped = false;
+        self.render(bfi, "end");
+    }
+
+    fn render(&mut self, bfi: &BFI, state: &str) {
+        if self.skipped {
+            return;
+        }
+        let status = format!("{} steps, {}, {}", self.progress.steps, self.speed, state);
+        let frame = self.progress.frame(bfi, &status);
+        // Playback is best effort and never stops the program.
+        let _ = writeln!(self.out, "\x1b[H\x1b[2J{}{}", frame, HELP);
+        let _ = self.out.flush();
+    }
+
+    /// Waits until the next command is due, handling the keys pressed meanwhile.
+    fn wait(&mut self, bfi: &BFI) {
+        let mut due = Instant::now() + Duration::from_secs_f64(1.0 / self.speed.0);
+        loop {
+            let keys = match self.keys {
+                Some(keys) => keys,
+                None => {
+                    thread::sleep(due.saturating_duration_since(Instant::now()));
+                    return;
+                }
+            };
+            let key = if self.paused {
+                keys.recv().map_err(|_| RecvTimeoutError::Disconnected)
+            } else {
+                keys.recv_timeout(due.saturating_duration_since(Instant::now()))
+            };
+            match key {
+                Ok(Key::Space) => {
+                    self.paused = !self.paused;
+                    due = Instant::now();
+                    let state = if self.paused { "paused" } else { "playing" };
+                    self.render(bfi, state);
+                }
+                Ok(Key::Right) => {
+                    self.paused = true;
+                    return;
+                }
+                Ok(Key::Up) => self.speed = self.speed.faster(),
+                Ok(Key::Down) => self.speed = self.speed.slower(),
+                Ok(Key::Quit) => {
+                    self.skipped = true;
+                    return;
+                }
+                Err(RecvTimeoutError::Timeout) => return,
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.keys = None;
+                    self.paused = false;
+                }
+            }
+            if !self.paused && Instant::now() >= due {
+                return;
+            }
+        }
+    }
+}
+
+impl<W: Write> Observer for Player<'_, W> {
+    fn start(&mut self, bfi: &BFI) {
+        self.progress.start(bfi);
+    }
+
+    fn before(&mut self, bfi: &BFI, _command: u8) {
+        if self.skipped {
+            return;
+        }
+        let state = if self.paused { "paused" } else { "playing" };
+        self.render(bfi, state);
+        self.wait(bfi);
+    }
+
+    fn after(&mut self, bfi: &BFI, command: u8) {
+        self.progress.update(bfi, command);
+    }
+}
+
+/// Formats the lines of the source around the next command, shown in
+/// reverse video, followed by the memory, the output, and `status`.
+fn frame(bfi: &BFI, high: usize, output: &[u8], symbols: &Symbols, status: &str) -> String {
+    let code = bfi.code();
+    let offset = bfi.offset().unwrap_or(code.len());
+    let line = code[..offset].iter().filter(|&&byte| byte == b'\n').count();
+    let mut text = String::new();
+    let mut start = 0;
+    for (i, source_line) in code.split(|&byte| byte == b'\n').enumerate() {
+        let end = start + source_line.len();
+        if i + CONTEXT_LINES >= line && i <= line + CONTEXT_LINES {
+            if (start..end).contains(&offset) {
+                let column = offset - start;
+                text += &String::from_utf8_lossy(&source_line[..column]);
+                text += "\x1b[7m";
+                text += &String::from_utf8_lossy(&source_line[column..column + 1]);
+                text += "\x1b[0m";
+                text += &String::from_utf8_lossy(&source_line[column + 1..]);
+            } else {
+                text += &String::from_utf8_lossy(source_line);
+            }
+            text.push('\n');
+        }
+        start = end + 1;
+    }
+    let tail = &output[output.len().saturating_sub(OUTPUT_TAIL)..];
+    format!(
+        "{}\nMemory: {}\nOutput: {}\n{}\n",
+        text,
+        explain::tape(bfi, high, symbols),
+        String::from_utf8_lossy(tail).escape_debug(),
+        status
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use bfi::symbols::Symbols;
+    use bfi::BFI;
+
+    use crate::play::{frame, parse_key, Key, Speed};
+
+    #[test]
+    fn test_speed() {
+        assert_eq!("20ips".parse(), Ok(Speed(20.0)));
+        assert_eq!("0.5".parse(), Ok(Speed(0.5)));
+        assert!("0ips".parse::<Speed>().is_err());
+        assert!("fast".parse::<Speed>().is_err());
+        assert!("inf".parse::<Speed>().is_err());
+        assert!("1e-300".parse::<Speed>().is_err());
+        assert!("1e7ips".parse::<Speed>().is_err());
+        let slowest = (0..2000).fold(Speed::default(), |speed, _| speed.slower());
+        assert_eq!(slowest, Speed(0.01));
+        let fastest = (0..2000).fold(Speed::default(), |speed, _| speed.faster());
+        assert_eq!(fastest, Speed(1e6));
+        assert_eq!(Speed(20.0).to_string(), "20ips");
+    }
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(parse_key(b" x"), Some((Some(Key::Space), 1)));
+        assert_eq!(parse_key(b"\x1b[C"), Some((Some(Key::Right), 3)));
+        assert_eq!(parse_key(b"\x1b[Dq"), Some((None, 3)));
+        assert_eq!(parse_key(b"\x1b["), None);
+        assert_eq!(parse_key(b"x"), Some((None, 1)));
+        assert_eq!(parse_key(b""), None);
+    }
+
+    #[test]
+    fn test_frame() {
+        let mut bfi = BFI::new("+\n+>\n.".to_string());
+        bfi.step(&mut Cursor::new(Vec::new()), &mut Vec::new()).unwrap();
+        bfi.step(&mut Cursor::new(Vec::new()), &mut Vec::new()).unwrap();
+        assert_eq!(
+            frame(&bfi, 0, b"a\n", &Symbols::default(), "2 steps"),
+            "+\n+\x1b[7m>\x1b[0m\n.\n\nMemory: [2]\nOutput: a\\n\n2 steps\n"
+        );
+
+        // Shows only the lines around the next command.
+        let bfi = BFI::new("\n".repeat(10) + "+");
+        let text = frame(&bfi, 0, b"", &Symbols::default(), "");
+        assert!(text.starts_with("\n\n\n\n\x1b[7m+\x1b[0m\n\n"));
+    }
+}
@@ -0,0 +1,265 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! Dialect plugins loaded at run time through the ABI of `include/bfi_plugin.h`.
+
+#![cfg_attr(not(feature = "plugins"), allow(dead_code))]
+
+use std::ffi::CStr;
+use std::fmt;
+use std::io;
+use std::os::raw::c_char;
+use std::sync::Arc;
+
+use bfi::BFIError;
+use bfi::Extension;
+
+pub const ABI_VERSION: u32 = 1;
+
+/// `BfiPluginV1` of `include/bfi_plugin.h`.
+#[repr(C)]
+pub struct PluginV1 {
+    pub abi_version: u32,
+    pub name: *const c_char,
+    pub translate: Option<unsafe extern "C" fn(*const u8, usize, *mut u8, usize) -> isize>,
+    pub commands: *const c_char,
+    pub execute: Option<unsafe extern "C" fn(u8, *mut i8) -> i32>,
+}
+
+#[cfg(feature = "plugins")]
+type Library = libloading::Library;
+#[cfg(not(feature = "plugins"))]
+type Library = ();
+
+/// A plugin, used both to translate programs and as their `Extension`.
+#[derive(Clone)]
+pub struct Plugin {
+    name: String,
+    translate: Option<unsafe extern "C" fn(*const u8, usize, *mut u8, usize) -> isize>,
+    commands: Vec<u8>,
+    execute: Option<unsafe extern "C" fn(u8, *mut i8) -> i32>,
+    // Keeps the functions above loaded.
+    _library: Option<Arc<Library>>,
+}
+
+// The functions of the ABI are required to be callable from any thread.
+unsafe impl Send for Plugin {}
+
+impl fmt::Debug for Plugin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Plugin").field("name", &self.name).finish()
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl Plugin {
+    /// Reads the vtable returned by `bfi_plugin_entry`.
+    ///
+    /// # Safety
+    ///
+    /// `vtable` must be null or point to a vtable valid as long as `library`.
+    pub unsafe fn new(vtable: *const PluginV1, library: Option<Arc<Library>>) -> io::Result<Self> {
+        let vtable = vtable
+            .as_ref()
+            .ok_or_else(|| invalid("plugin returned no vtable".to_string()))?;
+        if vtable.abi_version != ABI_VERSION {
+            return Err(invalid(format!(
+                "plugin implements ABI version {} instead of {}",
+                vtable.abi_version, ABI_VERSION
+            )));
+        }
+        let string = |s: *const c_char| {
+            if s.is_null() {
+                Vec::new()
+            } else {
+                CStr::from_ptr(s).to_bytes().to_vec()
+            }
+        };
+        Ok(Self {
+            name: String::from_utf8_lossy(&string(vtable.name)).into_owned(),
+            translate: vtable.translate,
+            commands: string(vtable.commands)
+                .into_iter()
+                .filter(u8::is_ascii)
+                .collect(),
+            execute: vtable.execute,
+            _library: library,
+        })
+    }
+
+    /// Translates a program of the dialect into standard brainfuck.
+    pub fn translate(&self, code: &str) -> io::Result<String> {
+        let translate = match self.translate {
+            Some(translate) => translate,
+            None => return Ok(code.to_string()),
+        };
+        let mut out = Vec::with_capacity(code.len());
+        // The second call gets the capacity asked for by the first one.
+        for _ in 0..2 {
+            let len =
+                unsafe { translate(code.as_ptr(), code.len(), out.as_mut_ptr(), out.capacity()) };
+            if len < 0 {
+                return Err(invalid(format!("{}: invalid program", self.name)));
+            }
+            let len = len as usize;
+            if len <= out.capacity() {
+                unsafe { out.set_len(len) };
+                return String::from_utf8(out)
+                    .map_err(|_| invalid(format!("{}: translation is not UTF-8", self.name)));
+            }
+            out.reserve_exact(len);
+        }
+        Err(invalid(format!("{}: translation keeps growing", self.name)))
+    }
+}
+
+impl Extension for Plugin {
+    fn handles(&self, command: u8) -> bool {
+        self.execute.is_some() && self.commands.contains(&command)
+    }
+
+    fn execute(&mut self, command: u8, cell: &mut i8) -> Result<(), BFIError> {
+        let execute = self.execute.expect("handles() is false without execute");
+        if unsafe { execute(command, cell) } < 0 {
+            Err(BFIError::Io(io::Error::other(format!(
+                "{}: command '{}' failed",
+                self.name, command as char
+            ))))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The state of the library, if any, is shared by the forks.
+    fn fork(&self) -> Option<Box<dyn Extension>> {
+        Some(Box::new(self.clone()))
+    }
+}
+
+#[cfg(feature = "plugins")]
+pub fn load(path: &str) -> io::Result<Plugin> {
+    // The errors of the dynamic loader name the library, as the caller does.
+    let prefix = format!("{}: ", path);
+    let error = |err: libloading::Error| {
+        let message = err.to_string();
+        io::Error::other(message.strip_prefix(&prefix).unwrap_or(&message).to_string())
+    };
+    unsafe {
+        let library = Arc::new(libloading::Library::new(path).map_err(error)?);
+        let vtable = {
+            let entry = library
+                .get::<unsafe extern "C" fn(u32) -> *const PluginV1>(b"bfi_plugin_entry\0")
+                .map_err(error)?;
+            entry(ABI_VERSION)
+        };
+        Plugin::new(vtable, Some(library))
+    }
+}
+
+#[cfg(not(feature = "plugins"))]
+pub fn load(_path: &str) -> io::Result<Plugin> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "bfi was built without the plugins feature",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::os::raw::c_char;
+    use std::slice;
+
+    use bfi::BFIError;
+    use bfi::BFI;
+
+    use crate::plugin::{Plugin, PluginV1, ABI_VERSION};
+
+    /// Translates `i` into `+` and `o` into `.`, keeping `*`.
+    unsafe extern "C" fn translate(
+        code: *const u8,
+        len: usize,
+        out: *mut u8,
+        capacity: usize,
+    ) -> isize {
+        let translation: Vec<u8> = slice::from_raw_parts(code, len)
+            .iter()
+            .filter_map(|c| match c {
+                b'i' => Some(b'+'),
+                b'o' => Some(b'.'),
+                b'*' => Some(b'*'),
+                _ => None,
+            })
+            .collect();
+        if translation.len() <= capacity {
+            out.copy_from_nonoverlapping(translation.as_ptr(), translation.len());
+        }
+        translation.len() as isize
+    }
+
+    /// Doubles the cell on `*`.
+    unsafe extern "C" fn execute(_command: u8, cell: *mut i8) -> i32 {
+        match (*cell).checked_mul(2) {
+            Some(value) => {
+                *cell = value;
+                0
+            }
+            None => -1,
+        }
+    }
+
+    fn vtable(abi_version: u32) -> *const PluginV1 {
+        Box::leak(Box::new(PluginV1 {
+            abi_version,
+            name: b"test\0".as_ptr() as *const c_char,
+            translate: Some(translate),
+            commands: b"*\0".as_ptr() as *const c_char,
+            execute: Some(execute),
+        }))
+    }
+
+    #[test]
+    fn test_plugin() {
+        let plugin = unsafe { Plugin::new(vtable(ABI_VERSION), None) }.unwrap();
+        assert_eq!(plugin.name, "test");
+        let code = plugin.translate("iii, **o").unwrap();
+        assert_eq!(code, "+++**.");
+
+        let mut bfi = BFI::new(code);
+        bfi.set_extension(Box::new(plugin.clone()));
+        let mut output = Vec::new();
+        bfi.interpret(&mut Cursor::new(Vec::new()), &mut output)
+            .unwrap();
+        assert_eq!(output, [12]);
+
+        let mut bfi = BFI::new("+*******".to_string());
+        bfi.set_extension(Box::new(plugin));
+        let err = bfi.interpret(&mut Cursor::new(Vec::new()), &mut Vec::new());
+        assert!(matches!(err, Err(BFIError::Io(_))));
+    }
+
+    #[test]
+    fn test_abi_version() {
+        assert!(unsafe { Plugin::new(vtable(ABI_VERSION + 1), None) }.is_err());
+        assert!(unsafe { Plugin::new(std::ptr::null(), None) }.is_err());
+    }
+}
@@ -0,0 +1,264 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! Delta debugging of programs that make a check fail.
+
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::process::{self, Command, Stdio};
+
+use bfi::BFIError;
+
+use crate::fingerprint::COMMANDS;
+
+const USAGE: &str = "\
+Usage: bfi reduce --check COMMAND FILE
+
+Removes spans of FILE, keeping its brackets balanced, for as long as the shell
+COMMAND still fails on what is left, and prints the smallest program found.
+COMMAND is run with each '{}' replaced by the path of the candidate, e.g.
+--check 'bfi {} | cmp -s - expected.txt'.
+
+Options:
+    --check COMMAND  Command that fails on the program and on its reductions";
+
+/// Whether `code` has as many `[` as `]`, none of them unmatched.
+fn balanced(code: &[u8]) -> bool {
+    let mut depth = 0usize;
+    for &byte in code {
+        match byte {
+            b'[' => depth += 1,
+            b']' if depth == 0 => return false,
+            b']' => depth -= 1,
+            _ => (),
+        }
+    }
+    depth == 0
+}
+
+/// Removes balanced spans of `code`, halving their length down to single
+/// bytes, while `fails` holds. Returns whether anything was removed.
+fn remove_spans<F: FnMut(&[u8]) -> bool>(code: &mut Vec<u8>, fails: &mut F) -> bool {
+    let mut removed = false;
+    let mut size = (code.len() / 2).max(1);
+    loop {
+        let mut reduced = false;
+        let mut start = 0;
+        while start < code.len() {
+            let end = (start + size).min(code.len());
+            if balanced(&code[start..end]) {
+                let candidate = [&code[..start], &code[end..]].concat();
+                if fails(&candidate) {
+                    *code = candidate;
+                    reduced = true;
+                    continue;
+                }
+            }
+            start += size;
+        }
+        removed |= reduced;
+        if reduced {
+            size = size.min(code.len() / 2).max(1);
+        } else if size == 1 {
+            return removed;
+        } else {
+            size /= 2;
+        }
+    }
+}
+
+/// Replaces loops with their bodies while `fails` holds. Returns whether
+/// anything was removed.
+fn unwrap_loops<F: FnMut(&[u8]) -> bool>(code: &mut Vec<u8>, fails: &mut F) -> bool {
+    let mut removed = false;
+    let mut start = 0;
+    while start < code.len() {
+        if code[start] == b'[' {
+            let mut depth = 0;
+            let end = (start..code.len()).find(|&i| {
+                match code[i] {
+                    b'[' => depth += 1,
+                    b']' => depth -= 1,
+                    _ => (),
+                }
+                depth == 0
+            });
+            if let Some(end) = end {
+                let candidate = [&code[..start], &code[start + 1..end], &code[end + 1..]].concat();
+                if fails(&candidate) {
+                    *code = candidate;
+                    removed = true;
+                    continue;
+                }
+            }
+        }
+        start += 1;
+    }
+    removed
+}
+
+/// Reduces `code`, on which `fails` holds, to a program on which it still
+/// holds and from which no span or loop can be removed.
+fn reduce<F: FnMut(&[u8]) -> bool>(code: &[u8], mut fails: F) -> Vec<u8> {
+    // Without comments first, which is most of many programs.
+    let stripped: Vec<u8> = code.iter().copied().filter(|byte| COMMANDS.contains(byte)).collect();
+    let mut code = if stripped.len() < code.len() && fails(&stripped) {
+        stripped
+    } else {
+        code.to_vec()
+    };
+    while remove_spans(&mut code, &mut fails) | unwrap_loops(&mut code, &mut fails) {}
+    code
+}
+
+/// Quotes `text` as a single word of the shell.
+fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+/// Creates a new directory that only the user can access, so that nobody
+/// else can replace or read the candidates written into it.
+fn private_dir() -> io::Result<PathBuf> {
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    let mut attempt = 0;
+    loop {
+        let dir = env::temp_dir().join(format!("bfi-reduce-{}-{}", process::id(), attempt));
+        match builder.create(&dir) {
+            Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists && attempt < 100 => attempt += 1,
+            result => return result.map(|()| dir),
+        }
+    }
+}
+
+/// Whether the shell `check` fails on the program `code`, written to `file`
+/// whose quoted path is `path`.
+fn check_fails(check: &str, file: &mut File, path: &str, code: &[u8]) -> io::Result<bool> {
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(code)?;
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(check.replace("{}", path))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+    Ok(!status.success())
+}
+
+pub fn main<I: Iterator<Item = String>>(args: I) -> Result<(), BFIError> {
+    let mut args = args;
+    let mut check = None;
+    let mut file = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            "--check" => check = Some(crate::option_value::<String>(&arg, args.next(), USAGE)),
+            _ if arg.starts_with('-') => crate::usage_error(&format!("Unknown option '{}'", arg), USAGE),
+            _ if file.is_none() => file = Some(arg),
+            _ => crate::usage_error("Give a single program", USAGE),
+        }
+    }
+    let file = file.unwrap_or_else(|| crate::usage_error("Give the program", USAGE));
+    let check = match check {
+        Some(check) if check.contains("{}") => check,
+        Some(_) => crate::usage_error("The --check command must contain '{}'", USAGE),
+        None => crate::usage_error("Give the --check command", USAGE),
+    };
+    let code = fs::read(&file)?;
+    let dir = private_dir()?;
+    let path = dir.join("candidate.b");
+    let mut candidate_file = match OpenOptions::new().write(true).create_new(true).open(&path) {
+        Ok(file) => file,
+        Err(err) => {
+            let _ = fs::remove_dir(&dir);
+            return Err(err.into());
+        }
+    };
+    let path = shell_quote(&path.display().to_string());
+    let mut checks = 0;
+    let mut error = None;
+    let mut fails = |candidate: &[u8]| {
+        checks += 1;
+        check_fails(&check, &mut candidate_file, &path, candidate).unwrap_or_else(|err| {
+            error.get_or_insert(err);
+            false
+        })
+    };
+    let original = fails(&code);
+    let reduced = if original { reduce(&code, &mut fails) } else { Vec::new() };
+    let _ = fs::remove_dir_all(&dir);
+    if let Some(err) = error {
+        return Err(err.into());
+    }
+    if !original {
+        let message = format!("{}: the check does not fail on the program", file);
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, message).into());
+    }
+    eprintln!("{}: {} of {} bytes left after {} checks", file, reduced.len(), code.len(), checks);
+    println!("{}", String::from_utf8_lossy(&reduced));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+
+    use crate::reduce::{balanced, check_fails, private_dir, reduce, shell_quote};
+
+    #[test]
+    fn test_balanced() {
+        assert!(balanced(b"+[-[.]]"));
+        assert!(!balanced(b"]["));
+        assert!(!balanced(b"[[]"));
+    }
+
+    #[test]
+    fn test_reduce() {
+        let code = b"Read: ,>,< Loop: [->+<] Print: >.".to_vec();
+        let fails = |code: &[u8]| code.contains(&b',') && code.contains(&b'[') && balanced(code);
+        let reduced = reduce(&code, fails);
+        assert_eq!(reduced.len(), 3);
+        assert!(fails(&reduced));
+
+        // Only unwrapping the loops leaves both commands.
+        let reduced = reduce(b"[,[.]]", |code: &[u8]| code.contains(&b',') && code.contains(&b'.'));
+        assert_eq!(reduced, b",.");
+    }
+
+    #[test]
+    fn test_check_fails() {
+        assert_eq!(shell_quote("a b'c"), "'a b'\\''c'");
+
+        let dir = private_dir().unwrap();
+        let path = dir.join("it's here.b");
+        let mut file = File::create(&path).unwrap();
+        let quoted = shell_quote(&path.display().to_string());
+        assert!(!check_fails("grep -q , {}", &mut file, &quoted, b"+,.").unwrap());
+        assert!(check_fails("grep -q , {}", &mut file, &quoted, b"+.").unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,1152 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! `bfi run`: executes program files over stdin and stdout.
+
+use std::cell::{Cell, RefCell};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::num::NonZeroU64;
+use std::process;
+use std::str::FromStr;
+use std::time::Duration;
+
+use bfi::io::{ByteIn, ByteOut};
+use bfi::link::{LinkError, Network, Wait};
+use bfi::BFIError;
+use bfi::EofPolicy;
+use bfi::Limits;
+use bfi::Strictness;
+use bfi::SyntaxIssue;
+use bfi::BFI;
+
+use crate::base64;
+use crate::cast::Recorder;
+use crate::config;
+use crate::diff::DiffEvery;
+use crate::digest::ExecutionDigest;
+use crate::explain::Explainer;
+use crate::fetch::{self, Limited};
+use crate::fingerprint::Fingerprint;
+use crate::json::Json;
+use crate::observer::{self, Observer};
+use crate::play::{self, Player, RawMode, Speed};
+use crate::plugin::{self, Plugin};
+use crate::sandbox;
+use crate::serve;
+use crate::sha256::hex;
+use crate::symex::position;
+
+pub const USAGE: &str = "\
+Usage: bfi [run] [OPTIONS] FILE...
+
+FILE and the --input SOURCE may be http:// or https:// URLs when bfi is built
+with the http feature and --allow-net is given.
+
+Defaults are read from ./bfi.toml, or else from $XDG_CONFIG_HOME/bfi/bfi.toml.
+
+Options:
+    --profile NAME        Apply a preset of the options below; later options
+                          still override it:
+                          strict   30000 cells, EOF 0, and the limits of
+                                   --sandbox but the time
+                          lenient  memory growing up to 16777216 cells,
+                                   EOF 0, no limits
+                          classic  30000 cells, EOF unchanged, no limits,
+                                   like the original interpreter
+                          standard wrapping cells, memory growing up to
+                                   16777216 cells, EOF unchanged, no
+                                   limits, as most programs expect
+    --standard            Same as --profile standard
+    --wrap                Wrap cells around from 255 to 0 and back instead of
+                          failing on overflow
+    --filter              Stream for use in pipelines: flush the output
+                          whenever the program waits for input
+    --eof POLICY          Value stored by ',' at the end of input:
+                          zero (default), minus-one, or unchanged
+    --json                Print a single JSON object with the status, the
+                          error, the stats, and the output of each program,
+                          instead of the output; forbids --filter and --play
+    --pipe-errors         Fail once the output pipe is closed instead of
+                          stopping quietly
+    --keep-going          Run the remaining files after one has failed, and
+                          exit with the status of the first failure
+    --link FROM:TO[:CAPACITY]
+                          Run the files together, feeding the output of the
+                          FROM-th to the input of the TO-th through a
+                          channel of CAPACITY bytes (default: 4096); the
+                          files without incoming link read the input, and
+                          those without outgoing link write the output
+    --deterministic       Reject programs using ',', so that the output
+                          depends only on the program; forbids --plugin
+    --strict              Reject commands between two words, like the ','
+                          of \"Hello, world\", and with --plugin, punctuation
+                          the plugin does not handle
+    --pure                Like --strict, and also reject everything but
+                          commands and whitespace
+    --fingerprint         Print to stderr the hash of each program stripped of
+                          comments, its size, and its number of commands
+    --digest              Print to stderr, after each program, a hash of the
+                          executed commands, the bytes they read and wrote,
+                          and of the final memory, to compare runs
+    --explain             Print to stderr what each command did and the
+                          memory after it, for small programs; at most
+                          10000 steps unless --max-steps is given
+    --diff-every N        Print to stderr every N steps the cells that changed
+                          since the previous time, with their old and new
+                          values
+    --play                Animate the source and the memory on stderr,
+                          writing the output at the end: space pauses,
+                          the right arrow steps, the up and down arrows
+                          change the speed, and q skips to the end
+    --speed SPEED         Commands per second of --play and --record-cast,
+                          like 20ips, from 0.01 to 1000000 (default: 10ips)
+    --record-cast FILE    Record the frames of --play to FILE in the
+                          asciinema format, without waiting
+    --input SOURCE        Read the input from SOURCE instead of stdin
+    --stdin-string TEXT   Use TEXT as the input, with the escapes \\n, \\r,
+                          \\t, \\0, \\\\, and \\xHH
+    --max-input BYTES     Fail once the input exceeds BYTES
+    --allow-net           Allow fetching programs and input from URLs
+    --max-download BYTES  Maximum size of each download (default: 1048576)
+    --max-steps N         Maximum commands executed by each program
+    --max-output BYTES    Maximum bytes written by each program
+    --tape-size N         Number of memory cells, at most 32768
+    --max-cells N         Grow the memory on demand up to N cells
+    --max-depth N         Maximum nesting depth of loops (default: 1024)
+    --load-tape FILE      Copy the bytes of FILE into the first cells of the
+                          memory before each program runs
+    --save-tape FILE      Write every cell of the memory to FILE after each
+                          program that ends without error
+    --max-time SECONDS    Abort once SECONDS of wall-clock time have passed
+    --plugin LIBRARY      Load a dialect plugin (plugins feature)
+    --sandbox             Run untrusted programs: at most 10000000 steps,
+                          30000 cells, 1048576 bytes of output and 10
+                          seconds, without plugins or URLs, and under
+                          seccomp on Linux (seccomp feature)";
+
+/// Presets of `--profile`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Profile {
+    Strict,
+    Lenient,
+    Classic,
+    Standard,
+}
+
+/// Cells of the memory of the original interpreter.
+const CLASSIC_CELLS: usize = 30_000;
+/// Ceiling of the growable memory of the lenient profile.
+const LENIENT_MAX_CELLS: usize = 1 << 24;
+
+impl FromStr for Profile {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "strict" => Ok(Profile::Strict),
+            "lenient" => Ok(Profile::Lenient),
+            "classic" => Ok(Profile::Classic),
+            "standard" => Ok(Profile::Standard),
+            _ => Err("expected strict, lenient, classic, or standard"),
+        }
+    }
+}
+
+impl Profile {
+    /// Overrides the memory, cell, EOF, and limit options of `options`.
+    pub fn apply(self, options: &mut Options) {
+        let (eof, limits, growable) = match self {
+            Profile::Strict => (EofPolicy::Zero, sandbox::LIMITS, false),
+            Profile::Standard => (
+                EofPolicy::Unchanged,
+                Limits {
+                    max_cells: Some(LENIENT_MAX_CELLS),
+                    ..Limits::default()
+                },
+                true,
+            ),
+            Profile::Lenient => (
+                EofPolicy::Zero,
+                Limits {
+                    max_cells: Some(LENIENT_MAX_CELLS),
+                    ..Limits::default()
+                },
+                true,
+            ),
+            Profile::Classic => (
+                EofPolicy::Unchanged,
+                Limits {
+                    max_cells: Some(CLASSIC_CELLS),
+                    ..Limits::default()
+                },
+                false,
+            ),
+        };
+        options.eof = eof;
+        options.limits = limits;
+        options.growable = growable;
+        options.wrapping = self == Profile::Standard;
+    }
+}
+
+/// Default of `--max-steps` with `--explain`, which prints a line per step.
+const EXPLAIN_MAX_STEPS: u64 = 10_000;
+
+/// Default of `--max-download`.
+const DEFAULT_MAX_DOWNLOAD: u64 = 1024 * 1024;
+
+/// Version of the object printed by `--json`, raised on incompatible changes.
+pub const JSON_SCHEMA: u64 = 1;
+
+/// Default capacity of the channels of `--link`.
+const DEFAULT_LINK_CAPACITY: usize = 4096;
+
+/// A channel of `--link`, between 0-based indices of the files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Link {
+    pub from: usize,
+    pub to: usize,
+    pub capacity: usize,
+}
+
+impl FromStr for Link {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split(':').collect();
+        let number = |field: &str| field.parse::<usize>().ok().filter(|&n| n > 0);
+        let link = match *fields.as_slice() {
+            [from, to] => (number(from), number(to), Some(DEFAULT_LINK_CAPACITY)),
+            [from, to, capacity] => (number(from), number(to), number(capacity)),
+            _ => (None, None, None),
+        };
+        match link {
+            (Some(from), Some(to), Some(capacity)) => Ok(Link {
+                from: from - 1,
+                to: to - 1,
+                capacity,
+            }),
+            _ => Err("expected FROM:TO or FROM:TO:CAPACITY, with the positions of the files from 1"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    pub filter: bool,
+    pub eof: EofPolicy,
+    pub json: bool,
+    pub pipe_errors: bool,
+    pub keep_going: bool,
+    pub links: Vec<Link>,
+    pub deterministic: bool,
+    pub strictness: Strictness,
+    pub fingerprint: bool,
+    pub digest: bool,
+    pub explain: bool,
+    pub diff_every: Option<NonZeroU64>,
+    pub play: bool,
+    pub speed: Speed,
+    pub record_cast: Option<String>,
+    pub limits: Limits,
+    pub growable: bool,
+    pub wrapping: bool,
+    pub max_depth: usize,
+    pub max_time: Option<Duration>,
+    pub input: Option<String>,
+    pub stdin_string: Option<Vec<u8>>,
+    pub max_input: Option<u64>,
+    pub load_tape: Option<String>,
+    pub save_tape: Option<String>,
+    pub allow_net: bool,
+    pub max_download: u64,
+    pub plugin: Option<String>,
+    pub sandbox: bool,
+    pub files: Vec<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            filter: false,
+            eof: EofPolicy::default(),
+            json: false,
+            pipe_errors: false,
+            keep_going: false,
+            links: Vec::new(),
+            deterministic: false,
+            strictness: Strictness::default(),
+            fingerprint: false,
+            digest: false,
+            explain: false,
+            diff_every: None,
+            play: false,
+            speed: Speed::default(),
+            record_cast: None,
+            limits: Limits::default(),
+            growable: false,
+            wrapping: false,
+            max_depth: bfi::DEFAULT_MAX_DEPTH,
+            max_time: None,
+            input: None,
+            stdin_string: None,
+            max_input: None,
+            load_tape: None,
+            save_tape: None,
+            allow_net: false,
+            max_download: DEFAULT_MAX_DOWNLOAD,
+            plugin: None,
+            sandbox: false,
+            files: Vec::new(),
+        }
+    }
+}
+
+/// Parses the command line, overriding the defaults of `options`.
+pub fn parse_options<I: Iterator<Item = String>>(mut args: I, mut options: Options) -> Options {
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--profile" => crate::option_value::<Profile>(&arg, args.next(), USAGE).apply(&mut options),
+            "--standard" => Profile::Standard.apply(&mut options),
+            "--wrap" => options.wrapping = true,
+            "--filter" => options.filter = true,
+            "--eof" => options.eof = crate::option_value(&arg, args.next(), USAGE),
+            "--json" => options.json = true,
+            "--pipe-errors" => options.pipe_errors = true,
+            "--keep-going" => options.keep_going = true,
+            "--link" => options.links.push(crate::option_value(&arg, args.next(), USAGE)),
+            "--deterministic" => options.deterministic = true,
+            "--strict" => options.strictness = options.strictness.max(Strictness::Strict),
+            "--pure" => options.strictness = Strictness::Pure,
+            "--fingerprint" => options.fingerprint = true,
+            "--digest" => options.digest = true,
+            "--explain" => options.explain = true,
+            "--diff-every" => options.diff_every = Some(crate::option_value(&arg, args.next(), USAGE)),
+            "--play" => options.play = true,
+            "--speed" => options.speed = crate::option_value(&arg, args.next(), USAGE),
+            "--record-cast" => options.record_cast = Some(crate::option_value(&arg, args.next(), USAGE)),
+            "--input" => options.input = Some(crate::option_value(&arg, args.next(), USAGE)),
+            "--stdin-string" => {
+                let text: String = crate::option_value(&arg, args.next(), USAGE);
+                let input = unescape(&text).unwrap_or_else(|err| {
+                    crate::usage_error(&format!("Invalid value for --stdin-string: {}", err), USAGE)
+                });
+                options.stdin_string = Some(input);
+            }
+            "--max-input" => options.max_input = Some(crate::option_value(&arg, args.next(), USAGE)),
+            "--allow-net" => options.allow_net = true,
+            "--max-download" => {
+                options.max_download = crate::option_value(&arg, args.next(), USAGE)
+            }
+            "--plugin" => options.plugin = Some(crate::option_value(&arg, args.next(), USAGE)),
+            "--sandbox" => options.sandbox = true,
+            "--max-steps" => {
+                options.limits.max_steps = Some(crate::option_value(&arg, args.next(), USAGE))
+            }
+            "--max-output" => {
+                options.limits.max_output = Some(crate::option_value(&arg, args.next(), USAGE))
+            }
+            "--tape-size" => {
+                options.limits.max_cells = Some(crate::option_value(&arg, args.next(), USAGE));
+                options.growable = false;
+            }
+            "--max-cells" => {
+                options.limits.max_cells = Some(crate::option_value(&arg, args.next(), USAGE));
+                options.growable = true;
+            }
+            "--max-depth" => options.max_depth = crate::option_value(&arg, args.next(), USAGE),
+            "--load-tape" => options.load_tape = Some(crate::option_value(&arg, args.next(), USAGE)),
+            "--save-tape" => options.save_tape = Some(crate::option_value(&arg, args.next(), USAGE)),
+            "--max-time" => {
+                options.max_time = Some(Duration::from_secs(crate::option_value(
+                    &arg,
+                    args.next(),
+                    USAGE,
+                )))
+            }
+            "--" => options.files.extend(&mut args),
+            _ if arg.starts_with("--") => {
+                crate::usage_error(&format!("Unknown option '{}'", arg), USAGE)
+            }
+            _ => options.files.push(arg),
+        }
+    }
+    options
+}
+
+/// Output of `--filter`: buffered, but flushed whenever the program blocks
+/// on input so that the next stage of the pipeline is never kept waiting.
+pub struct FilterOutput<'a, W: Write>(pub &'a RefCell<BufWriter<W>>);
+
+impl<W: Write> ByteOut for FilterOutput<'_, W> {
+    fn write_byte(&mut self, byte: u8) -> Result<(), io::Error> {
+        self.0.borrow_mut().write_all(&[byte])
+    }
+
+    fn flush_bytes(&mut self) -> Result<(), io::Error> {
+        self.0.borrow_mut().flush()
+    }
+}
+
+/// Input of `--filter`. The output is flushed before reading would block,
+/// i.e. when nothing buffered is left.
+pub struct FilterInput<'a, R: Read, W: Write> {
+    pub inner: &'a mut Limited<BufReader<R>>,
+    pub output: &'a RefCell<BufWriter<W>>,
+}
+
+impl<R: Read, W: Write> ByteIn for FilterInput<'_, R, W> {
+    fn read_byte(&mut self) -> Result<Option<u8>, io::Error> {
+        if self.inner.get_ref().buffer().is_empty() {
+            self.output.borrow_mut().flush()?;
+        }
+        self.inner.read_byte()
+    }
+}
+
+/// Decodes the escapes of `--stdin-string`.
+fn unescape(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.char_indices();
+    while let Some((offset, c)) = rest.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        let byte = match rest.next().map(|(_, c)| c) {
+            Some('n') => b'\n',
+            Some('r') => b'\r',
+            Some('t') => b'\t',
+            Some('0') => 0,
+            Some('\\') => b'\\',
+            Some('x') => {
+                let hex: String = rest.by_ref().take(2).map(|(_, c)| c).collect();
+                match u8::from_str_radix(&hex, 16) {
+                    Ok(byte) if hex.len() == 2 && hex.bytes().all(|b| b.is_ascii_hexdigit()) => byte,
+                    _ => return Err(format!("'\\x' at offset {} needs two hex digits", offset)),
+                }
+            }
+            Some(c) => return Err(format!("unknown escape '\\{}' at offset {}", c, offset)),
+            None => return Err(format!("'\\' at offset {} ends the text", offset)),
+        };
+        bytes.push(byte);
+    }
+    Ok(bytes)
+}
+
+/// Replaces the contents of `file` with `bytes`.
+fn rewrite(file: &mut File, bytes: &[u8]) -> io::Result<()> {
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(bytes)
+}
+
+/// Opens a file, or a URL if allowed by `options`.
+fn open(source: &str, options: &Options) -> io::Result<Box<dyn Read>> {
+    if fetch::is_url(source) {
+        if !options.allow_net {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "fetching URLs requires --allow-net",
+            ));
+        }
+        fetch::get(source, options.max_download)
+    } else {
+        Ok(Box::new(File::open(source)?))
+    }
+}
+
+/// Loads the program `source` configured by `options`, printing its syntax
+/// issues to stderr.
+pub fn load(source: &str, options: &Options, plugin: Option<&Plugin>) -> Result<BFI, BFIError> {
+    let bfi = read(source, options, plugin)?;
+    match first_issue(source, &bfi) {
+        Some(issue) => Err(issue.into()),
+        None => Ok(bfi),
+    }
+}
+
+/// Reads the program `source` configured by `options`, without checking it.
+pub fn read(source: &str, options: &Options, plugin: Option<&Plugin>) -> Result<BFI, BFIError> {
+    let read = || -> io::Result<String> {
+        let mut code = String::new();
+        open(source, options)?.read_to_string(&mut code)?;
+        Ok(code)
+    };
+    let mut bfi = match plugin {
+        Some(plugin) => {
+            let mut bfi = BFI::new(plugin.translate(&read()?)?);
+            bfi.set_extension(Box::new(plugin.clone()));
+            bfi
+        }
+        // Memory-mapped with the mmap feature.
+        None if !fetch::is_url(source) => BFI::from_file(source.to_string())?,
+        None => BFI::new(read()?),
+    };
+    configure(&mut bfi, options);
+    Ok(bfi)
+}
+
+/// Prints the syntax issues of `bfi` to stderr, and returns the first.
+fn first_issue(source: &str, bfi: &BFI) -> Option<SyntaxIssue> {
+    let issues = bfi.syntax_issues();
+    for issue in &issues {
+        eprintln!("{}: {}", source, issue);
+    }
+    issues.first().copied()
+}
+
+/// Returns the `--json` member describing the run of `file`, where
+/// `position` locates `error` and `bfi` is `None` if the file could not be
+/// read.
+fn json_program(
+    file: &str,
+    error: Option<&BFIError>,
+    position: Option<(usize, usize)>,
+    bfi: Option<&BFI>,
+    output: &[u8],
+) -> Json {
+    let status = if error.is_some() { "error" } else { "ok" };
+    let error = match error {
+        None => Json::Null,
+        Some(err) => {
+            let mut members = vec![
+                ("kind", Json::from(serve::error_kind(err))),
+                ("message", Json::from(err.to_string())),
+            ];
+            let (line, column) = match position {
+                Some((line, column)) => (Json::from(line), Json::from(column)),
+                None => (Json::Null, Json::Null),
+            };
+            members.push(("line", line));
+            members.push(("column", column));
+            Json::object(members)
+        }
+    };
+    let stats = match bfi {
+        Some(bfi) => Json::object(vec![
+            ("steps", Json::from(bfi.steps())),
+            ("output_bytes", Json::from(bfi.output_len())),
+            ("fingerprint", Fingerprint::of(bfi).to_json()),
+        ]),
+        None => Json::Null,
+    };
+    Json::object(vec![
+        ("file", Json::from(file)),
+        ("status", Json::from(status)),
+        ("error", error),
+        ("stats", stats),
+        ("output_base64", Json::from(base64::encode(output))),
+    ])
+}
+
+/// Runs `programs` together, connected by `options.links`: the programs
+/// without incoming link read `input`, and those without outgoing link write
+/// to stdout.
+fn run_linked(programs: Vec<(&String, BFI)>, options: &Options, input: &mut dyn ByteIn) -> Result<(), BFIError> {
+    let mut files = Vec::new();
+    let mut network = Network::new();
+    for (file, bfi) in programs {
+        files.push(file);
+        network.add(bfi);
+    }
+    for link in &options.links {
+        network.connect(link.from, link.to, link.capacity);
+    }
+    for node in 0..files.len() {
+        if !options.links.iter().any(|link| link.to == node) {
+            network.connect_input(node);
+        }
+        if !options.links.iter().any(|link| link.from == node) {
+            network.connect_output(node);
+        }
+    }
+    match network.run(input, &mut io::stdout()) {
+        Ok(()) => Ok(()),
+        // Nobody reads the output any more, e.g. after `| head`.
+        Err(LinkError::Failed {
+            error: BFIError::Io(ref err),
+            ..
+        }) if err.kind() == io::ErrorKind::BrokenPipe && !options.pipe_errors => Ok(()),
+        Err(LinkError::Failed { node, error }) => {
+            eprintln!("{}: {}", files[node], error);
+            process::exit(crate::exit_code(&error));
+        }
+        Err(LinkError::Deadlock { waiting }) => {
+            let waiting: Vec<String> = waiting
+                .iter()
+                .map(|&(node, wait)| match wait {
+                    Wait::Input => format!("{} (file {}) waits for input", files[node], node + 1),
+                    Wait::Output => format!("{} (file {}) waits to write", files[node], node + 1),
+                })
+                .collect();
+            eprintln!("Deadlock: {}", waiting.join(", "));
+            process::exit(crate::EXIT_RUNTIME);
+        }
+        Err(LinkError::Io(err)) => Err(err.into()),
+    }
+}
+
+/// Prints the object of `--json` holding the members of `programs`.
+fn print_json(programs: &[Json], options: &Options) -> io::Result<()> {
+    let ok = programs
+        .iter()
+        .all(|program| program.get("status").and_then(Json::as_str) == Some("ok"));
+    let report = Json::object(vec![
+        ("schema", Json::from(JSON_SCHEMA)),
+        ("status", Json::from(if ok { "ok" } else { "error" })),
+        ("programs", Json::Array(programs.to_vec())),
+    ]);
+    match writeln!(io::stdout(), "{}", report) {
+        // Nobody reads the output any more, e.g. after `| head`.
+        Err(ref err) if err.kind() == io::ErrorKind::BrokenPipe && !options.pipe_errors => Ok(()),
+        result => result,
+    }
+}
+
+/// Applies `options` to `bfi`.
+pub fn configure(bfi: &mut BFI, options: &Options) {
+    bfi.set_max_depth(options.max_depth);
+    bfi.set_deterministic(options.deterministic);
+    bfi.set_strictness(options.strictness);
+    bfi.set_eof_policy(options.eof);
+    bfi.set_growable(options.growable);
+    bfi.set_wrapping(options.wrapping);
+    bfi.set_limits(options.limits);
+}
+
+/// Runs `bfi`, stepping through `observers` if any.
+fn execute(
+    bfi: &mut BFI,
+    reader: &mut dyn ByteIn,
+    writer: &mut dyn ByteOut,
+    observers: &mut [&mut dyn Observer],
+) -> Result<(), BFIError> {
+    if observers.is_empty() {
+        bfi.interpret(reader, writer)
+    } else {
+        observer::interpret(bfi, reader, writer, observers)
+    }
+}
+
+fn filter<R: Read>(
+    bfi: &mut BFI,
+    input: &mut Limited<BufReader<R>>,
+    observers: &mut [&mut dyn Observer],
+) -> Result<(), BFIError> {
+    let output = RefCell::new(BufWriter::new(io::stdout().lock()));
+    let mut reader = FilterInput {
+        inner: input,
+        output: &output,
+    };
+    execute(bfi, &mut reader, &mut FilterOutput(&output), observers)
+}
+
+pub fn main<I: Iterator<Item = String>>(args: I) -> Result<(), BFIError> {
+    let mut options = parse_options(args, config::load()?);
+    if options.explain {
+        options.limits.max_steps.get_or_insert(EXPLAIN_MAX_STEPS);
+    }
+    if options.sandbox {
+        sandbox::restrict(&mut options).unwrap_or_else(|err| crate::usage_error(&err, USAGE));
+    }
+    if options.deterministic && options.plugin.is_some() {
+        // A plugin is native code whose behavior cannot be checked.
+        crate::usage_error("--deterministic does not allow --plugin", USAGE);
+    }
+    if options.play && options.filter {
+        crate::usage_error("--play does not allow --filter", USAGE);
+    }
+    if options.json && (options.play || options.filter) {
+        crate::usage_error("--json does not allow --play or --filter", USAGE);
+    }
+    if !options.links.is_empty() {
+        // The programs run together, and not through observers.
+        let conflicts = [
+            (options.keep_going, "--keep-going"),
+            (options.filter, "--filter"),
+            (options.json, "--json"),
+            (options.digest, "--digest"),
+            (options.explain, "--explain"),
+            (options.diff_every.is_some(), "--diff-every"),
+            (options.play, "--play"),
+            (options.record_cast.is_some(), "--record-cast"),
+            (options.save_tape.is_some(), "--save-tape"),
+        ];
+        if let Some(&(_, option)) = conflicts.iter().find(|&&(given, _)| given) {
+            crate::usage_error(&format!("--link does not allow {}", option), USAGE);
+        }
+        if let Some(link) = options.links.iter().find(|link| link.from.max(link.to) >= options.files.len()) {
+            crate::usage_error(
+                &format!("--link {}:{} names a missing file", link.from + 1, link.to + 1),
+                USAGE,
+            );
+        }
+    }
+    if options.sandbox && (options.play || options.record_cast.is_some()) {
+        crate::usage_error("--sandbox does not allow --play or --record-cast", USAGE);
+    }
+    // Named like the errors of the programs.
+    let exit = |path: &str, err: io::Error| -> ! {
+        let err = BFIError::from(err);
+        eprintln!("{}: {}", path, err);
+        process::exit(crate::exit_code(&err));
+    };
+    let plugin = (options.plugin.as_deref())
+        .map(|path| plugin::load(path).unwrap_or_else(|err| exit(path, err)));
+    let image = (options.load_tape.as_deref())
+        .map(|path| fs::read(path).unwrap_or_else(|err| exit(path, err)));
+    let source: Box<dyn Read> = match (&options.input, &options.stdin_string) {
+        (Some(_), Some(_)) => crate::usage_error("Give either --input or --stdin-string", USAGE),
+        (Some(source), None) => open(source, &options).unwrap_or_else(|err| exit(source, err)),
+        (None, Some(text)) => Box::new(io::Cursor::new(text.clone())),
+        (None, None) => Box::new(io::stdin().lock()),
+    };
+    // Counts only the bytes the programs consume, not those buffered.
+    let max_input = options.max_input.unwrap_or(u64::MAX);
+    let mut input = Limited::new(BufReader::new(source), max_input, "Input");
+    // The members of `--json`, printed even when stopping at a failure.
+    let report = options.json.then(|| RefCell::new(Vec::new()));
+    let record = |program: Json| {
+        if let Some(ref report) = report {
+            report.borrow_mut().push(program);
+        }
+    };
+    // The first failure, which gives the exit status.
+    let failure = Cell::new(None);
+    let report_failure = |file: &str, err: BFIError| {
+        eprintln!("{}: {}", file, err);
+        failure.set(failure.get().or(Some(crate::exit_code(&err))));
+    };
+    let fail = |file: &str, err: BFIError| {
+        let code = crate::exit_code(&err);
+        report_failure(file, err);
+        if !options.keep_going {
+            if let Some(ref report) = report {
+                // The failure of the program gives the exit status either way.
+                if let Err(err) = print_json(&report.borrow(), &options) {
+                    eprintln!("Error: {}", err);
+                }
+            }
+            process::exit(code);
+        }
+    };
+    let mut programs = Vec::new();
+    for file in &options.files {
+        let mut bfi = match read(file, &options, plugin.as_ref()) {
+            Ok(bfi) => bfi,
+            Err(err) => {
+                record(json_program(file, Some(&err), None, None, &[]));
+                fail(file, err);
+                continue;
+            }
+        };
+        if let Some(issue) = first_issue(file, &bfi) {
+            let err = BFIError::from(issue);
+            record(json_program(file, Some(&err), Some((issue.line, issue.column)), Some(&bfi), &[]));
+            fail(file, err);
+            continue;
+        }
+        if let Some(Err(err)) = image.as_deref().map(|image| bfi.load_tape(image)) {
+            record(json_program(file, Some(&err), None, Some(&bfi), &[]));
+            fail(file, err);
+            continue;
+        }
+        if options.fingerprint {
+            eprintln!("{}: {}", file, Fingerprint::of(&bfi));
+        }
+        programs.push((file, bfi));
+    }
+    let keys = options.play.then(play::keys);
+    // Fails before running the programs if the files cannot be written.
+    let mut recorder = (options.record_cast.as_deref())
+        .map(|path| Recorder::new(File::create(path).unwrap_or_else(|err| exit(path, err)), options.speed));
+    let mut tape = (options.save_tape.as_deref())
+        .map(|path| (path, File::create(path).unwrap_or_else(|err| exit(path, err))));
+    if let Some(max_time) = options.max_time {
+        sandbox::watchdog(max_time);
+    }
+    if options.sandbox {
+        sandbox::confine()?;
+    }
+    if !options.links.is_empty() {
+        return run_linked(programs, &options, &mut input);
+    }
+    for (file, mut bfi) in programs {
+        let mut digest = options.digest.then(ExecutionDigest::new);
+        let mut explainer = options.explain.then(|| Explainer::new(io::stderr()));
+        let mut observers: Vec<&mut dyn Observer> = Vec::new();
+        if let Some(ref mut digest) = digest {
+            observers.push(digest);
+        }
+        if let Some(ref mut explainer) = explainer {
+            observers.push(explainer);
+        }
+        let mut diff = options.diff_every.map(|every| DiffEvery::new(io::stderr(), every));
+        if let Some(ref mut diff) = diff {
+            observers.push(diff);
+        }
+        let mut player = options.play.then(|| Player::new(io::stderr(), keys.as_ref(), options.speed));
+        if let Some(ref mut player) = player {
+            observers.push(player);
+        }
+        if let Some(ref mut recorder) = recorder {
+            observers.push(recorder);
+        }
+        let raw_mode = if options.play { RawMode::enable() } else { None };
+        // The frames would overwrite the output written meanwhile, and
+        // `--json` encodes it.
+        let mut played = Vec::new();
+        let mut result = if options.filter {
+            filter(&mut bfi, &mut input, &mut observers)
+        } else if options.play || options.json {
+            execute(&mut bfi, &mut input, &mut played, &mut observers)
+        } else {
+            execute(&mut bfi, &mut input, &mut io::stdout(), &mut observers)
+        };
+        if let Some(ref mut player) = player {
+            player.finish(&bfi);
+            drop(raw_mode);
+            result = result.and(io::stdout().write_all(&played).map_err(BFIError::from));
+        }
+        if let Some(ref mut recorder) = recorder {
+            recorder.finish(&bfi);
+        }
+        if let Some(ref mut diff) = diff {
+            diff.finish(&bfi);
+        }
+        if let Some(digest) = digest {
+            eprintln!("{}: digest sha256:{}", file, hex(&digest.finish(&bfi)));
+        }
+        // The file named by the error.
+        let mut failed: &str = file;
+        if let (Ok(()), Some((path, ref mut tape))) = (&result, &mut tape) {
+            result = rewrite(tape, &bfi.save_tape()).map_err(BFIError::from);
+            failed = path;
+        }
+        let at = bfi.offset().map(|offset| position(bfi.code(), offset));
+        record(json_program(file, result.as_ref().err(), at, Some(&bfi), &played));
+        match result {
+            Ok(()) => (),
+            // Nobody reads the output any more, e.g. after `| head`.
+            Err(BFIError::Io(ref err)) if err.kind() == io::ErrorKind::BrokenPipe && !options.pipe_errors => break,
+            Err(err) => {
+                report_failure(failed, err);
+                if !options.keep_going {
+                    break;
+                }
+            }
+        }
+    }
+    if let (Some(path), Some(mut recorder)) = (&options.record_cast, recorder) {
+        if let Err(err) = recorder.save() {
+            report_failure(path, err.into());
+        }
+    }
+    if let Some(report) = report {
+        print_json(&report.into_inner(), &options)?;
+    }
+    if let Some(code) = failure.get() {
+        process::exit(code);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::io::{self, BufReader, BufWriter, Cursor, Read, Write};
+    use std::num::NonZeroU64;
+    use std::time::Duration;
+
+    use bfi::io::ByteIn;
+    use bfi::EofPolicy;
+    use bfi::Limits;
+    use bfi::Strictness;
+    use bfi::BFI;
+
+    use bfi::BFIError;
+
+    use crate::fetch::Limited;
+    use crate::json::Json;
+    use crate::play::Speed;
+    use crate::run::{
+        json_program, open, parse_options, unescape, FilterInput, FilterOutput, Link, Options, Profile,
+    };
+
+    /// Records how many bytes had been written at each flush.
+    #[derive(Default)]
+    struct Flushes {
+        data: Vec<u8>,
+        flushed_at: Vec<usize>,
+    }
+
+    impl Write for &mut Flushes {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.data.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.flushed_at.push(self.data.len());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let args = [
+            "--filter",
+            "a.b",
+            "--eof",
+            "unchanged",
+            "--json",
+            "--pipe-errors",
+            "--keep-going",
+            "--link",
+            "1:2:8",
+            "--link",
+            "2:1",
+            "--deterministic",
+            "--pure",
+            "--strict",
+            "--fingerprint",
+            "--digest",
+            "--explain",
+            "--diff-every",
+            "100",
+            "--play",
+            "--speed",
+            "20ips",
+            "--record-cast",
+            "out.cast",
+            "--input",
+            "in.txt",
+            "--max-input",
+            "100",
+            "--allow-net",
+            "--max-download",
+            "10",
+            "--max-steps",
+            "7",
+            "--max-cells",
+            "1000000",
+            "--max-depth",
+            "8",
+            "--load-tape",
+            "in.bin",
+            "--save-tape",
+            "out.bin",
+            "--max-output",
+            "64",
+            "--max-time",
+            "3",
+            "--wrap",
+            "--sandbox",
+            "--",
+            "--b.b",
+        ];
+        assert_eq!(
+            parse_options(
+                args.iter().map(|s| s.to_string()),
+                Options {
+                    eof: EofPolicy::MinusOne,
+                    limits: Limits {
+                        max_cells: Some(100),
+                        ..Limits::default()
+                    },
+                    ..Options::default()
+                }
+            ),
+            Options {
+                filter: true,
+                eof: EofPolicy::Unchanged,
+                json: true,
+                pipe_errors: true,
+                keep_going: true,
+                links: vec![
+                    Link {
+                        from: 0,
+                        to: 1,
+                        capacity: 8
+                    },
+                    Link {
+                        from: 1,
+                        to: 0,
+                        capacity: 4096
+                    },
+                ],
+                deterministic: true,
+                strictness: Strictness::Pure,
+                fingerprint: true,
+                digest: true,
+                explain: true,
+                diff_every: NonZeroU64::new(100),
+                play: true,
+                speed: Speed(20.0),
+                record_cast: Some("out.cast".to_string()),
+                limits: Limits {
+                    max_steps: Some(7),
+                    max_cells: Some(1_000_000),
+                    max_output: Some(64),
+                },
+                growable: true,
+                wrapping: true,
+                max_depth: 8,
+                max_time: Some(Duration::from_secs(3)),
+                input: Some("in.txt".to_string()),
+                stdin_string: None,
+                max_input: Some(100),
+                load_tape: Some("in.bin".to_string()),
+                save_tape: Some("out.bin".to_string()),
+                allow_net: true,
+                max_download: 10,
+                plugin: None,
+                sandbox: true,
+                files: vec!["a.b".to_string(), "--b.b".to_string()],
+            }
+        );
+        assert_eq!(
+            parse_options(std::iter::empty(), Options::default()),
+            Options::default()
+        );
+        assert!("0:1".parse::<Link>().is_err());
+        assert!("1:2:0".parse::<Link>().is_err());
+        assert!("1".parse::<Link>().is_err());
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape("a\\n\\x00\\xfF\\\\é").unwrap(), b"a\n\0\xff\\\xc3\xa9");
+        assert_eq!(unescape("ab\\q").unwrap_err(), "unknown escape '\\q' at offset 2");
+        assert_eq!(unescape("\\x4").unwrap_err(), "'\\x' at offset 0 needs two hex digits");
+        assert_eq!(unescape("\\x+1").unwrap_err(), "'\\x' at offset 0 needs two hex digits");
+        assert_eq!(unescape("a\\").unwrap_err(), "'\\' at offset 1 ends the text");
+
+        let options = parse_options(
+            ["--stdin-string", "hi\\n"].iter().map(|s| s.to_string()),
+            Options::default(),
+        );
+        assert_eq!(options.stdin_string, Some(b"hi\n".to_vec()));
+    }
+
+    #[test]
+    fn test_max_input() {
+        let mut output = Vec::new();
+        Limited::new(Cursor::new(b"abc".to_vec()), 3, "Input")
+            .read_to_end(&mut output)
+            .unwrap();
+        assert_eq!(output, b"abc");
+
+        let err = Limited::new(Cursor::new(b"abcd".to_vec()), 3, "Input")
+            .read_to_end(&mut Vec::new())
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Input exceeds 3 bytes");
+        assert_eq!(crate::exit_code(&BFIError::from(err)), 4);
+
+        // Only the bytes consumed count, not those buffered.
+        let source = BufReader::new(Cursor::new(b"abcd".to_vec()));
+        let mut input = Limited::new(source, 3, "Input");
+        assert_eq!(input.read_byte().unwrap(), Some(b'a'));
+        assert_eq!(input.get_ref().buffer(), b"bcd");
+    }
+
+    #[test]
+    fn test_profile() {
+        let args = ["--max-steps", "9", "--profile", "classic", "--eof", "zero"];
+        let options = parse_options(args.iter().map(|s| s.to_string()), Options::default());
+        assert_eq!(options.eof, EofPolicy::Zero);
+        assert_eq!(
+            options.limits,
+            Limits {
+                max_cells: Some(30_000),
+                ..Limits::default()
+            }
+        );
+
+        let args = ["--profile", "lenient"];
+        let options = parse_options(args.iter().map(|s| s.to_string()), Options::default());
+        assert!(options.growable);
+        assert_eq!(options.limits.max_steps, None);
+        assert!(!options.wrapping);
+        assert_eq!("strict".parse(), Ok(Profile::Strict));
+
+        let args = ["--standard", "--eof", "zero"];
+        let options = parse_options(args.iter().map(|s| s.to_string()), Options::default());
+        assert!(options.wrapping && options.growable);
+        assert_eq!(options.eof, EofPolicy::Zero);
+        assert!("bff".parse::<Profile>().is_err());
+    }
+
+    #[test]
+    fn test_filter() {
+        let mut flushes = Flushes::default();
+        {
+            let output = RefCell::new(BufWriter::new(&mut flushes));
+            let source = BufReader::with_capacity(2, Cursor::new(b"abc".to_vec()));
+            let mut input = Limited::new(source, u64::MAX, "Input");
+            let mut reader = FilterInput {
+                inner: &mut input,
+                output: &output,
+            };
+            let mut bfi = BFI::new(",[..,]".to_string());
+            bfi.interpret(&mut reader, &mut FilterOutput(&output))
+                .unwrap();
+        }
+        assert_eq!(flushes.data, b"aabbcc");
+        assert_eq!(flushes.flushed_at, vec![0, 4, 6, 6]);
+    }
+
+    #[test]
+    fn test_json_program() {
+        let mut bfi = BFI::new("+.".to_string());
+        bfi.interpret(&mut io::empty(), &mut Vec::new()).unwrap();
+        let program = json_program("a.b", None, None, Some(&bfi), b"\x01");
+        assert_eq!(program.get("status").and_then(Json::as_str), Some("ok"));
+        assert_eq!(program.get("error"), Some(&Json::Null));
+        let stats = program.get("stats").unwrap();
+        assert_eq!(stats.get("steps").and_then(Json::as_u64), Some(2));
+        assert_eq!(program.get("output_base64").and_then(Json::as_str), Some("AQ=="));
+
+        let err = BFIError::Io(io::ErrorKind::NotFound.into());
+        let program = json_program("b.b", Some(&err), None, None, &[]);
+        let error = program.get("error").unwrap();
+        assert_eq!(error.get("kind").and_then(Json::as_str), Some("io"));
+        assert_eq!(error.get("line"), Some(&Json::Null));
+        assert_eq!(program.get("stats"), Some(&Json::Null));
+
+        let program = json_program("c.b", Some(&BFIError::OutOfMemory), Some((2, 3)), Some(&bfi), &[]);
+        let error = program.get("error").unwrap();
+        assert_eq!(error.get("kind").and_then(Json::as_str), Some("out_of_memory"));
+        assert_eq!(error.get("column").and_then(Json::as_u64), Some(3));
+    }
+
+    #[test]
+    fn test_open() {
+        let options = Options::default();
+        let err = open("https://example.com/hello.b", &options).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(
+            open("no-such-file.b", &options).err().unwrap().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+}
@@ -0,0 +1,295 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! `--sandbox`: limits and process restrictions for untrusted programs.
+//!
+//! The limits of the command line and the configuration may tighten the
+//! bundled ones but never relax them. With the `seccomp` feature on Linux,
+//! the process is also confined to the system calls of the interpreter loop
+//! once the programs and the input are open.
+
+use std::process;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use bfi::Limits;
+
+use crate::run::Options;
+use crate::serve;
+
+/// Limits bundled with `--sandbox`.
+pub const LIMITS: Limits = Limits {
+    max_steps: Some(serve::DEFAULT_MAX_STEPS),
+    max_cells: Some(30_000),
+    max_output: Some(serve::DEFAULT_MAX_OUTPUT),
+};
+
+/// Wall-clock time bundled with `--sandbox`.
+pub const MAX_TIME: Duration = Duration::from_secs(10);
+
+/// Applies the sandbox to `options`, rejecting the options that would let
+/// the program reach files or the network other than its own.
+pub fn restrict(options: &mut Options) -> Result<(), String> {
+    if options.plugin.is_some() {
+        return Err("--plugin cannot be used with --sandbox".to_string());
+    }
+    if options.allow_net {
+        return Err("--allow-net cannot be used with --sandbox".to_string());
+    }
+    // Files cannot be written once confined.
+    if options.save_tape.is_some() {
+        return Err("--save-tape cannot be used with --sandbox".to_string());
+    }
+    options.limits = Limits {
+        max_steps: serve::clamp(options.limits.max_steps, LIMITS.max_steps),
+        max_cells: serve::clamp(options.limits.max_cells, LIMITS.max_cells),
+        max_output: serve::clamp(options.limits.max_output, LIMITS.max_output),
+    };
+    options.max_time = serve::clamp(options.max_time, Some(MAX_TIME));
+    Ok(())
+}
+
+/// Exits with `EXIT_LIMIT` once `max_time` has elapsed.
+///
+/// Returns once the thread of the watchdog has started, so that the system
+/// calls setting it up are made before `confine`.
+pub fn watchdog(max_time: Duration) {
+    let (started, running) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = started.send(());
+        thread::sleep(max_time);
+        eprintln!("Time limit exceeded");
+        process::exit(crate::EXIT_LIMIT);
+    });
+    let _ = running.recv();
+}
+
+#[cfg(all(
+    feature = "seccomp",
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+mod seccomp {
+    use std::io;
+
+    use libc::{sock_filter, sock_fprog};
+
+    #[cfg(target_arch = "x86_64")]
+    const AUDIT_ARCH: u32 = 0xc000_003e;
+    #[cfg(target_arch = "aarch64")]
+    const AUDIT_ARCH: u32 = 0xc000_00b7;
+
+    /// Offsets in `struct seccomp_data`.
+    const NR_OFFSET: u32 = 0;
+    const ARCH_OFFSET: u32 = 4;
+    /// Lower half of the third argument, the protection of `mmap`.
+    const ARG2_OFFSET: u32 = 16 + 2 * 8;
+
+    /// System calls left to the interpreter: I/O of the open descriptors,
+    /// memory, the watchdog and exiting.
+    const ALLOWED: &[libc::c_long] = &[
+        libc::SYS_read,
+        libc::SYS_write,
+        libc::SYS_writev,
+        libc::SYS_close,
+        libc::SYS_brk,
+        libc::SYS_munmap,
+        libc::SYS_mremap,
+        libc::SYS_madvise,
+        libc::SYS_futex,
+        libc::SYS_nanosleep,
+        libc::SYS_clock_nanosleep,
+        libc::SYS_sched_yield,
+        libc::SYS_sigaltstack,
+        libc::SYS_rt_sigprocmask,
+        libc::SYS_rt_sigreturn,
+        libc::SYS_exit,
+        libc::SYS_exit_group,
+    ];
+
+    fn statement(code: u32, k: u32) -> sock_filter {
+        sock_filter {
+            code: code as u16,
+            jt: 0,
+            jf: 0,
+            k,
+        }
+    }
+
+    fn jump(code: u32, k: u32, jt: usize, jf: usize) -> sock_filter {
+        sock_filter {
+            code: (libc::BPF_JMP | code | libc::BPF_K) as u16,
+            jt: jt as u8,
+            jf: jf as u8,
+            k,
+        }
+    }
+
+    fn load(offset: u32) -> sock_filter {
+        statement(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, offset)
+    }
+
+    fn ret(action: u32) -> sock_filter {
+        statement(libc::BPF_RET | libc::BPF_K, action)
+    }
+
+    /// Builds the filter: the calls of `ALLOWED` and non-executable `mmap`
+    /// are allowed, other calls fail with `EPERM`, and calls of another
+    /// architecture kill the process.
+    pub fn filter() -> Vec<sock_filter> {
+        let mut program = vec![
+            load(ARCH_OFFSET),
+            jump(libc::BPF_JEQ, AUDIT_ARCH, 1, 0),
+            ret(libc::SECCOMP_RET_KILL_PROCESS),
+            load(NR_OFFSET),
+        ];
+        for (i, &nr) in ALLOWED.iter().enumerate() {
+            // Past the remaining checks, the four instructions of the mmap
+            // check and the denial.
+            program.push(jump(libc::BPF_JEQ, nr as u32, ALLOWED.len() - i + 3, 0));
+        }
+        program.extend_from_slice(&[
+            jump(libc::BPF_JEQ, libc::SYS_mmap as u32, 0, 2),
+            load(ARG2_OFFSET),
+            jump(libc::BPF_JSET, libc::PROT_EXEC as u32, 0, 1),
+            ret(libc::SECCOMP_RET_ERRNO | libc::EPERM as u32),
+            ret(libc::SECCOMP_RET_ALLOW),
+        ]);
+        program
+    }
+
+    /// Installs the filter on every thread of the process.
+    pub fn apply() -> io::Result<()> {
+        let mut program = filter();
+        let fprog = sock_fprog {
+            len: program.len() as u16,
+            filter: program.as_mut_ptr(),
+        };
+        // SAFETY: `fprog` points to a valid filter that outlives the calls,
+        // which copy it into the kernel.
+        unsafe {
+            if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let result = libc::syscall(
+                libc::SYS_seccomp,
+                libc::SECCOMP_SET_MODE_FILTER,
+                libc::SECCOMP_FILTER_FLAG_TSYNC,
+                &fprog as *const sock_fprog,
+            );
+            match result {
+                0 => Ok(()),
+                -1 => Err(io::Error::last_os_error()),
+                _ => Err(io::Error::other("cannot apply seccomp to every thread")),
+            }
+        }
+    }
+}
+
+/// Confines the process with seccomp where supported.
+#[cfg(all(
+    feature = "seccomp",
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+pub fn confine() -> std::io::Result<()> {
+    seccomp::apply()
+}
+
+/// Confines the process with seccomp where supported.
+#[cfg(not(all(
+    feature = "seccomp",
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+)))]
+pub fn confine() -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bfi::Limits;
+
+    use crate::run::Options;
+    use crate::sandbox::{restrict, LIMITS, MAX_TIME};
+
+    #[test]
+    fn test_restrict() {
+        let mut options = Options {
+            limits: Limits {
+                max_steps: Some(100),
+                max_cells: Some(u32::MAX as usize),
+                max_output: None,
+            },
+            ..Options::default()
+        };
+        restrict(&mut options).unwrap();
+        assert_eq!(
+            options.limits,
+            Limits {
+                max_steps: Some(100),
+                ..LIMITS
+            }
+        );
+        assert_eq!(options.max_time, Some(MAX_TIME));
+
+        let mut options = Options {
+            max_time: Some(Duration::from_secs(1)),
+            ..Options::default()
+        };
+        restrict(&mut options).unwrap();
+        assert_eq!(options.max_time, Some(Duration::from_secs(1)));
+
+        let mut options = Options {
+            allow_net: true,
+            ..Options::default()
+        };
+        assert!(restrict(&mut options).is_err());
+        let mut options = Options {
+            plugin: Some("dialect.so".to_string()),
+            ..Options::default()
+        };
+        assert!(restrict(&mut options).is_err());
+        let mut options = Options {
+            save_tape: Some("tape.bin".to_string()),
+            ..Options::default()
+        };
+        assert!(restrict(&mut options).is_err());
+    }
+
+    #[cfg(all(
+        feature = "seccomp",
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    #[test]
+    fn test_filter() {
+        let program = super::seccomp::filter();
+        // Every jump lands inside the program.
+        for (i, instruction) in program.iter().enumerate() {
+            if instruction.code as u32 & 0x07 == libc::BPF_JMP {
+                assert!(i + 1 + (instruction.jt.max(instruction.jf) as usize) < program.len());
+            }
+        }
+        assert_eq!(program.last().unwrap().k, libc::SECCOMP_RET_ALLOW);
+    }
+}
@@ -0,0 +1,538 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! `bfi serve`: HTTP execution service.
+//!
+//! `POST /run` takes a JSON object with `code`, optional `input` (or
+//! `input_base64`), and optional `max_steps`, `max_cells`, and `max_output`,
+//! which cannot raise the limits the server was started with. It responds with
+//! the output, the stats, and the error of the run.
+//!
+//! With the `websocket` feature, `GET /ws` starts an interactive WebSocket
+//! session, see `websocket`.
+
+use std::io::{BufRead, BufReader, Cursor, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bfi::io::{ByteIn, ByteOut};
+use bfi::{BFIError, Limits, BFI};
+
+use crate::base64;
+use crate::fetch;
+use crate::fingerprint::Fingerprint;
+use crate::json::Json;
+#[cfg(feature = "websocket")]
+use crate::websocket;
+
+const DEFAULT_LISTEN: &str = "127.0.0.1:8080";
+pub const DEFAULT_MAX_STEPS: u64 = 10_000_000;
+pub const DEFAULT_MAX_OUTPUT: usize = 1024 * 1024;
+const MAX_HEADER_BYTES: u64 = 16 * 1024;
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_MAX_TIME: Duration = Duration::from_secs(600);
+
+pub const USAGE: &str = "\
+Usage: bfi serve [--listen ADDR] [--max-steps N] [--max-cells N] [--max-output BYTES]
+                 [--max-time SECONDS]
+
+Options:
+    --listen ADDR       Address to listen on (default: 127.0.0.1:8080)
+    --max-steps N       Maximum commands executed per request (default: 10000000)
+    --max-cells N       Maximum memory cells per request (default: 32768)
+    --max-output BYTES  Maximum output bytes per request (default: 1048576)
+    --max-time SECONDS  Maximum wall-clock time of a WebSocket session
+                        (default: 600)";
+
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// Returns the value of the header `name`, compared case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+pub struct HttpError {
+    pub status: u16,
+    pub message: String,
+}
+
+impl HttpError {
+    pub fn new<S: Into<String>>(status: u16, message: S) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+/// Reads one HTTP/1.1 request with a `Content-Length` body.
+pub fn read_request<R: BufRead>(reader: &mut R) -> Result<Request, HttpError> {
+    let mut head = reader.take(MAX_HEADER_BYTES);
+    let mut line = String::new();
+    head.read_line(&mut line)
+        .map_err(|err| HttpError::new(400, err.to_string()))?;
+    let mut parts = line.split_whitespace();
+    let (method, path) = match (parts.next(), parts.next()) {
+        (Some(method), Some(path)) => (method.to_string(), path.to_string()),
+        _ => return Err(HttpError::new(400, "Malformed request line")),
+    };
+
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        match head.read_line(&mut line) {
+            Ok(0) => return Err(HttpError::new(431, "Request header fields too large")),
+            Ok(_) => (),
+            Err(err) => return Err(HttpError::new(400, err.to_string())),
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    let mut request = Request {
+        method,
+        path,
+        headers,
+        body: Vec::new(),
+    };
+    let content_length = match request.header("content-length") {
+        Some(value) => value
+            .parse()
+            .map_err(|_| HttpError::new(400, "Invalid Content-Length"))?,
+        None => 0,
+    };
+    if content_length > MAX_BODY_BYTES {
+        return Err(HttpError::new(413, "Payload too large"));
+    }
+    request.body = vec![0; content_length];
+    head.into_inner()
+        .read_exact(&mut request.body)
+        .map_err(|err| HttpError::new(400, err.to_string()))?;
+    Ok(request)
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        431 => "Request Header Fields Too Large",
+        501 => "Not Implemented",
+        _ => "Internal Server Error",
+    }
+}
+
+pub fn write_response<W: Write>(writer: &mut W, status: u16, body: &Json) -> std::io::Result<()> {
+    let body = body.to_string();
+    write!(
+        writer,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason(status),
+        body.len(),
+        body
+    )?;
+    writer.flush()
+}
+
+pub fn error_body(message: &str) -> Json {
+    Json::object(vec![("error", Json::from(message))])
+}
+
+pub fn error_kind(err: &BFIError) -> &'static str {
+    match *err {
+        BFIError::Io(ref err) if fetch::is_size_limit(err) => "size_limit_exceeded",
+        BFIError::Io(_) => "io",
+        BFIError::MissingClosingBrackets => "missing_closing_brackets",
+        BFIError::MissingOpeningBrackets => "missing_opening_brackets",
+        BFIError::OutOfMemory => "out_of_memory",
+        BFIError::ArithmeticOverflow => "arithmetic_overflow",
+        BFIError::StepLimitExceeded => "step_limit_exceeded",
+        BFIError::OutputLimitExceeded => "output_limit_exceeded",
+        BFIError::MemoryLimitExceeded => "memory_limit_exceeded",
+        BFIError::NestingTooDeep => "nesting_too_deep",
+        BFIError::InputForbidden => "input_forbidden",
+        BFIError::StrayCharacters => "stray_characters",
+        BFIError::InvalidAssertion => "invalid_assertion",
+        BFIError::AssertionFailed(_) => "assertion_failed",
+    }
+}
+
+/// Steps between two checks of the time limit of `interpret_until`.
+const TIME_CHECK_INTERVAL: u64 = 1 << 12;
+
+/// Runs `bfi`, whose syntax has been checked, like `BFI::interpret`, but
+/// returns `Ok(false)` once it has run for `max_time`.
+pub fn interpret_until(
+    bfi: &mut BFI,
+    reader: &mut dyn ByteIn,
+    writer: &mut dyn ByteOut,
+    max_time: Option<Duration>,
+) -> Result<bool, BFIError> {
+    let start = Instant::now();
+    while bfi.step(reader, writer)? {
+        if bfi.steps().is_multiple_of(TIME_CHECK_INTERVAL) && max_time.is_some_and(|max| start.elapsed() > max) {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Returns the members of `result_members` for a run stopped by its time limit.
+pub fn timed_out_members(bfi: &BFI) -> Vec<(&'static str, Json)> {
+    let mut members = result_members(&Ok(()), bfi);
+    for member in members.iter_mut() {
+        match member.0 {
+            "status" => member.1 = Json::from("error"),
+            "error" => {
+                member.1 = Json::object(vec![
+                    ("kind", Json::from("time_limit_exceeded")),
+                    ("message", Json::from("Time limit exceeded")),
+                ])
+            }
+            _ => (),
+        }
+    }
+    members
+}
+
+/// Returns the smaller of the requested and the allowed limit.
+pub fn clamp<T: Ord + Copy>(requested: Option<T>, allowed: Option<T>) -> Option<T> {
+    match (requested, allowed) {
+        (Some(requested), Some(allowed)) => Some(requested.min(allowed)),
+        (requested, allowed) => requested.or(allowed),
+    }
+}
+
+fn optional_number(body: &Json, key: &str) -> Result<Option<u64>, HttpError> {
+    match body.get(key) {
+        None | Some(Json::Null) => Ok(None),
+        Some(value) => value.as_u64().map(Some).ok_or_else(|| {
+            HttpError::new(400, format!("`{}` must be a non-negative integer", key))
+        }),
+    }
+}
+
+/// Reads the limits requested by `body`, bounded by `allowed`.
+pub fn request_limits(body: &Json, allowed: &Limits) -> Result<Limits, HttpError> {
+    Ok(Limits {
+        max_steps: clamp(optional_number(body, "max_steps")?, allowed.max_steps),
+        max_cells: clamp(
+            optional_number(body, "max_cells")?.map(|n| n as usize),
+            allowed.max_cells,
+        ),
+        max_output: clamp(
+            optional_number(body, "max_output")?.map(|n| n as usize),
+            allowed.max_output,
+        ),
+    })
+}
+
+/// Reads the program given by `body`.
+pub fn request_code(body: &Json) -> Result<&str, HttpError> {
+    body.get("code")
+        .and_then(Json::as_str)
+        .ok_or_else(|| HttpError::new(400, "`code` must be a string"))
+}
+
+/// Returns the status, the stats, and the error of a finished run.
+pub fn result_members(result: &Result<(), BFIError>, bfi: &BFI) -> Vec<(&'static str, Json)> {
+    let error = match *result {
+        Ok(()) => Json::Null,
+        Err(ref err) => Json::object(vec![
+            ("kind", Json::from(error_kind(err))),
+            ("message", Json::from(err.to_string())),
+        ]),
+    };
+    vec![
+        (
+            "status",
+            Json::from(if result.is_ok() { "ok" } else { "error" }),
+        ),
+        (
+            "stats",
+            Json::object(vec![
+                ("steps", Json::from(bfi.steps())),
+                ("output_bytes", Json::from(bfi.output_len())),
+                ("fingerprint", Fingerprint::of(bfi).to_json()),
+            ]),
+        ),
+        ("error", error),
+    ]
+}
+
+/// Executes the program described by a `POST /run` body, stopping it after
+/// `max_time` if given.
+pub fn run(body: &Json, allowed: &Limits, max_time: Option<Duration>) -> Result<Json, HttpError> {
+    let code = request_code(body)?;
+    let input = match (body.get("input"), body.get("input_base64")) {
+        (Some(input), None) => input
+            .as_str()
+            .ok_or_else(|| HttpError::new(400, "`input` must be a string"))?
+            .as_bytes()
+            .to_vec(),
+        (None, Some(input)) => input
+            .as_str()
+            .ok_or_else(|| HttpError::new(400, "`input_base64` must be a string"))
+            .and_then(|input| base64::decode(input).map_err(|err| HttpError::new(400, err)))?,
+        (None, None) => Vec::new(),
+        (Some(_), Some(_)) => {
+            return Err(HttpError::new(400, "Give either `input` or `input_base64`"))
+        }
+    };
+    let limits = request_limits(body, allowed)?;
+
+    let mut bfi = BFI::new(code.to_string());
+    bfi.set_limits(limits);
+    let mut output = Vec::new();
+    let result = bfi
+        .check_syntax()
+        .and_then(|()| interpret_until(&mut bfi, &mut Cursor::new(input), &mut output, max_time));
+
+    let mut members = vec![
+        (
+            "output",
+            Json::from(String::from_utf8_lossy(&output).into_owned()),
+        ),
+        ("output_base64", Json::from(base64::encode(&output))),
+    ];
+    match result {
+        Ok(false) => members.extend(timed_out_members(&bfi)),
+        result => members.extend(result_members(&result.map(|_| ()), &bfi)),
+    }
+    Ok(Json::object(members))
+}
+
+fn respond(request: &Request, allowed: &Limits) -> Result<Json, HttpError> {
+    match (request.method.as_str(), request.path.as_str()) {
+        ("POST", "/run") => {
+            let body = std::str::from_utf8(&request.body)
+                .map_err(|_| HttpError::new(400, "Body is not UTF-8"))?;
+            let body = Json::parse(body).map_err(|err| HttpError::new(400, err))?;
+            run(&body, allowed, None)
+        }
+        (_, "/run") => Err(HttpError::new(405, "Use POST")),
+        _ => Err(HttpError::new(404, "Not found")),
+    }
+}
+
+/// Settings of the service, shared by the connections.
+#[derive(Clone)]
+struct Server {
+    allowed: Limits,
+    #[cfg_attr(not(feature = "websocket"), allow(dead_code))]
+    max_time: Duration,
+    #[cfg(feature = "websocket")]
+    sessions: tokio::runtime::Handle,
+}
+
+fn handle(stream: TcpStream, server: &Server) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(&stream);
+    let request = read_request(&mut reader);
+    if let Ok(ref request) = request {
+        if request.path == "/ws" {
+            #[cfg(feature = "websocket")]
+            {
+                let buffered = reader.buffer().to_vec();
+                return websocket::session(
+                    request,
+                    buffered,
+                    stream,
+                    server.allowed,
+                    server.max_time,
+                    &server.sessions,
+                );
+            }
+            #[cfg(not(feature = "websocket"))]
+            {
+                let body = error_body("WebSocket sessions need the websocket feature");
+                return write_response(&mut &stream, 501, &body);
+            }
+        }
+    }
+
+    let (status, body) = match request.and_then(|request| respond(&request, &server.allowed)) {
+        Ok(body) => (200, body),
+        Err(err) => (err.status, error_body(&err.message)),
+    };
+    write_response(&mut &stream, status, &body)
+}
+
+/// Parses `--listen` and the limit options shared by the services, leaving
+/// the others to `other`, which returns whether it knew the option.
+pub fn parse_options<I, F>(mut args: I, default_listen: &str, usage: &str, mut other: F) -> (String, Limits)
+where
+    I: Iterator<Item = String>,
+    F: FnMut(&str, &mut I) -> bool,
+{
+    let mut listen = default_listen.to_string();
+    let mut allowed = Limits {
+        max_steps: Some(DEFAULT_MAX_STEPS),
+        max_cells: None,
+        max_output: Some(DEFAULT_MAX_OUTPUT),
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--listen" => listen = crate::option_value(&arg, args.next(), usage),
+            "--max-steps" => {
+                allowed.max_steps = Some(crate::option_value(&arg, args.next(), usage))
+            }
+            "--max-cells" => {
+                allowed.max_cells = Some(crate::option_value(&arg, args.next(), usage))
+            }
+            "--max-output" => {
+                allowed.max_output = Some(crate::option_value(&arg, args.next(), usage))
+            }
+            _ if other(&arg, &mut args) => (),
+            _ => crate::usage_error(&format!("Unknown option '{}'", arg), usage),
+        }
+    }
+    (listen, allowed)
+}
+
+pub fn main<I: Iterator<Item = String>>(args: I) -> Result<(), BFIError> {
+    let mut max_time = DEFAULT_MAX_TIME;
+    let (listen, allowed) = parse_options(args, DEFAULT_LISTEN, USAGE, |arg, args| {
+        let known = arg == "--max-time";
+        if known {
+            max_time = Duration::from_secs(crate::option_value(arg, args.next(), USAGE));
+        }
+        known
+    });
+    let server = Server {
+        allowed,
+        max_time,
+        #[cfg(feature = "websocket")]
+        sessions: websocket::runtime()?,
+    };
+    let listener = TcpListener::bind(&listen)?;
+    eprintln!("Listening on {}", listener.local_addr()?);
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let server = server.clone();
+                thread::spawn(move || {
+                    if let Err(err) = handle(stream, &server) {
+                        eprintln!("{}", err);
+                    }
+                });
+            }
+            Err(err) => eprintln!("{}", err),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use bfi::Limits;
+
+    use crate::json::Json;
+    use crate::serve::{clamp, read_request, run};
+
+    #[test]
+    fn test_read_request() {
+        let raw = "POST /run HTTP/1.1\r\nHost: localhost\r\ncontent-length: 4\r\n\r\nbody";
+        let request = read_request(&mut Cursor::new(raw)).ok().unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/run");
+        assert_eq!(request.header("HOST"), Some("localhost"));
+        assert_eq!(request.body, b"body");
+
+        let raw = "POST /run HTTP/1.1\r\nContent-Length: 99999999\r\n\r\n";
+        assert_eq!(
+            read_request(&mut Cursor::new(raw)).err().unwrap().status,
+            413
+        );
+
+        let raw = format!("GET / HTTP/1.1\r\nX: {}\r\n\r\n", "a".repeat(20000));
+        assert_eq!(
+            read_request(&mut Cursor::new(raw)).err().unwrap().status,
+            431
+        );
+    }
+
+    #[test]
+    fn test_clamp() {
+        assert_eq!(clamp(Some(5), Some(10)), Some(5));
+        assert_eq!(clamp(Some(50), Some(10)), Some(10));
+        assert_eq!(clamp(None, Some(10)), Some(10));
+        assert_eq!(clamp(Some(5), None), Some(5));
+        assert_eq!(clamp::<u64>(None, None), None);
+    }
+
+    #[test]
+    fn test_run() {
+        let allowed = Limits {
+            max_steps: Some(1000),
+            ..Limits::default()
+        };
+        let body = Json::parse(r#"{"code": ",>,<>[-<+>]<++++++++++++++++++++++++++++++++++++++++++++++++.", "input_base64": "AQI="}"#).unwrap();
+        let result = run(&body, &allowed, None).ok().unwrap();
+        assert_eq!(result.get("status").and_then(Json::as_str), Some("ok"));
+        assert_eq!(result.get("output").and_then(Json::as_str), Some("3"));
+        assert_eq!(result.get("error"), Some(&Json::Null));
+
+        let body = Json::parse(r#"{"code": "+[]", "max_steps": 1000000}"#).unwrap();
+        let result = run(&body, &allowed, None).ok().unwrap();
+        assert_eq!(result.get("status").and_then(Json::as_str), Some("error"));
+        let error = result.get("error").unwrap();
+        assert_eq!(
+            error.get("kind").and_then(Json::as_str),
+            Some("step_limit_exceeded")
+        );
+        let stats = result.get("stats").unwrap();
+        assert_eq!(stats.get("steps").and_then(Json::as_u64), Some(1000));
+        let fingerprint = stats.get("fingerprint").unwrap();
+        assert_eq!(fingerprint.get("instructions").and_then(Json::as_u64), Some(3));
+
+        let body = Json::parse(r#"{"code": ",.", "input": "A", "max_output": 0}"#).unwrap();
+        let result = run(&body, &allowed, None).ok().unwrap();
+        let error = result.get("error").unwrap();
+        assert_eq!(
+            error.get("kind").and_then(Json::as_str),
+            Some("output_limit_exceeded")
+        );
+
+        let body = Json::parse(r#"{"input": ""}"#).unwrap();
+        assert_eq!(run(&body, &allowed, None).err().unwrap().status, 400);
+    }
+}
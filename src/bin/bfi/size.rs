@@ -0,0 +1,216 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! Code-golf metrics of programs.
+
+use std::fmt;
+
+use bfi::program::Program;
+use bfi::{BFIError, BFI};
+
+use crate::fingerprint::{Fingerprint, COMMANDS};
+use crate::json::Json;
+use crate::run::{self, Options};
+
+const USAGE: &str = "\
+Usage: bfi size [--json] FILE [OTHER]
+
+Prints the number of commands of FILE, each command apart, the maximum
+nesting depth of its loops, the number of commands left once the optimizer
+has removed those that cannot change the output, and the bytes of the source.
+With OTHER, also prints its metrics and how they differ from those of FILE.
+
+Options:
+    --json  Print a JSON object per line instead";
+
+/// The commands of `bfi` as a `Program`, without those of the extension.
+pub fn program(bfi: &BFI) -> Result<Program, BFIError> {
+    let code: String = bfi
+        .commands()
+        .map(|(_, byte)| byte)
+        .filter(|byte| COMMANDS.contains(byte))
+        .map(char::from)
+        .collect();
+    code.parse()
+}
+
+/// Sizes of a program.
+#[derive(Debug, PartialEq)]
+pub struct Size {
+    pub fingerprint: Fingerprint,
+    pub depth: usize,
+    /// Commands left by `Program::optimized`.
+    pub optimized: usize,
+}
+
+impl Size {
+    pub fn of(bfi: &BFI) -> Result<Self, BFIError> {
+        let program = program(bfi)?;
+        Ok(Self {
+            fingerprint: Fingerprint::of(bfi),
+            depth: program.depth(),
+            optimized: program.optimized().len(),
+        })
+    }
+
+    /// Metrics compared by `difference`, with their names.
+    fn metrics(&self) -> [(&'static str, usize); 4] {
+        [
+            ("commands", self.fingerprint.instructions()),
+            ("depth", self.depth),
+            ("optimized", self.optimized),
+            ("bytes", self.fingerprint.size),
+        ]
+    }
+
+    pub fn to_json(&self) -> Json {
+        let counts = COMMANDS
+            .iter()
+            .zip(self.fingerprint.counts.iter())
+            .map(|(&command, &count)| ((command as char).to_string(), Json::from(count)))
+            .collect();
+        let mut members: Vec<(String, Json)> = self
+            .metrics()
+            .iter()
+            .map(|&(name, value)| (name.to_string(), Json::from(value)))
+            .collect();
+        members.insert(1, ("counts".to_string(), Json::Object(counts)));
+        Json::Object(members)
+    }
+}
+
+impl fmt::Display for Size {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} commands (", self.fingerprint.instructions())?;
+        for (i, (&command, &count)) in COMMANDS.iter().zip(self.fingerprint.counts.iter()).enumerate() {
+            let separator = if i == 0 { "" } else { " " };
+            write!(f, "{}{}{}", separator, command as char, count)?;
+        }
+        write!(
+            f,
+            "), depth {}, {} optimized, {} bytes of source",
+            self.depth, self.optimized, self.fingerprint.size
+        )
+    }
+}
+
+/// Differences of the metrics of `other` from those of `size`, by name.
+pub fn difference(size: &Size, other: &Size) -> Vec<(&'static str, i64)> {
+    size.metrics()
+        .iter()
+        .zip(other.metrics().iter())
+        .map(|(&(name, a), &(_, b))| (name, b as i64 - a as i64))
+        .collect()
+}
+
+fn report<W: fmt::Write>(out: &mut W, files: &[(String, Size)], json: bool) -> fmt::Result {
+    for (file, size) in files {
+        if json {
+            let mut members = vec![("file".to_string(), Json::from(file.as_str()))];
+            if let Json::Object(metrics) = size.to_json() {
+                members.extend(metrics);
+            }
+            writeln!(out, "{}", Json::Object(members))?;
+        } else {
+            writeln!(out, "{}: {}", file, size)?;
+        }
+    }
+    if let [(file, size), (other_file, other)] = files {
+        let difference = difference(size, other);
+        if json {
+            let members = difference
+                .iter()
+                .map(|&(name, delta)| (name, Json::Number(delta as f64)))
+                .collect();
+            writeln!(out, "{}", Json::object(vec![("difference", Json::object(members))]))?;
+        } else {
+            let deltas: Vec<String> = difference
+                .iter()
+                .map(|&(name, delta)| format!("{:+} {}", delta, name))
+                .collect();
+            writeln!(out, "{} vs {}: {}", other_file, file, deltas.join(", "))?;
+        }
+    }
+    Ok(())
+}
+
+pub fn main<I: Iterator<Item = String>>(args: I) -> Result<(), BFIError> {
+    let mut json = false;
+    let mut files = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            "--json" => json = true,
+            _ if arg.starts_with('-') => crate::usage_error(&format!("Unknown option '{}'", arg), USAGE),
+            _ => files.push(arg),
+        }
+    }
+    if files.is_empty() || files.len() > 2 {
+        crate::usage_error("Give one file, or two to compare", USAGE);
+    }
+    let mut sizes = Vec::new();
+    for file in files {
+        let size = Size::of(&run::load(&file, &Options::default(), None)?)?;
+        sizes.push((file, size));
+    }
+    let mut out = String::new();
+    report(&mut out, &sizes, json).expect("writing to a String cannot fail");
+    print!("{}", out);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bfi::BFI;
+
+    use crate::json::Json;
+    use crate::size::{report, Size};
+
+    #[test]
+    fn test_size() {
+        let size = Size::of(&BFI::new("Echo: ,[.,]\n;; cell 0 = c\n+-><[-]".to_string())).unwrap();
+        assert_eq!(size.fingerprint.instructions(), 12);
+        assert_eq!(size.depth, 1);
+        assert_eq!(size.optimized, 5);
+        assert_eq!(
+            size.to_string(),
+            "12 commands (>1 <1 +1 -2 .1 ,2 [2 ]2), depth 1, 5 optimized, 33 bytes of source"
+        );
+        let json = size.to_json();
+        assert_eq!(json.get("optimized").and_then(Json::as_u64), Some(5));
+        assert_eq!(json.get("counts").and_then(|counts| counts.get(",")).and_then(Json::as_u64), Some(2));
+
+        let other = Size::of(&BFI::new("+[[.]]".to_string())).unwrap();
+        let files = vec![("a.b".to_string(), size), ("b.b".to_string(), other)];
+        let mut out = String::new();
+        report(&mut out, &files, false).unwrap();
+        assert_eq!(
+            out.lines().last(),
+            Some("b.b vs a.b: -6 commands, +1 depth, +1 optimized, -27 bytes")
+        );
+        let mut out = String::new();
+        report(&mut out, &files, true).unwrap();
+        let lines: Vec<Json> = out.lines().map(|line| Json::parse(line).unwrap()).collect();
+        assert_eq!(lines[1].get("file").and_then(Json::as_str), Some("b.b"));
+        assert_eq!(lines[2].get("difference").and_then(|d| d.get("depth")), Some(&Json::Number(1.0)));
+    }
+}
@@ -0,0 +1,199 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! Slices of a run: the part of a program that wrote some output.
+
+use std::collections::HashMap;
+use std::io::{self, Read};
+
+use bfi::{BFIError, BFI};
+
+use crate::fingerprint::COMMANDS;
+use crate::run::{self, Options};
+use crate::symex::position;
+
+const USAGE: &str = "\
+Usage: bfi slice [OPTIONS] FILE
+
+Runs FILE on the standard input until it has written the first --bytes bytes
+of its output, and prints the steps this took, the command that wrote the last
+of them, the shortest prefix of the program that writes them, and the lines
+the steps went to.
+
+Options:
+    --bytes N       Bytes of output to slice (default: all of them, or those
+                    written before the program fails)
+    --max-steps N   Fail after executing N commands";
+
+/// Lines listed by the report, those with the most steps first.
+const MAX_LINES: usize = 5;
+
+/// Where the first `bytes` bytes of the output of a run come from.
+#[derive(Debug, PartialEq)]
+struct Slice {
+    bytes: usize,
+    /// Steps up to the one writing the last of the bytes.
+    steps: u64,
+    /// Byte offset in the source of the command writing the last byte.
+    writer: usize,
+    /// Byte offset of the furthest command executed until then.
+    furthest: usize,
+    /// Commands up to the furthest one, i.e. of the shortest prefix of the
+    /// program writing the bytes, since no bracket after it has been reached.
+    prefix: usize,
+    /// Steps executed on each line, the most first.
+    lines: Vec<(usize, u64)>,
+}
+
+/// Runs `bfi` on `input` until the output has `bytes` bytes, or returns
+/// `None` if it ends with fewer.
+fn slice(bfi: &mut BFI, input: &[u8], bytes: usize) -> Result<Option<Slice>, BFIError> {
+    let mut input = input;
+    let mut output = Vec::new();
+    let mut steps: HashMap<usize, u64> = HashMap::new();
+    let mut writer = 0;
+    while output.len() < bytes {
+        let offset = match bfi.offset() {
+            Some(offset) => offset,
+            None => return Ok(None),
+        };
+        let written = output.len();
+        if !bfi.step(&mut input, &mut output)? {
+            return Ok(None);
+        }
+        *steps.entry(offset).or_default() += 1;
+        if output.len() > written {
+            writer = offset;
+        }
+    }
+    let furthest = steps.keys().copied().max().unwrap_or_default();
+    let prefix = bfi
+        .commands()
+        .filter(|&(offset, byte)| offset <= furthest && COMMANDS.contains(&byte))
+        .count();
+    let mut lines: HashMap<usize, u64> = HashMap::new();
+    for (&offset, &count) in &steps {
+        *lines.entry(position(bfi.code(), offset).0).or_default() += count;
+    }
+    let mut lines: Vec<(usize, u64)> = lines.into_iter().collect();
+    lines.sort_by_key(|&(line, count)| (std::cmp::Reverse(count), line));
+    Ok(Some(Slice {
+        bytes,
+        steps: bfi.steps(),
+        writer,
+        furthest,
+        prefix,
+        lines,
+    }))
+}
+
+/// Describes `slice` of the program `code`.
+fn report(code: &[u8], slice: &Slice) -> String {
+    let location = |offset| {
+        let (line, column) = position(code, offset);
+        format!("line {}, column {}", line, column)
+    };
+    let lines: Vec<String> = slice
+        .lines
+        .iter()
+        .take(MAX_LINES)
+        .map(|&(line, count)| format!("{}: {}", line, count))
+        .collect();
+    format!(
+        "the first {} bytes of output take {} steps and the first {} commands, up to {}\n\
+         the last byte is written at {}\n\
+         steps by line: {}",
+        slice.bytes,
+        slice.steps,
+        slice.prefix,
+        location(slice.furthest),
+        location(slice.writer),
+        lines.join(", ")
+    )
+}
+
+pub fn main<I: Iterator<Item = String>>(args: I) -> Result<(), BFIError> {
+    let mut args = args;
+    let mut bytes = None;
+    let mut options = Options::default();
+    let mut file = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            "--bytes" => bytes = Some(crate::option_value(&arg, args.next(), USAGE)),
+            "--max-steps" => options.limits.max_steps = Some(crate::option_value(&arg, args.next(), USAGE)),
+            _ if arg.starts_with('-') => crate::usage_error(&format!("Unknown option '{}'", arg), USAGE),
+            _ if file.is_none() => file = Some(arg),
+            _ => crate::usage_error("Give a single program", USAGE),
+        }
+    }
+    let file = file.unwrap_or_else(|| crate::usage_error("Give the program", USAGE));
+    let mut input = Vec::new();
+    io::stdin().lock().read_to_end(&mut input)?;
+    let mut bfi = run::load(&file, &options, None)?;
+    let bytes = match bytes {
+        Some(bytes) => bytes,
+        // A first run tells the length of the whole output, or of what
+        // it wrote before failing.
+        None => {
+            let mut output = Vec::new();
+            if let Err(err) = bfi.interpret(&mut &input[..], &mut output) {
+                eprintln!("{}: {}, slicing the output written before", file, err);
+            }
+            bfi = run::load(&file, &options, None)?;
+            output.len()
+        }
+    };
+    match slice(&mut bfi, &input, bytes)? {
+        Some(slice) if bytes > 0 => println!("{}: {}", file, report(bfi.code(), &slice).replace('\n', "\n    ")),
+        _ if bytes == 0 => println!("{}: no output to slice", file),
+        _ => println!("{}: the program ends before writing {} bytes", file, bytes),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bfi::BFI;
+
+    use crate::slice::{report, slice};
+
+    #[test]
+    fn test_slice() {
+        let code = "Print A: +++++ +++[>++++ ++++<-]>+.\n+.+.\nLoop: +[]";
+        let mut bfi = BFI::new(code.to_string());
+        let sliced = slice(&mut bfi, b"", 2).unwrap().unwrap();
+        assert_eq!(sliced.steps, 118);
+        assert_eq!(sliced.prefix, 26);
+        assert_eq!((sliced.writer, sliced.furthest), (37, 37));
+        assert_eq!(sliced.lines, vec![(1, 116), (2, 2)]);
+        assert_eq!(
+            report(code.as_bytes(), &sliced),
+            "the first 2 bytes of output take 118 steps and the first 26 commands, up to line 2, column 2\n\
+             the last byte is written at line 2, column 2\n\
+             steps by line: 1: 116, 2: 2"
+        );
+
+        let mut bfi = BFI::new("+.".to_string());
+        assert_eq!(slice(&mut bfi, b"", 2).unwrap(), None);
+    }
+}
@@ -0,0 +1,493 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! Bounded symbolic execution, with the bytes of the input as unknowns.
+//!
+//! A cell holds a constant or an input byte plus a constant, and an input
+//! byte is either known or known to differ from some values. Every bracket,
+//! and every `+` or `-` that may overflow, whose outcome depends on an
+//! unknown byte splits the path in two, so a loop counting an input byte
+//! down splits once per iteration.
+
+use bfi::{BFIError, EofPolicy, BFI};
+
+use crate::fingerprint::COMMANDS;
+use crate::run::{self, Options};
+
+const USAGE: &str = "\
+Usage: bfi symex [OPTIONS] FILE
+
+Experimental: runs FILE on an unknown input, following every path the input
+can take up to the bounds, and prints an input for each path that overflows a
+byte or leaves the memory, and, with --reach, for each path that reaches a
+command.
+
+Options:
+    --reach LINE:COLUMN
+                    Print the inputs reaching the command at LINE:COLUMN
+    --max-input N   Bytes of the longest input (default: 4)
+    --max-paths N   Paths to explore (default: 1000)
+    --max-steps N   Steps of each path (default: 10000)
+    --eof POLICY    What ',' stores once the input has ended: zero (default),
+                    minus-one, or unchanged
+    --wrapping      Let bytes wrap around instead of overflowing";
+
+/// Bounds and semantics of the exploration.
+#[derive(Debug, Clone, Copy)]
+struct Settings {
+    max_input: usize,
+    max_paths: usize,
+    max_steps: u64,
+    eof: EofPolicy,
+    wrapping: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            max_input: 4,
+            max_paths: 1000,
+            max_steps: 10_000,
+            eof: EofPolicy::Zero,
+            wrapping: false,
+        }
+    }
+}
+
+/// Value of a cell: `offset` plus the input byte `input`, if any, modulo 256.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Value {
+    input: Option<usize>,
+    offset: u8,
+}
+
+impl Value {
+    fn constant(offset: u8) -> Self {
+        Self { input: None, offset }
+    }
+}
+
+/// What a path knows of an input byte.
+#[derive(Debug, Clone, PartialEq)]
+enum Domain {
+    Is(u8),
+    Not(Vec<u8>),
+}
+
+/// A path through the program, with the input it assumes.
+#[derive(Debug, Clone)]
+struct Path {
+    pc: usize,
+    pointer: usize,
+    /// Cells up to the rightmost one visited.
+    cells: Vec<Value>,
+    inputs: Vec<Domain>,
+    /// Whether the input has ended.
+    ended: bool,
+    steps: u64,
+    /// Whether the path has reached the wanted command.
+    reached: bool,
+}
+
+impl Path {
+    fn new() -> Self {
+        Self {
+            pc: 0,
+            pointer: 0,
+            cells: vec![Value::constant(0)],
+            inputs: Vec::new(),
+            ended: false,
+            steps: 0,
+            reached: false,
+        }
+    }
+
+    /// Whether `value` is `target` on this path, or `None` if it may be
+    /// either.
+    fn equals(&self, value: Value, target: u8) -> Option<bool> {
+        let index = match value.input {
+            Some(index) => index,
+            None => return Some(value.offset == target),
+        };
+        let byte = target.wrapping_sub(value.offset);
+        match self.inputs[index] {
+            Domain::Is(known) => Some(known == byte),
+            Domain::Not(ref excluded) if excluded.contains(&byte) => Some(false),
+            // Only `byte` is left.
+            Domain::Not(ref excluded) if excluded.len() == 255 => Some(true),
+            Domain::Not(_) => None,
+        }
+    }
+
+    /// Restricts the input to those where `value` is `target`, or is not.
+    fn assume(&mut self, value: Value, target: u8, equal: bool) {
+        if let Some(index) = value.input {
+            let byte = target.wrapping_sub(value.offset);
+            match self.inputs[index] {
+                Domain::Not(_) if equal => self.inputs[index] = Domain::Is(byte),
+                Domain::Not(ref mut excluded) => excluded.push(byte),
+                Domain::Is(_) => (),
+            }
+        }
+    }
+
+    /// Byte `index` of an input taking this path.
+    fn byte(&self, index: usize) -> u8 {
+        match self.inputs[index] {
+            Domain::Is(byte) => byte,
+            Domain::Not(ref excluded) => (0..=255).find(|byte| !excluded.contains(byte)).unwrap_or_default(),
+        }
+    }
+
+    /// An input taking this path, as far as it has gone.
+    fn input(&self) -> Vec<u8> {
+        (0..self.inputs.len()).map(|index| self.byte(index)).collect()
+    }
+}
+
+/// How a path ended.
+#[derive(Debug)]
+enum Outcome {
+    Ended,
+    /// The path exceeded the steps.
+    Unfinished,
+    Failed(BFIError),
+}
+
+/// An input reaching the wanted command, or failing with `error`.
+#[derive(Debug)]
+struct Finding {
+    /// Byte offset of the command in the source.
+    offset: usize,
+    error: Option<BFIError>,
+    input: Vec<u8>,
+}
+
+#[derive(Debug, Default)]
+struct Exploration {
+    findings: Vec<Finding>,
+    ended: usize,
+    unfinished: usize,
+    /// Whether paths were left unexplored once `max_paths` was reached.
+    truncated: bool,
+}
+
+/// Explorer of the paths of a program.
+struct Engine<'a> {
+    bfi: &'a BFI,
+    /// Commands with their offsets in the source.
+    commands: Vec<(usize, u8)>,
+    /// Index of the matching bracket of each bracket.
+    jumps: Vec<usize>,
+    settings: Settings,
+    /// Index of the wanted command.
+    target: Option<usize>,
+}
+
+impl<'a> Engine<'a> {
+    /// Explores `bfi`, whose syntax has been checked.
+    fn new(bfi: &'a BFI, settings: Settings) -> Self {
+        let commands: Vec<(usize, u8)> = bfi.commands().filter(|(_, byte)| COMMANDS.contains(byte)).collect();
+        let mut jumps = vec![0; commands.len()];
+        let mut open = Vec::new();
+        for (index, &(_, byte)) in commands.iter().enumerate() {
+            match byte {
+                b'[' => open.push(index),
+                b']' => {
+                    if let Some(start) = open.pop() {
+                        jumps[start] = index;
+                        jumps[index] = start;
+                    }
+                }
+                _ => (),
+            }
+        }
+        Self {
+            bfi,
+            commands,
+            jumps,
+            settings,
+            target: None,
+        }
+    }
+
+    /// Whether `value` is `target` on `path`, assuming it is if either is
+    /// possible and adding to `forks` the path, still before its command,
+    /// assuming it is not.
+    fn decide(&self, path: &mut Path, value: Value, target: u8, forks: &mut Vec<Path>) -> bool {
+        path.equals(value, target).unwrap_or_else(|| {
+            let mut other = path.clone();
+            other.assume(value, target, false);
+            forks.push(other);
+            path.assume(value, target, true);
+            true
+        })
+    }
+
+    /// Executes the command at the program counter of `path`, or returns how
+    /// the path ended.
+    fn step(&self, path: &mut Path, forks: &mut Vec<Path>) -> Option<Outcome> {
+        let &(_, byte) = match self.commands.get(path.pc) {
+            Some(command) => command,
+            None => return Some(Outcome::Ended),
+        };
+        if path.steps >= self.settings.max_steps {
+            return Some(Outcome::Unfinished);
+        }
+        let cell = path.cells[path.pointer];
+        match byte {
+            b'>' => {
+                if self.bfi.cell(path.pointer + 1).is_none() {
+                    return Some(Outcome::Failed(BFIError::OutOfMemory));
+                }
+                path.pointer += 1;
+                if path.pointer == path.cells.len() {
+                    path.cells.push(Value::constant(0));
+                }
+            }
+            b'<' => {
+                if path.pointer == 0 {
+                    return Some(Outcome::Failed(BFIError::OutOfMemory));
+                }
+                path.pointer -= 1;
+            }
+            b'+' | b'-' => {
+                let (limit, delta) = if byte == b'+' {
+                    (i8::MAX as u8, 1)
+                } else {
+                    (i8::MIN as u8, u8::MAX)
+                };
+                if !self.settings.wrapping && self.decide(path, cell, limit, forks) {
+                    return Some(Outcome::Failed(BFIError::ArithmeticOverflow));
+                }
+                path.cells[path.pointer].offset = cell.offset.wrapping_add(delta);
+            }
+            b',' => {
+                if !path.ended && path.inputs.len() < self.settings.max_input {
+                    let mut other = path.clone();
+                    other.ended = true;
+                    forks.push(other);
+                    path.inputs.push(Domain::Not(Vec::new()));
+                    path.cells[path.pointer] = Value {
+                        input: Some(path.inputs.len() - 1),
+                        offset: 0,
+                    };
+                } else {
+                    path.ended = true;
+                    match self.settings.eof {
+                        EofPolicy::Zero => path.cells[path.pointer] = Value::constant(0),
+                        EofPolicy::MinusOne => path.cells[path.pointer] = Value::constant(u8::MAX),
+                        EofPolicy::Unchanged => (),
+                    }
+                }
+            }
+            b'[' | b']' => {
+                // `[` skips the loop on zero, and `]` repeats it otherwise.
+                let zero = self.decide(path, cell, 0, forks);
+                if zero == (byte == b'[') {
+                    path.pc = self.jumps[path.pc];
+                }
+            }
+            _ => (),
+        }
+        path.pc += 1;
+        path.steps += 1;
+        None
+    }
+
+    fn explore(&self) -> Exploration {
+        let mut exploration = Exploration::default();
+        let mut pending = vec![Path::new()];
+        let mut paths = 1;
+        let mut forks = Vec::new();
+        while let Some(mut path) = pending.pop() {
+            let outcome = loop {
+                if self.target == Some(path.pc) && !path.reached {
+                    path.reached = true;
+                    exploration.findings.push(Finding {
+                        offset: self.commands[path.pc].0,
+                        error: None,
+                        input: path.input(),
+                    });
+                }
+                let outcome = self.step(&mut path, &mut forks);
+                for fork in forks.drain(..) {
+                    if paths < self.settings.max_paths {
+                        paths += 1;
+                        pending.push(fork);
+                    } else {
+                        exploration.truncated = true;
+                    }
+                }
+                if let Some(outcome) = outcome {
+                    break outcome;
+                }
+            };
+            match outcome {
+                Outcome::Ended => exploration.ended += 1,
+                Outcome::Unfinished => exploration.unfinished += 1,
+                Outcome::Failed(error) => exploration.findings.push(Finding {
+                    offset: self.commands[path.pc].0,
+                    error: Some(error),
+                    input: path.input(),
+                }),
+            }
+        }
+        exploration
+    }
+}
+
+/// 1-based line and column of the byte `offset` of `code`.
+pub fn position(code: &[u8], offset: usize) -> (usize, usize) {
+    let before = &code[..offset];
+    let line_start = before.iter().rposition(|&byte| byte == b'\n').map_or(0, |i| i + 1);
+    let line = before.iter().filter(|&&byte| byte == b'\n').count() + 1;
+    // UTF-8 continuation bytes do not start a character.
+    let column = before[line_start..].iter().filter(|&&byte| byte & 0xc0 != 0x80).count() + 1;
+    (line, column)
+}
+
+fn parse_position(text: &str) -> Result<(usize, usize), String> {
+    let (line, column) = text.split_once(':').ok_or("expected LINE:COLUMN")?;
+    let number = |text: &str| text.parse::<usize>().map_err(|err| err.to_string());
+    Ok((number(line)?, number(column)?))
+}
+
+pub fn main<I: Iterator<Item = String>>(args: I) -> Result<(), BFIError> {
+    let mut args = args;
+    let mut settings = Settings::default();
+    let mut reach = None;
+    let mut file = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            "--reach" => {
+                let value: String = crate::option_value(&arg, args.next(), USAGE);
+                reach = Some(parse_position(&value).unwrap_or_else(|err| {
+                    crate::usage_error(&format!("Invalid value '{}' for {}: {}", value, arg, err), USAGE)
+                }));
+            }
+            "--max-input" => settings.max_input = crate::option_value(&arg, args.next(), USAGE),
+            "--max-paths" => settings.max_paths = crate::option_value(&arg, args.next(), USAGE),
+            "--max-steps" => settings.max_steps = crate::option_value(&arg, args.next(), USAGE),
+            "--eof" => settings.eof = crate::option_value(&arg, args.next(), USAGE),
+            "--wrapping" => settings.wrapping = true,
+            _ if arg.starts_with('-') => crate::usage_error(&format!("Unknown option '{}'", arg), USAGE),
+            _ if file.is_none() => file = Some(arg),
+            _ => crate::usage_error("Give a single program", USAGE),
+        }
+    }
+    let file = file.unwrap_or_else(|| crate::usage_error("Give the program", USAGE));
+    let bfi = run::load(&file, &Options::default(), None)?;
+    let mut engine = Engine::new(&bfi, settings);
+    if let Some((line, column)) = reach {
+        engine.target = engine
+            .commands
+            .iter()
+            .position(|&(offset, _)| position(bfi.code(), offset) == (line, column));
+        if engine.target.is_none() {
+            crate::usage_error(&format!("No command at line {}, column {} of {}", line, column, file), USAGE);
+        }
+    }
+    let exploration = engine.explore();
+    for finding in &exploration.findings {
+        let (line, column) = position(bfi.code(), finding.offset);
+        let input = finding.input.escape_ascii();
+        match finding.error {
+            Some(ref error) => println!("{}: {} at line {}, column {} with input \"{}\"", file, error, line, column, input),
+            None => println!("{}: Reached line {}, column {} with input \"{}\"", file, line, column, input),
+        }
+    }
+    let failed = exploration.findings.iter().filter(|finding| finding.error.is_some()).count();
+    eprintln!(
+        "{}: {} paths: {} ended, {} failed, {} unfinished within {} steps{}",
+        file,
+        exploration.ended + failed + exploration.unfinished,
+        exploration.ended,
+        failed,
+        exploration.unfinished,
+        settings.max_steps,
+        if exploration.truncated {
+            ", more left after --max-paths"
+        } else {
+            ""
+        }
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bfi::{BFIError, BFI};
+
+    use crate::symex::{position, Engine, Exploration, Settings};
+
+    fn explore(code: &str, settings: Settings, target: Option<usize>) -> Exploration {
+        let bfi = BFI::new(code.to_string());
+        let mut engine = Engine::new(&bfi, settings);
+        engine.target = target;
+        engine.explore()
+    }
+
+    #[test]
+    fn test_explore() {
+        // Overflows on 127 only, and ends on the end of input or any other byte.
+        let settings = Settings {
+            max_input: 1,
+            ..Settings::default()
+        };
+        let exploration = explore(",+", settings, None);
+        assert_eq!((exploration.ended, exploration.unfinished, exploration.truncated), (2, 0, false));
+        assert_eq!(exploration.findings.len(), 1);
+        assert!(matches!(exploration.findings[0].error, Some(BFIError::ArithmeticOverflow)));
+        assert_eq!((exploration.findings[0].offset, exploration.findings[0].input.as_slice()), (1, &b"\x7f"[..]));
+
+        let exploration = explore("x<", settings, None);
+        assert!(matches!(exploration.findings[0].error, Some(BFIError::OutOfMemory)));
+        assert_eq!(exploration.findings[0].input, b"");
+
+        // The output needs a byte other than 0, 1, and 2, then never ends.
+        let wrapping = Settings { wrapping: true, ..settings };
+        let exploration = explore(",[-[-[.]]]", wrapping, Some(6));
+        assert_eq!(exploration.findings.len(), 1);
+        assert!(exploration.findings[0].error.is_none());
+        assert_eq!(exploration.findings[0].input, b"\x03");
+        assert_eq!((exploration.ended, exploration.unfinished), (4, 1));
+
+        let exploration = explore(
+            ",[-]",
+            Settings {
+                max_paths: 10,
+                ..wrapping
+            },
+            None,
+        );
+        assert!(exploration.truncated);
+        assert_eq!(exploration.ended, 10);
+    }
+
+    #[test]
+    fn test_position() {
+        assert_eq!(position(b"+\n\xc3\xa9.", 4), (2, 2));
+        assert_eq!(position(b"+", 0), (1, 1));
+    }
+}
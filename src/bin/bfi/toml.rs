@@ -0,0 +1,554 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! Parser of the subset of TOML used by configuration and spec files.
+//!
+//! Supports tables, arrays of tables, dotted keys, strings (basic, literal,
+//! and multi-line), integers, floats, booleans, arrays, and inline tables.
+//! Dates are not supported. Documents are represented as `Json` objects.
+//!
+//! Like `json`, `sha256`, and `base64`, it is written here rather than taken
+//! from the `toml` crate so that the default build of `bfi` has no
+//! dependencies; the optional features are the only ones pulling crates in.
+
+use crate::json::Json;
+
+pub fn parse(text: &str) -> Result<Json, String> {
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        pos: 0,
+        line: 1,
+    };
+    let mut root = Json::Object(Vec::new());
+    let mut current: Vec<String> = Vec::new();
+    loop {
+        parser.skip_blank();
+        match parser.peek() {
+            None => return Ok(root),
+            Some('[') => {
+                parser.pos += 1;
+                let array = parser.eat('[');
+                let path = parser.key()?;
+                parser.expect(']')?;
+                if array {
+                    parser.expect(']')?;
+                }
+                let (last, parent) = path.split_last().unwrap();
+                let members = parser.table(&mut root, parent)?;
+                match members.iter_mut().find(|(key, _)| key == last) {
+                    Some((_, Json::Array(tables))) if array => {
+                        tables.push(Json::Object(Vec::new()))
+                    }
+                    Some((_, Json::Object(_))) if !array => {}
+                    Some(_) => return Err(parser.error(&format!("duplicate key '{}'", last))),
+                    None if array => {
+                        members.push((last.clone(), Json::Array(vec![Json::Object(Vec::new())])))
+                    }
+                    None => members.push((last.clone(), Json::Object(Vec::new()))),
+                }
+                current = path;
+            }
+            Some(_) => {
+                let path = parser.key()?;
+                parser.skip_whitespace();
+                parser.expect('=')?;
+                let value = parser.value()?;
+                let mut full = current.clone();
+                full.extend(path);
+                let (last, parent) = full.split_last().unwrap();
+                parser.insert(&mut root, parent, last, value)?;
+            }
+        }
+        parser.skip_whitespace();
+        parser.skip_comment();
+        match parser.peek() {
+            None => {}
+            Some('\n') => {}
+            Some('\r') if parser.chars.get(parser.pos + 1) == Some(&'\n') => {}
+            Some(_) => return Err(parser.error("expected the end of line")),
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Parser {
+    fn error(&self, message: &str) -> String {
+        format!("{} at line {}", message, self.line)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", c)))
+        }
+    }
+
+    fn starts_with(&self, s: &str) -> bool {
+        s.chars()
+            .enumerate()
+            .all(|(i, c)| self.chars.get(self.pos + i) == Some(&c))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek() == Some(' ') || self.peek() == Some('\t') {
+            self.pos += 1;
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            while self.peek().is_some() && self.peek() != Some('\n') {
+                self.pos += 1;
+            }
+        }
+    }
+
+    /// Skips whitespace, comments, and newlines.
+    fn skip_blank(&mut self) {
+        loop {
+            self.skip_whitespace();
+            self.skip_comment();
+            match self.peek() {
+                Some('\n') => {
+                    self.pos += 1;
+                    self.line += 1;
+                }
+                Some('\r') => self.pos += 1,
+                _ => return,
+            }
+        }
+    }
+
+    /// Parses a possibly dotted key.
+    fn key(&mut self) -> Result<Vec<String>, String> {
+        let mut path = Vec::new();
+        loop {
+            self.skip_whitespace();
+            let part = match self.peek() {
+                Some('"') => self.basic_string()?,
+                Some('\'') => self.literal_string()?,
+                _ => {
+                    let start = self.pos;
+                    while self
+                        .peek()
+                        .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+                    {
+                        self.pos += 1;
+                    }
+                    if start == self.pos {
+                        return Err(self.error("expected a key"));
+                    }
+                    self.chars[start..self.pos].iter().collect()
+                }
+            };
+            path.push(part);
+            self.skip_whitespace();
+            if !self.eat('.') {
+                return Ok(path);
+            }
+        }
+    }
+
+    /// Returns the members of the table at `path`, creating missing tables.
+    /// An array of tables stands for its last table.
+    fn table<'a>(
+        &self,
+        root: &'a mut Json,
+        path: &[String],
+    ) -> Result<&'a mut Vec<(String, Json)>, String> {
+        let mut node = root;
+        for key in path {
+            let members = match node {
+                Json::Object(members) => members,
+                _ => unreachable!(),
+            };
+            let index = match members.iter().position(|(k, _)| k == key) {
+                Some(index) => index,
+                None => {
+                    members.push((key.clone(), Json::Object(Vec::new())));
+                    members.len() - 1
+                }
+            };
+            node = match &mut members[index].1 {
+                Json::Array(tables) => tables.last_mut(),
+                value => Some(value),
+            }
+            .filter(|value| matches!(value, Json::Object(_)))
+            .ok_or_else(|| self.error(&format!("'{}' is not a table", key)))?;
+        }
+        match node {
+            Json::Object(members) => Ok(members),
+            _ => unreachable!(),
+        }
+    }
+
+    fn insert(
+        &self,
+        root: &mut Json,
+        path: &[String],
+        key: &str,
+        value: Json,
+    ) -> Result<(), String> {
+        let members = self.table(root, path)?;
+        if members.iter().any(|(k, _)| k == key) {
+            return Err(self.error(&format!("duplicate key '{}'", key)));
+        }
+        members.push((key.to_string(), value));
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('"') if self.starts_with("\"\"\"") => self.multiline_string().map(Json::String),
+            Some('"') => self.basic_string().map(Json::String),
+            Some('\'') if self.starts_with("'''") => {
+                self.multiline_literal_string().map(Json::String)
+            }
+            Some('\'') => self.literal_string().map(Json::String),
+            Some('[') => self.array(),
+            Some('{') => self.inline_table(),
+            Some('t') if self.starts_with("true") => {
+                self.pos += 4;
+                Ok(Json::Bool(true))
+            }
+            Some('f') if self.starts_with("false") => {
+                self.pos += 5;
+                Ok(Json::Bool(false))
+            }
+            Some(c) if c == '+' || c == '-' || c.is_ascii_digit() => self.number(),
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("expected a value")),
+        }
+    }
+
+    fn array(&mut self) -> Result<Json, String> {
+        self.expect('[')?;
+        let mut values = Vec::new();
+        loop {
+            self.skip_blank();
+            if self.eat(']') {
+                return Ok(Json::Array(values));
+            }
+            values.push(self.value()?);
+            self.skip_blank();
+            if !self.eat(',') {
+                self.skip_blank();
+                self.expect(']')?;
+                return Ok(Json::Array(values));
+            }
+        }
+    }
+
+    fn inline_table(&mut self) -> Result<Json, String> {
+        self.expect('{')?;
+        let mut table = Json::Object(Vec::new());
+        self.skip_whitespace();
+        if self.eat('}') {
+            return Ok(table);
+        }
+        loop {
+            let path = self.key()?;
+            self.expect('=')?;
+            let value = self.value()?;
+            let (last, parent) = path.split_last().unwrap();
+            self.insert(&mut table, parent, last, value)?;
+            self.skip_whitespace();
+            if self.eat('}') {
+                return Ok(table);
+            }
+            self.expect(',')?;
+        }
+    }
+
+    fn escape(&mut self, s: &mut String) -> Result<(), String> {
+        let c = self
+            .peek()
+            .ok_or_else(|| self.error("unterminated string"))?;
+        self.pos += 1;
+        match c {
+            '"' => s.push('"'),
+            '\\' => s.push('\\'),
+            'b' => s.push('\u{8}'),
+            'f' => s.push('\u{c}'),
+            'n' => s.push('\n'),
+            'r' => s.push('\r'),
+            't' => s.push('\t'),
+            'u' | 'U' => {
+                let len = if c == 'u' { 4 } else { 8 };
+                let end = self.pos + len;
+                if end > self.chars.len() {
+                    return Err(self.error("invalid unicode escape"));
+                }
+                let hex: String = self.chars[self.pos..end].iter().collect();
+                let code = u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .and_then(std::char::from_u32)
+                    .ok_or_else(|| self.error("invalid unicode escape"))?;
+                s.push(code);
+                self.pos = end;
+            }
+            _ => return Err(self.error("invalid escape")),
+        }
+        Ok(())
+    }
+
+    fn basic_string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            match self.peek() {
+                Some('"') => {
+                    self.pos += 1;
+                    return Ok(s);
+                }
+                Some('\\') => {
+                    self.pos += 1;
+                    self.escape(&mut s)?;
+                }
+                Some('\n') | None => return Err(self.error("unterminated string")),
+                Some(c) => {
+                    self.pos += 1;
+                    s.push(c);
+                }
+            }
+        }
+    }
+
+    fn literal_string(&mut self) -> Result<String, String> {
+        self.expect('\'')?;
+        let start = self.pos;
+        loop {
+            match self.peek() {
+                Some('\'') => {
+                    let s = self.chars[start..self.pos].iter().collect();
+                    self.pos += 1;
+                    return Ok(s);
+                }
+                Some('\n') | None => return Err(self.error("unterminated string")),
+                Some(_) => self.pos += 1,
+            }
+        }
+    }
+
+    /// Skips the newline right after the opening delimiter of a multi-line string.
+    fn skip_first_newline(&mut self) {
+        if self.starts_with("\r\n") {
+            self.pos += 2;
+            self.line += 1;
+        } else if self.eat('\n') {
+            self.line += 1;
+        }
+    }
+
+    fn multiline_string(&mut self) -> Result<String, String> {
+        self.pos += 3;
+        self.skip_first_newline();
+        let mut s = String::new();
+        loop {
+            if self.starts_with("\"\"\"") {
+                self.pos += 3;
+                return Ok(s);
+            }
+            match self.peek() {
+                Some('\\') => {
+                    self.pos += 1;
+                    if matches!(
+                        self.peek(),
+                        Some(' ') | Some('\t') | Some('\r') | Some('\n')
+                    ) {
+                        // A line ending backslash trims the following whitespace.
+                        while let Some(c) = self.peek().filter(|c| c.is_whitespace()) {
+                            self.pos += 1;
+                            if c == '\n' {
+                                self.line += 1;
+                            }
+                        }
+                    } else {
+                        self.escape(&mut s)?;
+                    }
+                }
+                Some(c) => {
+                    self.pos += 1;
+                    if c == '\n' {
+                        self.line += 1;
+                    }
+                    s.push(c);
+                }
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    fn multiline_literal_string(&mut self) -> Result<String, String> {
+        self.pos += 3;
+        self.skip_first_newline();
+        let mut s = String::new();
+        loop {
+            if self.starts_with("'''") {
+                self.pos += 3;
+                return Ok(s);
+            }
+            let c = self
+                .peek()
+                .ok_or_else(|| self.error("unterminated string"))?;
+            self.pos += 1;
+            if c == '\n' {
+                self.line += 1;
+            }
+            s.push(c);
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || "+-._".contains(c))
+        {
+            self.pos += 1;
+        }
+        let text: String = self.chars[start..self.pos]
+            .iter()
+            .filter(|&&c| c != '_')
+            .collect();
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, text.strip_prefix('+').unwrap_or(&text)),
+        };
+        let radix = match digits.get(..2) {
+            Some("0x") => 16,
+            Some("0o") => 8,
+            Some("0b") => 2,
+            _ => 10,
+        };
+        let value = if radix != 10 {
+            u64::from_str_radix(&digits[2..], radix)
+                .ok()
+                .map(|n| n as f64)
+        } else {
+            digits.parse::<f64>().ok().filter(|_| {
+                digits
+                    .chars()
+                    .all(|c| c.is_ascii_digit() || "eE.+-".contains(c))
+            })
+        };
+        match value {
+            Some(n) if negative => Ok(Json::Number(-n)),
+            Some(n) => Ok(Json::Number(n)),
+            None => Err(self.error("invalid number")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::json::Json;
+    use crate::toml::parse;
+
+    #[test]
+    fn test_parse() {
+        let doc = parse(
+            r#"
+# comment
+title = "bfi" # trailing comment
+count = 1_000
+hex = 0xff
+flags = [true, false,
+  # inside an array
+]
+
+[limits]
+max_steps = 10
+"quoted key".sub = 'C:\path'
+
+[[case]]
+input = """
+line 1
+line 2\n"""
+
+[[case]]
+input = '''raw\n'''
+point = { x = 1, y = -2 }
+"#,
+        )
+        .unwrap();
+        assert_eq!(doc.get("title").and_then(Json::as_str), Some("bfi"));
+        assert_eq!(doc.get("count").and_then(Json::as_u64), Some(1000));
+        assert_eq!(doc.get("hex").and_then(Json::as_u64), Some(255));
+        assert_eq!(
+            doc.get("flags"),
+            Some(&Json::Array(vec![Json::Bool(true), Json::Bool(false)]))
+        );
+        let limits = doc.get("limits").unwrap();
+        assert_eq!(limits.get("max_steps").and_then(Json::as_u64), Some(10));
+        assert_eq!(
+            limits
+                .get("quoted key")
+                .and_then(|t| t.get("sub"))
+                .and_then(Json::as_str),
+            Some("C:\\path")
+        );
+        let cases = match doc.get("case") {
+            Some(Json::Array(cases)) => cases,
+            _ => panic!(),
+        };
+        assert_eq!(
+            cases[0].get("input").and_then(Json::as_str),
+            Some("line 1\nline 2\n")
+        );
+        assert_eq!(cases[1].get("input").and_then(Json::as_str), Some("raw\\n"));
+        assert_eq!(
+            cases[1].get("point").and_then(|p| p.get("y")),
+            Some(&Json::Number(-2.0))
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("a = ").is_err());
+        assert!(parse("a = 1 b = 2").is_err());
+        assert!(parse("a = 1\na = 2").is_err());
+        assert!(parse("a = \"open").is_err());
+        assert!(parse("a = 1979-05-27").is_err());
+        assert_eq!(
+            parse("a = 1\n[a]").unwrap_err(),
+            "duplicate key 'a' at line 2"
+        );
+    }
+}
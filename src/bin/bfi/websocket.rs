@@ -0,0 +1,505 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! Interactive sessions over WebSocket (RFC 6455), enabled by the
+//! `websocket` feature.
+//!
+//! The first message of the client is a JSON object like the body of
+//! `POST /run` (without the input). Then the payload of every message of the
+//! client is fed to `,`, and closing the connection is the end of input. The
+//! output is sent in binary messages of the bytes written by `.` since the
+//! previous one, whenever the program waits for input or yields, and at most
+//! every 4096 bytes. The session ends with a text message holding the JSON
+//! status, stats, and error of the run, followed by a close frame.
+//!
+//! Sessions run as tasks of a shared runtime on `BFI::interpret_async`.
+
+use std::io::{self, Cursor, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::runtime::{Handle, Runtime};
+use tokio::sync::Mutex;
+
+use bfi::{Limits, BFI};
+
+use crate::base64;
+use crate::json::Json;
+use crate::serve::{self, HttpError, Request};
+
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const MAX_PAYLOAD_BYTES: u64 = 1024 * 1024;
+const IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+/// Largest binary message of output.
+const OUTPUT_CHUNK_BYTES: usize = 4096;
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xa;
+
+/// SHA-1 (RFC 3174), only used to compute `Sec-WebSocket-Accept`.
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [
+        0x6745_2301,
+        0xefcd_ab89,
+        0x98ba_dcfe,
+        0x1032_5476,
+        0xc3d2_e1f0,
+    ];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for i in 0..16 {
+            w[i] = u32::from_be_bytes([
+                block[4 * i],
+                block[4 * i + 1],
+                block[4 * i + 2],
+                block[4 * i + 3],
+            ]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, wi) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5a82_7999),
+                20..=39 => (b ^ c ^ d, 0x6ed9_eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1b_bcdc),
+                _ => (b ^ c ^ d, 0xca62_c1d6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*wi);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e].iter()) {
+            *h = h.wrapping_add(*v);
+        }
+    }
+
+    let mut digest = [0u8; 20];
+    for (i, h) in h.iter().enumerate() {
+        digest[4 * i..4 * i + 4].copy_from_slice(&h.to_be_bytes());
+    }
+    digest
+}
+
+/// Computes `Sec-WebSocket-Accept` for the `Sec-WebSocket-Key` of the client.
+fn accept_key(key: &str) -> String {
+    base64::encode(&sha1(format!("{}{}", key, GUID).as_bytes()))
+}
+
+struct Frame {
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+/// Reads one masked frame sent by a client.
+async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Frame> {
+    let mut head = [0u8; 2];
+    reader.read_exact(&mut head).await?;
+    let fin = head[0] & 0x80 != 0;
+    let opcode = head[0] & 0x0f;
+    if head[1] & 0x80 == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Unmasked client frame",
+        ));
+    }
+
+    let len = match head[1] & 0x7f {
+        126 => {
+            let mut len = [0u8; 2];
+            reader.read_exact(&mut len).await?;
+            u16::from_be_bytes(len) as u64
+        }
+        127 => {
+            let mut len = [0u8; 8];
+            reader.read_exact(&mut len).await?;
+            u64::from_be_bytes(len)
+        }
+        len => len as u64,
+    };
+    if len > MAX_PAYLOAD_BYTES {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Frame too large",
+        ));
+    }
+
+    let mut mask = [0u8; 4];
+    reader.read_exact(&mut mask).await?;
+    let mut payload = vec![0; len as usize];
+    reader.read_exact(&mut payload).await?;
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+    Ok(Frame {
+        fin,
+        opcode,
+        payload,
+    })
+}
+
+/// Writes one unmasked frame, as servers do.
+async fn write_frame<W: AsyncWrite + Unpin>(writer: &mut W, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        len if len < 126 => frame.push(len as u8),
+        len if len <= u16::MAX as usize => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    writer.write_all(&frame).await?;
+    writer.flush().await
+}
+
+/// Reads a whole data message, answering pings on the way.
+/// Returns `None` once the client closed the connection.
+async fn read_message<R, W>(reader: &mut R, writer: &Mutex<W>) -> io::Result<Option<Vec<u8>>>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut message = Vec::new();
+    loop {
+        let frame = match tokio::time::timeout(IDLE_TIMEOUT, read_frame(reader)).await {
+            Ok(Ok(frame)) => frame,
+            Ok(Err(ref err)) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Ok(Err(err)) => return Err(err),
+            Err(_) => return Err(io::ErrorKind::TimedOut.into()),
+        };
+        match frame.opcode {
+            OPCODE_CONTINUATION | OPCODE_TEXT | OPCODE_BINARY => {
+                if message.len() as u64 + frame.payload.len() as u64 > MAX_PAYLOAD_BYTES {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Message too large",
+                    ));
+                }
+                message.extend_from_slice(&frame.payload);
+                if frame.fin {
+                    return Ok(Some(message));
+                }
+            }
+            OPCODE_PING => write_frame(&mut *writer.lock().await, OPCODE_PONG, &frame.payload).await?,
+            OPCODE_CLOSE => return Ok(None),
+            _ => (),
+        }
+    }
+}
+
+/// Feeds the messages of the client to `input` until the client closes the
+/// connection.
+async fn feed<R, W, I>(reader: &mut R, writer: &Mutex<W>, mut input: I) -> io::Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+    I: AsyncWrite + Unpin,
+{
+    while let Some(message) = read_message(reader, writer).await? {
+        if input.write_all(&message).await.is_err() {
+            // The program has ended.
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Sends what the program wrote to `output` until it has ended.
+async fn drain<W, O>(writer: &Mutex<W>, mut output: O) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+    O: AsyncRead + Unpin,
+{
+    let mut chunk = [0u8; OUTPUT_CHUNK_BYTES];
+    loop {
+        let len = output.read(&mut chunk).await?;
+        if len == 0 {
+            return Ok(());
+        }
+        write_frame(&mut *writer.lock().await, OPCODE_BINARY, &chunk[..len]).await?;
+    }
+}
+
+/// Returns the summary of a run that was stopped after `max_time`.
+fn timed_out(bfi: &BFI) -> Json {
+    Json::object(serve::timed_out_members(bfi))
+}
+
+async fn run<R, W>(mut reader: R, writer: W, allowed: &Limits, max_time: Duration) -> io::Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let writer = Mutex::new(writer);
+    let first = match read_message(&mut reader, &writer).await? {
+        Some(message) => message,
+        None => return Ok(()),
+    };
+    let start = std::str::from_utf8(&first)
+        .map_err(|_| HttpError::new(400, "First message is not UTF-8"))
+        .and_then(|text| Json::parse(text).map_err(|err| HttpError::new(400, err)))
+        .and_then(|body| {
+            Ok((
+                serve::request_code(&body)?.to_string(),
+                serve::request_limits(&body, allowed)?,
+            ))
+        });
+
+    let summary = match start {
+        Ok((code, limits)) => {
+            let mut bfi = BFI::new(code);
+            bfi.set_limits(limits);
+            let (mut input, client_input) = tokio::io::duplex(MAX_PAYLOAD_BYTES as usize);
+            let (output, client_output) = tokio::io::duplex(OUTPUT_CHUNK_BYTES);
+            let execution = async {
+                let mut output = output;
+                let result = tokio::time::timeout(max_time, bfi.interpret_async(&mut input, &mut output)).await;
+                // Ends `drain` once the rest of the output has been sent.
+                drop(output);
+                result
+            };
+            let (result, drained) = {
+                let execution = async { tokio::join!(execution, drain(&writer, client_output)) };
+                let feeding = feed(&mut reader, &writer, client_input);
+                tokio::pin!(execution, feeding);
+                tokio::select! {
+                    outcome = &mut execution => outcome,
+                    fed = &mut feeding => {
+                        fed?;
+                        execution.await
+                    }
+                }
+            };
+            drained?;
+            match result {
+                Ok(Err(bfi::BFIError::Io(err))) => return Err(err),
+                Ok(result) => Json::object(serve::result_members(&result, &bfi)),
+                Err(_) => timed_out(&bfi),
+            }
+        }
+        Err(err) => serve::error_body(&err.message),
+    };
+    let mut writer = writer.into_inner();
+    write_frame(&mut writer, OPCODE_TEXT, summary.to_string().as_bytes()).await?;
+    write_frame(&mut writer, OPCODE_CLOSE, &1000u16.to_be_bytes()).await
+}
+
+/// Starts the runtime running the sessions on a thread of its own.
+pub fn runtime() -> io::Result<Handle> {
+    let runtime = Runtime::new()?;
+    let handle = runtime.handle().clone();
+    std::thread::spawn(move || runtime.block_on(std::future::pending::<()>()));
+    Ok(handle)
+}
+
+/// Completes the WebSocket handshake of `request` and runs the session on
+/// `sessions`, stopping the program after `max_time`.
+///
+/// `buffered` holds the bytes read from `stream` after the request.
+pub fn session(
+    request: &Request,
+    buffered: Vec<u8>,
+    stream: TcpStream,
+    allowed: Limits,
+    max_time: Duration,
+    sessions: &Handle,
+) -> io::Result<()> {
+    let upgrade = request
+        .header("upgrade")
+        .is_some_and(|value| value.eq_ignore_ascii_case("websocket"));
+    let key = match request.header("sec-websocket-key") {
+        Some(key) if upgrade && request.method == "GET" => key,
+        _ => {
+            let body = serve::error_body("Expected a WebSocket upgrade");
+            return serve::write_response(&mut { &stream }, 400, &body);
+        }
+    };
+
+    write!(
+        &mut { &stream },
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(key)
+    )?;
+    stream.set_read_timeout(None)?;
+    stream.set_nonblocking(true)?;
+    sessions.spawn(async move {
+        let result = async {
+            let (reader, writer) = tokio::net::TcpStream::from_std(stream)?.into_split();
+            run(Cursor::new(buffered).chain(reader), writer, &allowed, max_time).await
+        };
+        if let Err(err) = result.await {
+            eprintln!("{}", err);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::time::Duration;
+
+    use bfi::Limits;
+
+    use crate::json::Json;
+    use crate::websocket::{accept_key, read_frame, run, sha1, write_frame};
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    fn client_frame(opcode: u8, fin: bool, payload: &[u8]) -> Vec<u8> {
+        let mask = [0x12, 0x34, 0x56, 0x78];
+        let mut frame = vec![
+            if fin { 0x80 } else { 0 } | opcode,
+            0x80 | payload.len() as u8,
+        ];
+        frame.extend_from_slice(&mask);
+        frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        frame
+    }
+
+    /// Splits the frames written by the server into `(opcode, payload)` pairs.
+    fn server_frames(mut bytes: &[u8]) -> Vec<(u8, Vec<u8>)> {
+        let mut frames = Vec::new();
+        while !bytes.is_empty() {
+            assert!(bytes[1] < 127);
+            let (len, start) = match bytes[1] {
+                126 => (u16::from_be_bytes([bytes[2], bytes[3]]) as usize, 4),
+                len => (len as usize, 2),
+            };
+            frames.push((bytes[0] & 0x0f, bytes[start..start + len].to_vec()));
+            bytes = &bytes[start + len..];
+        }
+        frames
+    }
+
+    /// Runs a session on the frames of `client` and returns the frames of the server.
+    fn session(client: Vec<u8>, max_time: Duration) -> Vec<(u8, Vec<u8>)> {
+        let mut written = Vec::new();
+        block_on(run(&client[..], &mut written, &Limits::default(), max_time)).unwrap();
+        server_frames(&written)
+    }
+
+    #[test]
+    fn test_sha1() {
+        let hex = |digest: [u8; 20]| {
+            digest
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>()
+        };
+        assert_eq!(hex(sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(
+            hex(sha1(b"abc")),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        assert_eq!(
+            hex(sha1(&[b'a'; 1000])),
+            "291e9a6c66994949b57ba5e650361e98fc36b1ba"
+        );
+    }
+
+    #[test]
+    fn test_accept_key() {
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn test_frames() {
+        let frame = client_frame(1, true, b"hello");
+        let read = block_on(read_frame(&mut &frame[..])).unwrap();
+        assert!(read.fin);
+        assert_eq!(read.opcode, 1);
+        assert_eq!(read.payload, b"hello");
+
+        let mut unmasked = Vec::new();
+        block_on(write_frame(&mut unmasked, 2, b"hi")).unwrap();
+        assert_eq!(unmasked, [0x82, 2, b'h', b'i']);
+        assert!(block_on(read_frame(&mut &unmasked[..])).is_err());
+
+        let mut long = Vec::new();
+        block_on(write_frame(&mut long, 2, &[0; 300])).unwrap();
+        assert_eq!(long[..4], [0x82, 126, 1, 44]);
+    }
+
+    #[test]
+    fn test_run() {
+        let mut client = client_frame(1, true, br#"{"code": ",[+.,]"}"#);
+        client.extend(client_frame(2, false, b"a"));
+        client.extend(client_frame(9, true, b"ping"));
+        client.extend(client_frame(0, true, b"b"));
+        client.extend(client_frame(8, true, &[]));
+
+        let frames = session(client, Duration::from_secs(10));
+        assert_eq!(frames[0], (0xa, b"ping".to_vec()));
+        assert_eq!(frames[1], (2, b"bc".to_vec()));
+        let summary = Json::parse(std::str::from_utf8(&frames[2].1).unwrap()).unwrap();
+        assert_eq!(frames[2].0, 1);
+        assert_eq!(summary.get("status").and_then(Json::as_str), Some("ok"));
+        assert_eq!(frames[3], (8, vec![0x03, 0xe8]));
+
+        // The output is batched, and the session stops after the time limit.
+        let client = client_frame(1, true, br#"{"code": "+[.]", "max_output": 10000}"#);
+        let frames = session(client, Duration::from_secs(10));
+        let output: Vec<usize> = frames.iter().filter(|frame| frame.0 == 2).map(|frame| frame.1.len()).collect();
+        assert_eq!(output.iter().sum::<usize>(), 10000);
+        assert!(output.len() < 100 && output.iter().all(|len| *len <= 4096));
+        let client = client_frame(1, true, br#"{"code": "+[]"}"#);
+        let frames = session(client, Duration::from_millis(100));
+        let summary = Json::parse(std::str::from_utf8(&frames[0].1).unwrap()).unwrap();
+        let error = summary.get("error").unwrap();
+        assert_eq!(error.get("kind").and_then(Json::as_str), Some("time_limit_exceeded"));
+    }
+}
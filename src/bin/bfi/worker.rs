@@ -0,0 +1,295 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! `bfi worker`: runs the jobs dropped in a directory and writes their
+//! results to another one.
+//!
+//! A job is a `.json` file with the body of `POST /run` of `bfi serve`. A
+//! worker claims it by renaming it to `NAME.json.running`, so that several
+//! threads and processes can share a directory, and writes the response
+//! body to `NAME.json` in the output directory through a temporary file.
+//!
+//! Jobs get the limits and the time limit of `--sandbox`, but the process is
+//! not confined with seccomp, since it keeps reading and renaming files.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use bfi::{BFIError, Limits};
+
+use crate::json::Json;
+use crate::sandbox;
+use crate::serve::{self, HttpError};
+
+const USAGE: &str = "\
+Usage: bfi worker --in DIR --out DIR [OPTIONS]
+
+Runs each job file NAME.json appearing in the --in directory, with the body of
+'POST /run' of 'bfi serve' and at most the limits and the time limit of
+--sandbox, and writes the result to NAME.json in the --out directory. Write
+jobs under another name and rename them into place, so that none is read half
+written. A job being run is renamed to NAME.json.running, and removed once its
+result is written. The workers stop at the first error of the directories.
+
+Options:
+    --in DIR        Directory of the jobs
+    --out DIR       Directory of the results, created if needed
+    --jobs N        Jobs run at once (default: the number of CPUs)
+    --poll MS       Milliseconds between two looks for jobs (default: 500)
+    --once          Exit once no job is left instead of waiting for more
+    --reclaim       Run again the jobs left as NAME.json.running by a worker
+                    that died; only when no other worker uses --in
+    --max-steps N, --max-cells N, --max-output BYTES, --max-time SECONDS
+                    Tighten the limits of --sandbox";
+
+const DEFAULT_POLL: Duration = Duration::from_millis(500);
+
+/// Claims the first pending job of `dir` in the order of their names,
+/// returning its name and the path it was moved to.
+fn claim(dir: &Path) -> io::Result<Option<(String, PathBuf)>> {
+    let mut names = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        if let Ok(name) = entry?.file_name().into_string() {
+            if name.ends_with(".json") && !name.starts_with('.') {
+                names.push(name);
+            }
+        }
+    }
+    names.sort();
+    for name in names {
+        let claimed = dir.join(format!("{}.running", name));
+        match fs::rename(dir.join(&name), &claimed) {
+            Ok(()) => return Ok(Some((name, claimed))),
+            // Claimed by another worker in the meantime.
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(None)
+}
+
+/// Renames the jobs of `dir` left running back to pending, returning how
+/// many there were.
+fn reclaim(dir: &Path) -> io::Result<usize> {
+    let mut reclaimed = 0;
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if let Some(name) = path.file_name().and_then(|name| name.to_str()) {
+            if let Some(job) = name.strip_suffix(".running").filter(|job| job.ends_with(".json")) {
+                fs::rename(&path, dir.join(job))?;
+                reclaimed += 1;
+            }
+        }
+    }
+    Ok(reclaimed)
+}
+
+/// Runs the job `text` and returns the response body of `POST /run`.
+fn execute(text: &str, allowed: &Limits, max_time: Duration) -> Json {
+    let result = Json::parse(text)
+        .map_err(|err| HttpError::new(400, err))
+        .and_then(|body| serve::run(&body, allowed, Some(max_time)));
+    match result {
+        Ok(body) => body,
+        Err(err) => serve::error_body(&err.message),
+    }
+}
+
+/// Writes `contents` to `dir/name` so that readers never see part of it.
+fn write_atomically(dir: &Path, name: &str, contents: &str) -> io::Result<()> {
+    let temporary = dir.join(format!(".{}.tmp", name));
+    fs::write(&temporary, contents)?;
+    fs::rename(&temporary, dir.join(name))
+}
+
+/// Settings of the workers.
+struct Pool {
+    allowed: Limits,
+    max_time: Duration,
+    poll: Duration,
+    once: bool,
+    /// Set once a worker has failed, so that the others stop too.
+    stop: AtomicBool,
+}
+
+/// Runs the jobs of `input` one at a time until none is left if `once`, or
+/// until another worker fails.
+fn work(input: &Path, output: &Path, pool: &Pool) -> io::Result<()> {
+    let result = run_jobs(input, output, pool);
+    if result.is_err() {
+        pool.stop.store(true, Ordering::Relaxed);
+    }
+    result
+}
+
+fn run_jobs(input: &Path, output: &Path, pool: &Pool) -> io::Result<()> {
+    while !pool.stop.load(Ordering::Relaxed) {
+        let (name, claimed) = match claim(input)? {
+            Some(job) => job,
+            None if pool.once => return Ok(()),
+            None => {
+                thread::sleep(pool.poll);
+                continue;
+            }
+        };
+        let text = fs::read_to_string(&claimed);
+        let result = match text {
+            Ok(ref text) => execute(text, &pool.allowed, pool.max_time),
+            Err(ref err) => serve::error_body(&err.to_string()),
+        };
+        write_atomically(output, &name, &format!("{}\n", result))?;
+        fs::remove_file(&claimed)?;
+        let status = result.get("status").and_then(Json::as_str).unwrap_or("invalid");
+        eprintln!("{}: {}", name, status);
+    }
+    Ok(())
+}
+
+pub fn main<I: Iterator<Item = String>>(args: I) -> Result<(), BFIError> {
+    let mut args = args;
+    let mut input = None;
+    let mut output = None;
+    let mut jobs = thread::available_parallelism().map_or(1, |n| n.get());
+    let mut poll = DEFAULT_POLL;
+    let mut once = false;
+    let mut reclaiming = false;
+    let mut allowed = sandbox::LIMITS;
+    let mut max_time = sandbox::MAX_TIME;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            "--in" => input = Some(PathBuf::from(crate::option_value::<String>(&arg, args.next(), USAGE))),
+            "--out" => output = Some(PathBuf::from(crate::option_value::<String>(&arg, args.next(), USAGE))),
+            "--jobs" => jobs = crate::option_value(&arg, args.next(), USAGE),
+            "--poll" => poll = Duration::from_millis(crate::option_value(&arg, args.next(), USAGE)),
+            "--once" => once = true,
+            "--reclaim" => reclaiming = true,
+            "--max-time" => {
+                max_time = max_time.min(Duration::from_secs(crate::option_value(&arg, args.next(), USAGE)))
+            }
+            "--max-steps" => {
+                allowed.max_steps = serve::clamp(Some(crate::option_value(&arg, args.next(), USAGE)), allowed.max_steps)
+            }
+            "--max-cells" => {
+                allowed.max_cells = serve::clamp(Some(crate::option_value(&arg, args.next(), USAGE)), allowed.max_cells)
+            }
+            "--max-output" => {
+                allowed.max_output =
+                    serve::clamp(Some(crate::option_value(&arg, args.next(), USAGE)), allowed.max_output)
+            }
+            _ => crate::usage_error(&format!("Unknown option '{}'", arg), USAGE),
+        }
+    }
+    let (input, output) = match (input, output) {
+        (Some(input), Some(output)) => (input, output),
+        _ => crate::usage_error("Give the --in and --out directories", USAGE),
+    };
+    if jobs == 0 {
+        crate::usage_error("--jobs must be at least 1", USAGE);
+    }
+    fs::read_dir(&input)?;
+    fs::create_dir_all(&output)?;
+    if reclaiming {
+        eprintln!("{}: {} jobs reclaimed", input.display(), reclaim(&input)?);
+    }
+    let pool = Pool {
+        allowed,
+        max_time,
+        poll,
+        once,
+        stop: AtomicBool::new(false),
+    };
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs).map(|_| scope.spawn(|| work(&input, &output, &pool))).collect();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().unwrap_or_else(|_| Err(io::Error::other("a worker panicked"))))
+            .collect::<io::Result<Vec<()>>>()
+    })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    use crate::json::Json;
+    use crate::sandbox;
+    use crate::worker::{reclaim, work, Pool};
+
+    fn pool(max_time: Duration) -> Pool {
+        Pool {
+            allowed: sandbox::LIMITS,
+            max_time,
+            poll: Duration::from_millis(1),
+            once: true,
+            stop: AtomicBool::new(false),
+        }
+    }
+
+    #[test]
+    fn test_work() {
+        let dir = env::temp_dir().join(format!("bfi-worker-test-{}", std::process::id()));
+        let (input, output) = (dir.join("jobs"), dir.join("results"));
+        fs::create_dir_all(&input).unwrap();
+        fs::create_dir_all(&output).unwrap();
+        fs::write(input.join("echo.json"), r#"{"code": ",[.,]", "input": "hi"}"#).unwrap();
+        fs::write(input.join("loop.json"), r#"{"code": "+[]", "max_steps": 100}"#).unwrap();
+        fs::write(input.join("bad.json"), "{").unwrap();
+        fs::write(input.join("draft.json.tmp"), "{}").unwrap();
+        fs::write(input.join("slow.json"), r#"{"code": "+[]"}"#).unwrap();
+        fs::write(input.join("left.json.running"), r#"{"code": "+."}"#).unwrap();
+        assert_eq!(reclaim(&input).unwrap(), 1);
+
+        work(&input, &output, &pool(Duration::from_millis(10))).unwrap();
+        let result = |name: &str| Json::parse(&fs::read_to_string(output.join(name)).unwrap()).unwrap();
+        assert_eq!(result("echo.json").get("output").and_then(Json::as_str), Some("hi"));
+        let error = result("loop.json");
+        let kind = error.get("error").and_then(|error| error.get("kind")).and_then(Json::as_str);
+        assert_eq!(kind, Some("step_limit_exceeded"));
+        assert!(result("bad.json").get("error").is_some());
+        let error = result("slow.json");
+        let kind = error.get("error").and_then(|error| error.get("kind")).and_then(Json::as_str);
+        assert_eq!(kind, Some("time_limit_exceeded"));
+        assert_eq!(result("left.json").get("status").and_then(Json::as_str), Some("ok"));
+        let mut left: Vec<_> = fs::read_dir(&input).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        left.sort();
+        assert_eq!(left, vec!["draft.json.tmp"]);
+        assert_eq!(fs::read_dir(&output).unwrap().count(), 5);
+
+        // A failing worker stops the others.
+        fs::write(input.join("next.json"), r#"{"code": "+"}"#).unwrap();
+        let failing = pool(sandbox::MAX_TIME);
+        assert!(work(&dir.join("missing"), &output, &failing).is_err());
+        assert!(failing.stop.load(Ordering::Relaxed));
+        work(&input, &output, &failing).unwrap();
+        assert!(input.join("next.json").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
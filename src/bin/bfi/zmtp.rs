@@ -0,0 +1,257 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! ZeroMQ Message Transport Protocol 3.0 (ZMTP, 23/ZMTP) over TCP, with the
+//! NULL security mechanism, as needed by the Jupyter kernel.
+
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+
+const FLAG_MORE: u8 = 0x01;
+const FLAG_LONG: u8 = 0x02;
+const FLAG_COMMAND: u8 = 0x04;
+/// Maximum size of a frame accepted from a peer.
+const MAX_FRAME_BYTES: u64 = 64 * 1024 * 1024;
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn greeting() -> [u8; 64] {
+    let mut greeting = [0u8; 64];
+    greeting[0] = 0xff;
+    greeting[9] = 0x7f;
+    greeting[10] = 3;
+    greeting[12..16].copy_from_slice(b"NULL");
+    greeting
+}
+
+/// Encodes the `READY` command of the NULL mechanism.
+fn ready(socket_type: &str) -> Vec<u8> {
+    let mut body = vec![5];
+    body.extend_from_slice(b"READY");
+    body.push(11);
+    body.extend_from_slice(b"Socket-Type");
+    body.extend_from_slice(&(socket_type.len() as u32).to_be_bytes());
+    body.extend_from_slice(socket_type.as_bytes());
+    body
+}
+
+/// Returns the value of the property `name` of a `READY` command.
+fn property<'a>(command: &'a [u8], name: &str) -> Option<&'a [u8]> {
+    let mut rest = command.get(6..)?;
+    while !rest.is_empty() {
+        let name_len = rest[0] as usize;
+        let key = rest.get(1..1 + name_len)?;
+        let value_start = 5 + name_len;
+        let value_len = rest
+            .get(1 + name_len..value_start)?
+            .iter()
+            .fold(0usize, |len, &b| len << 8 | b as usize);
+        let value = rest.get(value_start..value_start + value_len)?;
+        if key.eq_ignore_ascii_case(name.as_bytes()) {
+            return Some(value);
+        }
+        rest = &rest[value_start + value_len..];
+    }
+    None
+}
+
+/// Reads a frame, returning whether it is a command, whether more frames of
+/// the message follow, and its body.
+fn read_frame<R: Read>(reader: &mut R) -> io::Result<(bool, bool, Vec<u8>)> {
+    let mut flags = [0u8; 1];
+    reader.read_exact(&mut flags)?;
+    let flags = flags[0];
+    let len = if flags & FLAG_LONG != 0 {
+        let mut len = [0u8; 8];
+        reader.read_exact(&mut len)?;
+        u64::from_be_bytes(len)
+    } else {
+        let mut len = [0u8; 1];
+        reader.read_exact(&mut len)?;
+        len[0] as u64
+    };
+    if len > MAX_FRAME_BYTES {
+        return Err(invalid("ZMTP frame too large"));
+    }
+    let mut body = vec![0; len as usize];
+    reader.read_exact(&mut body)?;
+    Ok((flags & FLAG_COMMAND != 0, flags & FLAG_MORE != 0, body))
+}
+
+fn frame(flags: u8, body: &[u8], out: &mut Vec<u8>) {
+    if body.len() > u8::MAX as usize {
+        out.push(flags | FLAG_LONG);
+        out.extend_from_slice(&(body.len() as u64).to_be_bytes());
+    } else {
+        out.push(flags);
+        out.push(body.len() as u8);
+    }
+    out.extend_from_slice(body);
+}
+
+/// Encodes a multipart message.
+pub fn encode<T: AsRef<[u8]>>(frames: &[T]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for (i, body) in frames.iter().enumerate() {
+        let more = if i + 1 < frames.len() { FLAG_MORE } else { 0 };
+        frame(more, body.as_ref(), &mut out);
+    }
+    out
+}
+
+/// Reads a multipart message, skipping commands such as `PING`.
+/// Returns `None` once the peer closed the connection.
+pub fn read_message<R: Read>(reader: &mut R) -> io::Result<Option<Vec<Vec<u8>>>> {
+    let mut frames = Vec::new();
+    loop {
+        let (command, more, body) = match read_frame(reader) {
+            Ok(frame) => frame,
+            Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof && frames.is_empty() => {
+                return Ok(None)
+            }
+            Err(err) => return Err(err),
+        };
+        if command {
+            continue;
+        }
+        frames.push(body);
+        if !more {
+            return Ok(Some(frames));
+        }
+    }
+}
+
+/// An accepted connection, after the handshake.
+pub struct Connection {
+    pub stream: TcpStream,
+    /// `Identity` announced by the peer, empty if none.
+    pub identity: Vec<u8>,
+}
+
+impl Connection {
+    /// Performs the handshake as a socket of `socket_type`, e.g. `ROUTER`.
+    pub fn accept(stream: TcpStream, socket_type: &str) -> io::Result<Self> {
+        let identity = handshake(&mut &stream, socket_type)?;
+        Ok(Self { stream, identity })
+    }
+
+    pub fn send<T: AsRef<[u8]>>(&self, frames: &[T]) -> io::Result<()> {
+        (&self.stream).write_all(&encode(frames))
+    }
+
+    pub fn receive(&self) -> io::Result<Option<Vec<Vec<u8>>>> {
+        read_message(&mut &self.stream)
+    }
+}
+
+fn handshake<S: Read + Write>(stream: &mut S, socket_type: &str) -> io::Result<Vec<u8>> {
+    stream.write_all(&greeting())?;
+    let mut peer = [0u8; 64];
+    stream.read_exact(&mut peer)?;
+    if peer[0] != 0xff || peer[9] & 0x01 == 0 || peer[10] < 3 {
+        return Err(invalid("peer does not speak ZMTP 3"));
+    }
+    if &peer[12..32] != b"NULL\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0" {
+        return Err(invalid("unsupported ZMTP security mechanism"));
+    }
+
+    let mut out = Vec::new();
+    frame(FLAG_COMMAND, &ready(socket_type), &mut out);
+    stream.write_all(&out)?;
+    let (command, _, body) = read_frame(stream)?;
+    if !command || !body.starts_with(b"\x05READY") {
+        return Err(invalid("expected the READY command"));
+    }
+    Ok(property(&body, "Identity").unwrap_or_default().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read, Write};
+
+    use crate::zmtp::{encode, greeting, handshake, property, read_message, ready};
+
+    /// Peer of `handshake`, replaying what a DEALER socket would send.
+    struct Peer {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for Peer {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for Peer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_handshake() {
+        let mut command = ready("DEALER");
+        command.push(8);
+        command.extend_from_slice(b"Identity");
+        command.extend_from_slice(&3u32.to_be_bytes());
+        command.extend_from_slice(b"abc");
+        assert_eq!(property(&command, "socket-type"), Some(&b"DEALER"[..]));
+
+        let mut input = greeting().to_vec();
+        input.extend_from_slice(&[0x04, command.len() as u8]);
+        input.extend_from_slice(&command);
+        let mut peer = Peer {
+            input: Cursor::new(input),
+            output: Vec::new(),
+        };
+        assert_eq!(handshake(&mut peer, "ROUTER").unwrap(), b"abc");
+        assert_eq!(&peer.output[..64], &greeting()[..]);
+        assert_eq!(&peer.output[66..72], b"\x05READY");
+
+        let mut peer = Peer {
+            input: Cursor::new(vec![0; 64]),
+            output: Vec::new(),
+        };
+        assert!(handshake(&mut peer, "ROUTER").is_err());
+    }
+
+    #[test]
+    fn test_message() {
+        let long = vec![b'x'; 300];
+        let encoded = encode(&[&b""[..], b"abc", &long]);
+        assert_eq!(&encoded[..7], b"\x01\x00\x01\x03abc");
+        assert_eq!(encoded[7], 0x02);
+        assert_eq!(&encoded[8..16], &300u64.to_be_bytes());
+
+        // A PING command between the frames is skipped.
+        let mut input = b"\x04\x05\x04PING".to_vec();
+        input.extend_from_slice(&encoded);
+        let mut reader = Cursor::new(input);
+        let message = read_message(&mut reader).unwrap().unwrap();
+        assert_eq!(message, vec![b"".to_vec(), b"abc".to_vec(), long]);
+        assert_eq!(read_message(&mut reader).unwrap(), None);
+    }
+}
@@ -0,0 +1,159 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! Helpers for build scripts compiling brainfuck programs into Rust.
+//!
+//! In `build.rs`:
+//!
+//! ```no_run
+//! bfi::build::compile_dir("programs").unwrap();
+//! ```
+//!
+//! Then in the crate:
+//!
+//! ```ignore
+//! include!(concat!(env!("OUT_DIR"), "/bf_programs.rs"));
+//!
+//! hello::run(&mut std::io::stdin(), &mut std::io::stdout()).unwrap();
+//! let (name, run) = PROGRAMS[0];
+//! ```
+
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsStr;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::transpile;
+use crate::BFIError;
+
+/// Signature of the compiled programs listed in `PROGRAMS`.
+pub type Program = fn(&mut dyn io::Read, &mut dyn io::Write) -> Result<(), BFIError>;
+
+/// Name of the file written into the output directory.
+pub const OUTPUT_FILE_NAME: &str = "bf_programs.rs";
+
+fn module_name(stem: &str) -> String {
+    let mut name: String = stem
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, '_');
+    }
+    name
+}
+
+/// Compiles every `.b` file in `dir` into `$OUT_DIR/bf_programs.rs`.
+///
+/// Each program `foo.b` becomes a module `foo` with a `run` function, and
+/// `PROGRAMS` lists `(file stem, run)` pairs sorted by file name. Fails if
+/// two files would be the same module, like `a-b.b` and `a_b.b`.
+/// Returns the path of the written file.
+pub fn compile_dir<P: AsRef<Path>>(dir: P) -> Result<PathBuf, BFIError> {
+    let out_dir = env::var_os("OUT_DIR")
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "OUT_DIR is not set"))?;
+    println!("cargo:rerun-if-changed={}", dir.as_ref().display());
+    compile_dir_to(dir, out_dir)
+}
+
+/// Same as `compile_dir` but writes into `out_dir` instead of `$OUT_DIR`.
+pub fn compile_dir_to<P: AsRef<Path>, Q: AsRef<Path>>(dir: P, out_dir: Q) -> Result<PathBuf, BFIError> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && path.extension() == Some(OsStr::new("b")) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    let mut registry = String::new();
+    let mut out = String::new();
+    let mut modules: HashMap<String, &Path> = HashMap::new();
+    for path in &paths {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let name = module_name(&stem);
+        if let Some(other) = modules.insert(name.clone(), path) {
+            let message = format!(
+                "{} and {} would both be the module {}",
+                other.display(),
+                path.display(),
+                name
+            );
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, message).into());
+        }
+        let code = fs::read_to_string(path)?;
+        let rust = transpile::to_rust(&code, "run").map_err(|err| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), err))
+        })?;
+
+        let _ = writeln!(out, "#[allow(clippy::all)]\npub mod {} {{\n{}}}\n", name, rust);
+        let _ = writeln!(registry, "    ({:?}, {}::run),", stem, name);
+    }
+    let _ = writeln!(out, "pub static PROGRAMS: &[(&str, ::bfi::build::Program)] = &[\n{}];", registry);
+
+    let out_path = out_dir.as_ref().join(OUTPUT_FILE_NAME);
+    fs::write(&out_path, out)?;
+    Ok(out_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use crate::build::{compile_dir_to, module_name};
+
+    #[test]
+    fn test_module_name() {
+        assert_eq!(module_name("hello"), "hello");
+        assert_eq!(module_name("Hello-World"), "hello_world");
+        assert_eq!(module_name("99bottles"), "_99bottles");
+    }
+
+    #[test]
+    fn test_compile_dir_to() {
+        let dir = env::temp_dir().join(format!("bfi-build-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("b-side.b"), "+.").unwrap();
+        fs::write(dir.join("a.b"), "-.").unwrap();
+        fs::write(dir.join("readme.txt"), "[").unwrap();
+
+        let out_path = compile_dir_to(&dir, &dir).unwrap();
+        let out = fs::read_to_string(out_path).unwrap();
+        assert!(out.contains("pub mod a {\n#[allow(dead_code, unused_mut, unused_variables)]\npub fn run("));
+        assert!(out.contains("pub mod b_side {\n#[allow(dead_code, unused_mut, unused_variables)]\npub fn run("));
+        assert!(out.contains("    (\"a\", a::run),\n    (\"b-side\", b_side::run),\n];"));
+
+        fs::write(dir.join("broken.b"), "[").unwrap();
+        let err = compile_dir_to(&dir, &dir).unwrap_err();
+        assert!(err.to_string().ends_with("broken.b: Missing closing bracket(s)"));
+        fs::remove_file(dir.join("broken.b")).unwrap();
+
+        fs::write(dir.join("b_side.b"), "+.").unwrap();
+        let err = compile_dir_to(&dir, &dir).unwrap_err().to_string();
+        assert!(err.contains("b-side.b and "));
+        assert!(err.ends_with("b_side.b would both be the module b_side"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,273 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! C API of the interpreter.
+//!
+//! The declarations are mirrored in `include/bfi.h`, which is generated by
+//! `cbindgen --config cbindgen.toml --output include/bfi.h`.
+
+use std::io::Write;
+use std::os::raw::c_char;
+use std::ptr;
+use std::slice;
+
+use crate::BFIError;
+use crate::BFI;
+
+/// Status codes returned by the C API.
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BFIStatus {
+    Ok = 0,
+    Halted = 1,
+    NullPointer = -1,
+    Io = -2,
+    MissingClosingBrackets = -3,
+    MissingOpeningBrackets = -4,
+    OutOfMemory = -5,
+    ArithmeticOverflow = -6,
+    StepLimitExceeded = -7,
+    OutputLimitExceeded = -8,
+    MemoryLimitExceeded = -9,
+    NestingTooDeep = -10,
+    InputForbidden = -11,
+    StrayCharacters = -12,
+    InvalidAssertion = -13,
+    AssertionFailed = -14,
+}
+
+impl<'a> From<&'a BFIError> for BFIStatus {
+    fn from(err: &'a BFIError) -> BFIStatus {
+        match *err {
+            BFIError::Io(_) => BFIStatus::Io,
+            BFIError::MissingClosingBrackets => BFIStatus::MissingClosingBrackets,
+            BFIError::MissingOpeningBrackets => BFIStatus::MissingOpeningBrackets,
+            BFIError::OutOfMemory => BFIStatus::OutOfMemory,
+            BFIError::ArithmeticOverflow => BFIStatus::ArithmeticOverflow,
+            BFIError::StepLimitExceeded => BFIStatus::StepLimitExceeded,
+            BFIError::OutputLimitExceeded => BFIStatus::OutputLimitExceeded,
+            BFIError::MemoryLimitExceeded => BFIStatus::MemoryLimitExceeded,
+            BFIError::NestingTooDeep => BFIStatus::NestingTooDeep,
+            BFIError::InputForbidden => BFIStatus::InputForbidden,
+            BFIError::StrayCharacters => BFIStatus::StrayCharacters,
+            BFIError::InvalidAssertion => BFIStatus::InvalidAssertion,
+            BFIError::AssertionFailed(_) => BFIStatus::AssertionFailed,
+        }
+    }
+}
+
+fn status_of(result: Result<(), BFIError>) -> BFIStatus {
+    match result {
+        Ok(()) => BFIStatus::Ok,
+        Err(ref err) => BFIStatus::from(err),
+    }
+}
+
+/// Creates an interpreter with an empty program.
+///
+/// The returned pointer must be released with `bfi_free`.
+#[no_mangle]
+pub extern "C" fn bfi_new() -> *mut BFI {
+    Box::into_raw(Box::new(BFI::new(String::new())))
+}
+
+/// Loads `len` bytes of `code` as the program, resetting the memory and the pointer.
+///
+/// On a syntax error, the previously loaded program and its state are kept.
+///
+/// # Safety
+///
+/// `bfi` must come from `bfi_new`, and `code` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn bfi_load(bfi: *mut BFI, code: *const c_char, len: usize) -> BFIStatus {
+    if bfi.is_null() || (code.is_null() && len > 0) {
+        return BFIStatus::NullPointer;
+    }
+
+    let code = if len == 0 {
+        String::new()
+    } else {
+        let bytes = slice::from_raw_parts(code as *const u8, len);
+        String::from_utf8_lossy(bytes).into_owned()
+    };
+    let new_bfi = BFI::new(code);
+    let result = new_bfi.check_syntax();
+    if result.is_ok() {
+        *bfi = new_bfi;
+    }
+    status_of(result)
+}
+
+/// Runs the loaded program from the beginning, reading from stdin and writing to stdout.
+///
+/// stdout is flushed before returning.
+///
+/// # Safety
+///
+/// `bfi` must come from `bfi_new`.
+#[no_mangle]
+pub unsafe extern "C" fn bfi_run(bfi: *mut BFI) -> BFIStatus {
+    match bfi.as_mut() {
+        Some(bfi) => {
+            let mut stdout = std::io::stdout();
+            let result = bfi.interpret(&mut std::io::stdin(), &mut stdout);
+            status_of(result.and_then(|()| Ok(stdout.flush()?)))
+        }
+        None => BFIStatus::NullPointer,
+    }
+}
+
+/// Executes one command of the loaded program, skipping comments, reading from stdin and writing to stdout.
+///
+/// Returns `BFI_STATUS_HALTED` once the end of the program has been reached.
+///
+/// # Safety
+///
+/// `bfi` must come from `bfi_new`.
+#[no_mangle]
+pub unsafe extern "C" fn bfi_step(bfi: *mut BFI) -> BFIStatus {
+    match bfi.as_mut() {
+        Some(bfi) => {
+            let mut stdout = std::io::stdout();
+            let result = bfi.step(&mut std::io::stdin(), &mut stdout);
+            let result = result.and_then(|running| {
+                stdout.flush()?;
+                Ok(running)
+            });
+            match result {
+                Ok(true) => BFIStatus::Ok,
+                Ok(false) => BFIStatus::Halted,
+                Err(ref err) => BFIStatus::from(err),
+            }
+        }
+        None => BFIStatus::NullPointer,
+    }
+}
+
+/// Stores the value of the cell at `index` into `value`.
+///
+/// # Safety
+///
+/// `bfi` must come from `bfi_new`, and `value` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn bfi_get_cell(bfi: *const BFI, index: usize, value: *mut i8) -> BFIStatus {
+    if value.is_null() {
+        return BFIStatus::NullPointer;
+    }
+    match bfi.as_ref() {
+        Some(bfi) => match bfi.cell(index) {
+            Some(cell) => {
+                ptr::write(value, cell);
+                BFIStatus::Ok
+            }
+            None => BFIStatus::OutOfMemory,
+        },
+        None => BFIStatus::NullPointer,
+    }
+}
+
+/// Releases an interpreter created by `bfi_new`. Passing NULL is a no-op.
+///
+/// # Safety
+///
+/// `bfi` must come from `bfi_new` and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn bfi_free(bfi: *mut BFI) {
+    if !bfi.is_null() {
+        drop(Box::from_raw(bfi));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr;
+
+    use super::*;
+
+    #[test]
+    fn test_load_and_get_cell() {
+        unsafe {
+            let bfi = bfi_new();
+            let code = "+++>++";
+            assert_eq!(bfi_load(bfi, code.as_ptr() as *const c_char, code.len()), BFIStatus::Ok);
+            while bfi_step(bfi) == BFIStatus::Ok {}
+
+            let mut value = 0i8;
+            assert_eq!(bfi_get_cell(bfi, 0, &mut value), BFIStatus::Ok);
+            assert_eq!(value, 3);
+            assert_eq!(bfi_get_cell(bfi, 1, &mut value), BFIStatus::Ok);
+            assert_eq!(value, 2);
+            assert_eq!(bfi_get_cell(bfi, usize::MAX, &mut value), BFIStatus::OutOfMemory);
+            bfi_free(bfi);
+        }
+    }
+
+    #[test]
+    fn test_error_status() {
+        unsafe {
+            let bfi = bfi_new();
+            let code = "[[]";
+            assert_eq!(
+                bfi_load(bfi, code.as_ptr() as *const c_char, code.len()),
+                BFIStatus::MissingClosingBrackets
+            );
+            let code = "<";
+            assert_eq!(bfi_load(bfi, code.as_ptr() as *const c_char, code.len()), BFIStatus::Ok);
+            assert_eq!(bfi_step(bfi), BFIStatus::OutOfMemory);
+            bfi_free(bfi);
+        }
+    }
+
+    #[test]
+    fn test_load_error_keeps_program() {
+        unsafe {
+            let bfi = bfi_new();
+            let code = "]";
+            assert_eq!(
+                bfi_load(bfi, code.as_ptr() as *const c_char, code.len()),
+                BFIStatus::MissingOpeningBrackets
+            );
+            assert_eq!(bfi_step(bfi), BFIStatus::Halted);
+
+            let code = "++";
+            assert_eq!(bfi_load(bfi, code.as_ptr() as *const c_char, code.len()), BFIStatus::Ok);
+            assert_eq!(bfi_step(bfi), BFIStatus::Ok);
+            let code = "+]";
+            assert_eq!(
+                bfi_load(bfi, code.as_ptr() as *const c_char, code.len()),
+                BFIStatus::MissingOpeningBrackets
+            );
+            while bfi_step(bfi) == BFIStatus::Ok {}
+            let mut value = 0i8;
+            assert_eq!(bfi_get_cell(bfi, 0, &mut value), BFIStatus::Ok);
+            assert_eq!(value, 2);
+            bfi_free(bfi);
+        }
+    }
+
+    #[test]
+    fn test_null_pointer() {
+        unsafe {
+            assert_eq!(bfi_load(ptr::null_mut(), ptr::null(), 0), BFIStatus::NullPointer);
+            assert_eq!(bfi_run(ptr::null_mut()), BFIStatus::NullPointer);
+            assert_eq!(bfi_step(ptr::null_mut()), BFIStatus::NullPointer);
+            bfi_free(ptr::null_mut());
+        }
+    }
+}
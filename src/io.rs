@@ -0,0 +1,211 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! Byte-level I/O used by `,` and `.`.
+//!
+//! With the `std` feature every `std::io::Read` is a `ByteIn` and every
+//! `std::io::Write` is a `ByteOut`. On other targets, implement the traits for
+//! the device (e.g. a UART driver) to connect it to the interpreter.
+
+#[cfg(feature = "std")]
+pub use std::io::Error;
+
+/// Source of the bytes read by `,`.
+pub trait ByteIn {
+    /// Reads one byte, or returns `Ok(None)` at the end of input.
+    fn read_byte(&mut self) -> Result<Option<u8>, Error>;
+}
+
+/// Sink of the bytes written by `.`.
+pub trait ByteOut {
+    /// Writes one byte, retrying until it has been accepted.
+    fn write_byte(&mut self, byte: u8) -> Result<(), Error>;
+
+    /// Delivers buffered bytes. Called once the program has ended, whether or
+    /// not it failed.
+    fn flush_bytes(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: std::io::Read + ?Sized> ByteIn for R {
+    fn read_byte(&mut self) -> Result<Option<u8>, Error> {
+        let mut buf = [0u8; 1];
+        loop {
+            match self.read(&mut buf) {
+                Ok(0) => return Ok(None),
+                Ok(_) => return Ok(Some(buf[0])),
+                Err(ref err) if err.kind() == std::io::ErrorKind::Interrupted => (),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write + ?Sized> ByteOut for W {
+    /// Uses `write_all`, which retries short and interrupted writes.
+    fn write_byte(&mut self, byte: u8) -> Result<(), Error> {
+        self.write_all(&[byte])
+    }
+
+    fn flush_bytes(&mut self) -> Result<(), Error> {
+        self.flush()
+    }
+}
+
+#[cfg(not(feature = "std"))]
+mod core_io {
+    use alloc::vec::Vec;
+    use core::fmt;
+
+    use super::{ByteIn, ByteOut};
+
+    /// Error reported by a device.
+    #[derive(Debug)]
+    pub struct Error {
+        message: &'static str,
+    }
+
+    impl Error {
+        pub fn new(message: &'static str) -> Self {
+            Self { message }
+        }
+    }
+
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str(self.message)
+        }
+    }
+
+    impl ByteIn for &[u8] {
+        fn read_byte(&mut self) -> Result<Option<u8>, Error> {
+            match self.split_first() {
+                Some((&byte, tail)) => {
+                    *self = tail;
+                    Ok(Some(byte))
+                }
+                None => Ok(None),
+            }
+        }
+    }
+
+    impl ByteOut for Vec<u8> {
+        fn write_byte(&mut self, byte: u8) -> Result<(), Error> {
+            self.push(byte);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(not(feature = "std"))]
+pub use self::core_io::Error;
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::io::{ByteIn, ByteOut, Error};
+    use crate::BFI;
+
+    #[test]
+    fn test_read_byte() {
+        let mut reader = Cursor::new(vec![1, 2]);
+        assert_eq!(reader.read_byte().unwrap(), Some(1));
+        assert_eq!(reader.read_byte().unwrap(), Some(2));
+        assert_eq!(reader.read_byte().unwrap(), None);
+    }
+
+    #[test]
+    fn test_write_byte() {
+        let mut writer = Vec::new();
+        writer.write_byte(1).unwrap();
+        writer.write_byte(2).unwrap();
+        assert_eq!(writer, [1, 2]);
+    }
+
+    #[test]
+    fn test_write_byte_retries() {
+        /// Accepts nothing on every other call.
+        struct Flaky {
+            data: Vec<u8>,
+            interrupt: bool,
+            flushed: usize,
+        }
+
+        impl std::io::Write for Flaky {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.interrupt = !self.interrupt;
+                if self.interrupt {
+                    return Err(std::io::ErrorKind::Interrupted.into());
+                }
+                self.data.push(buf[0]);
+                Ok(1)
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                self.flushed += 1;
+                Ok(())
+            }
+        }
+
+        let mut writer = Flaky {
+            data: Vec::new(),
+            interrupt: false,
+            flushed: 0,
+        };
+        let mut bfi = BFI::new("+.+.+[.]".to_string());
+        bfi.set_limits(crate::Limits {
+            max_output: Some(3),
+            ..crate::Limits::default()
+        });
+        assert!(bfi.interpret(&mut std::io::empty(), &mut writer).is_err());
+        assert_eq!(writer.data, [1, 2, 3]);
+        assert_eq!(writer.flushed, 1);
+    }
+
+    #[test]
+    fn test_device() {
+        struct Uart {
+            rx: Vec<u8>,
+            tx: Vec<u8>,
+        }
+
+        impl ByteIn for Uart {
+            fn read_byte(&mut self) -> Result<Option<u8>, Error> {
+                Ok(self.rx.pop())
+            }
+        }
+
+        impl ByteOut for Uart {
+            fn write_byte(&mut self, byte: u8) -> Result<(), Error> {
+                self.tx.push(byte);
+                Ok(())
+            }
+        }
+
+        let mut rx = Uart { rx: vec![3, 2, 1], tx: Vec::new() };
+        let mut tx = Uart { rx: Vec::new(), tx: Vec::new() };
+        let mut bfi = BFI::new(",.,.,.".to_string());
+        bfi.interpret(&mut rx, &mut tx).unwrap();
+        assert_eq!(tx.tx, [1, 2, 3]);
+    }
+}
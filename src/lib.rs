@@ -0,0 +1,1622 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ *
+ *
+ *  The Original Code is http://esoteric.sange.fi/brainfuck/impl/interp/BFI.java
+ *  The Initial Developer of the Original Code is Thomas Cort.
+ *  Portions created by the Initial Developer are Copyright (C) 2003
+ *  the Initial Developer under GPL.
+ */
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+
+use crate::assertion::{Assertion, AssertionFailure};
+use crate::io::ByteIn;
+use crate::io::ByteOut;
+use crate::tape::Tape;
+
+#[cfg(feature = "std")]
+pub mod adaptor;
+pub mod assertion;
+#[cfg(feature = "async")]
+mod asynchronous;
+#[cfg(feature = "std")]
+pub mod bench;
+#[cfg(feature = "std")]
+pub mod build;
+#[cfg(feature = "capi")]
+pub mod capi;
+#[cfg(feature = "python")]
+mod python;
+pub mod io;
+pub mod link;
+pub mod program;
+pub mod symbols;
+mod tape;
+pub mod termination;
+pub mod transpile;
+
+#[derive(Debug)]
+pub enum BFIError {
+    Io(io::Error),
+    MissingClosingBrackets,
+    MissingOpeningBrackets,
+    OutOfMemory,
+    ArithmeticOverflow,
+    StepLimitExceeded,
+    OutputLimitExceeded,
+    MemoryLimitExceeded,
+    NestingTooDeep,
+    InputForbidden,
+    StrayCharacters,
+    InvalidAssertion,
+    AssertionFailed(Box<AssertionFailure>),
+}
+
+impl fmt::Display for BFIError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            BFIError::Io(ref err) => write!(f, "{}", err),
+            BFIError::MissingClosingBrackets => write!(f, "Missing closing bracket(s)"),
+            BFIError::MissingOpeningBrackets => write!(f, "Missing opening bracket(s)"),
+            BFIError::OutOfMemory => write!(f, "Pointer moved to out of range of memory"),
+            BFIError::ArithmeticOverflow => write!(f, "Byte overflow"),
+            BFIError::StepLimitExceeded => write!(f, "Step limit exceeded"),
+            BFIError::OutputLimitExceeded => write!(f, "Output limit exceeded"),
+            BFIError::MemoryLimitExceeded => write!(f, "Memory limit exceeded"),
+            BFIError::NestingTooDeep => write!(f, "Loops nested too deeply"),
+            BFIError::InputForbidden => write!(f, "Input is forbidden in deterministic mode"),
+            BFIError::StrayCharacters => write!(f, "Stray characters in strict mode"),
+            BFIError::InvalidAssertion => write!(f, "Invalid assertion"),
+            BFIError::AssertionFailed(ref failure) => write!(f, "{}", failure),
+        }
+    }
+}
+
+impl From<io::Error> for BFIError {
+    fn from(err: io::Error) -> BFIError {
+        BFIError::Io(err)
+    }
+}
+
+/// Number of steps between two progress events at the trace level.
+#[cfg(feature = "tracing")]
+const TRACE_PROGRESS_INTERVAL: u64 = 1 << 20;
+
+/// A command of the program, located by `scan`.
+#[derive(Debug, Clone, Copy)]
+struct Command {
+    byte: u8,
+    /// Byte offset in the source, for diagnostics.
+    offset: usize,
+    /// Index of the matching bracket of `[` and `]`.
+    jump: usize,
+}
+
+/// Source text of the program.
+#[derive(Debug)]
+enum Source {
+    Text(String),
+    /// A memory-mapped file, which need not be valid UTF-8.
+    #[cfg(feature = "mmap")]
+    Mapped(memmap2::Mmap),
+}
+
+impl Source {
+    fn as_bytes(&self) -> &[u8] {
+        match *self {
+            Source::Text(ref text) => text.as_bytes(),
+            #[cfg(feature = "mmap")]
+            Source::Mapped(ref map) => map,
+        }
+    }
+}
+
+/// Lists the bytes of `code` with their offsets, leaving out the annotation
+/// lines but their newline, see `symbols::is_annotation`.
+///
+/// Iterating over bytes is safe for any UTF-8 source: the bytes of a
+/// multi-byte character are never ASCII, so never taken for commands.
+pub(crate) fn source_bytes(code: &[u8]) -> impl Iterator<Item = (usize, u8)> + '_ {
+    let mut annotation = false;
+    code.iter().enumerate().filter_map(move |(offset, &byte)| {
+        if offset == 0 || code[offset - 1] == b'\n' {
+            annotation = symbols::is_annotation(&code[offset..]);
+        }
+        if annotation && byte != b'\n' {
+            None
+        } else {
+            Some((offset, byte))
+        }
+    })
+}
+
+/// Lists the commands of `code`, skipping comments, and matches the brackets.
+fn scan(code: &[u8], extension: Option<&dyn Extension>) -> Vec<Command> {
+    let mut commands: Vec<Command> = Vec::new();
+    let mut open = Vec::new();
+    for (offset, byte) in source_bytes(code) {
+        let extended = byte.is_ascii() && extension.is_some_and(|extension| extension.handles(byte));
+        if !is_command(byte) && !extended {
+            continue;
+        }
+        let index = commands.len();
+        let mut jump = index;
+        match byte {
+            b'[' => open.push(index),
+            b']' => {
+                if let Some(start) = open.pop() {
+                    commands[start].jump = index;
+                    jump = start;
+                }
+            }
+            _ => (),
+        }
+        commands.push(Command { byte, offset, jump });
+    }
+    commands
+}
+
+/// Parses the assertions of `code`, putting each before the first of
+/// `commands` after it.
+fn attach_assertions(code: &[u8], commands: &[Command]) -> (Vec<(usize, Assertion)>, Vec<usize>) {
+    let (assertions, invalid) = assertion::parse(code);
+    let assertions = assertions
+        .into_iter()
+        .map(|(offset, assertion)| (commands.partition_point(|command| command.offset < offset), assertion))
+        .collect();
+    (assertions, invalid)
+}
+
+fn is_command(byte: u8) -> bool {
+    b"><+-.,[]".contains(&byte)
+}
+
+/// Letters, digits, and non-ASCII characters make up words.
+fn is_word(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || !byte.is_ascii()
+}
+
+/// Whether the next character on the line, spaces aside, is a word character.
+fn before_word(rest: &[u8]) -> bool {
+    rest.iter().find(|&&byte| byte != b' ' && byte != b'\t').is_some_and(|&byte| is_word(byte))
+}
+
+/// Decodes the character starting at `offset`, which may be invalid UTF-8.
+fn char_at(code: &[u8], offset: usize) -> char {
+    let rest = &code[offset..code.len().min(offset + 4)];
+    let valid = match core::str::from_utf8(rest) {
+        Ok(valid) => valid,
+        Err(err) => core::str::from_utf8(&rest[..err.valid_up_to()]).unwrap_or_default(),
+    };
+    valid.chars().next().unwrap_or(char::REPLACEMENT_CHARACTER)
+}
+
+/// Resource limits enforced while interpreting. `None` means unlimited.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum number of executed commands.
+    pub max_steps: Option<u64>,
+    /// Number of cells of the memory, at most the default 32768, or the
+    /// ceiling of a growable memory.
+    pub max_cells: Option<usize>,
+    /// Maximum number of bytes written by `.`.
+    pub max_output: Option<usize>,
+}
+
+/// What `,` stores in the current cell once the input has ended.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum EofPolicy {
+    /// Store 0.
+    #[default]
+    Zero,
+    /// Store -1, i.e. 255.
+    MinusOne,
+    /// Leave the cell unchanged.
+    Unchanged,
+}
+
+impl FromStr for EofPolicy {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "zero" => Ok(EofPolicy::Zero),
+            "minus-one" => Ok(EofPolicy::MinusOne),
+            "unchanged" => Ok(EofPolicy::Unchanged),
+            _ => Err("expected zero, minus-one, or unchanged"),
+        }
+    }
+}
+
+/// Why a run reported by `BFI::interpret_reported` stopped.
+#[derive(Debug)]
+pub enum HaltReason {
+    /// The end of the program was reached.
+    End,
+    Error(BFIError),
+}
+
+/// Work done by a run of `BFI::interpret_reported`.
+#[derive(Debug)]
+pub struct ExecutionReport {
+    /// Number of executed commands.
+    pub steps: u64,
+    /// Number of bytes read by `,`, not counting the end of input.
+    pub bytes_in: usize,
+    /// Number of bytes written by `.`.
+    pub bytes_out: usize,
+    /// Highest index of the cell under the pointer.
+    pub peak_pointer: usize,
+    pub halted_reason: HaltReason,
+}
+
+/// Default maximum nesting depth of loops, see `BFI::set_max_depth`.
+pub const DEFAULT_MAX_DEPTH: usize = 1024;
+
+/// How suspicious characters are treated by `BFI::syntax_issues`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Strictness {
+    /// Everything but commands is a comment.
+    #[default]
+    Lenient,
+    /// Rejects commands between two words, like the `,` of "Hello, world",
+    /// and with an extension, ASCII punctuation it does not handle.
+    Strict,
+    /// Also rejects everything but commands and whitespace.
+    Pure,
+}
+
+/// Kind of an issue found by `BFI::syntax_issues`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyntaxIssueKind {
+    /// A `]` without a preceding `[` to close.
+    UnmatchedClosing,
+    /// A `[` that is never closed.
+    Unclosed,
+    /// A `[` nested deeper than the maximum depth.
+    TooDeep,
+    /// A `,` in deterministic mode.
+    Input,
+    /// A command between two words, in strict mode.
+    Stray(char),
+    /// A character that is not a command, in strict mode.
+    Unexpected(char),
+    /// An `assert` annotation that cannot be parsed.
+    InvalidAssertion,
+}
+
+/// An issue of the source, located by byte offset and by 1-based line and column.
+///
+/// Columns count characters, not bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyntaxIssue {
+    pub kind: SyntaxIssueKind,
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for SyntaxIssueKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            SyntaxIssueKind::UnmatchedClosing => write!(f, "Unmatched ']'"),
+            SyntaxIssueKind::Unclosed => write!(f, "Unclosed '['"),
+            SyntaxIssueKind::TooDeep => write!(f, "Too deeply nested '['"),
+            SyntaxIssueKind::Input => write!(f, "Forbidden ','"),
+            SyntaxIssueKind::Stray(c) => write!(f, "Command '{}' between words", c),
+            SyntaxIssueKind::Unexpected(c) => write!(f, "Unexpected {:?}", c),
+            SyntaxIssueKind::InvalidAssertion => write!(f, "Invalid assertion"),
+        }
+    }
+}
+
+impl fmt::Display for SyntaxIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{} at line {}, column {}", self.kind, self.line, self.column)
+    }
+}
+
+impl From<SyntaxIssue> for BFIError {
+    fn from(issue: SyntaxIssue) -> BFIError {
+        match issue.kind {
+            SyntaxIssueKind::UnmatchedClosing => BFIError::MissingOpeningBrackets,
+            SyntaxIssueKind::Unclosed => BFIError::MissingClosingBrackets,
+            SyntaxIssueKind::TooDeep => BFIError::NestingTooDeep,
+            SyntaxIssueKind::Input => BFIError::InputForbidden,
+            SyntaxIssueKind::Stray(_) | SyntaxIssueKind::Unexpected(_) => BFIError::StrayCharacters,
+            SyntaxIssueKind::InvalidAssertion => BFIError::InvalidAssertion,
+        }
+    }
+}
+
+/// Handler of commands beyond the eight standard ones, e.g. for dialects.
+pub trait Extension: fmt::Debug + Send {
+    /// Returns whether the ASCII character `command` is handled by `execute`.
+    fn handles(&self, command: u8) -> bool;
+
+    /// Executes `command` on the value of the current cell.
+    fn execute(&mut self, command: u8, cell: &mut i8) -> Result<(), BFIError>;
+
+    /// Returns a copy of the handler for `BFI::fork`, or `None`, the default,
+    /// if it cannot be copied.
+    fn fork(&self) -> Option<Box<dyn Extension>> {
+        None
+    }
+}
+
+#[derive(Debug)]
+pub struct BFI {
+    x: Tape,
+    // The program is shared with the forks.
+    c: Arc<Source>,
+    commands: Arc<Vec<Command>>,
+    /// Assertions by index of the command following them, in order.
+    assertions: Arc<Vec<(usize, Assertion)>>,
+    /// Offsets of the invalid assertions.
+    invalid_assertions: Arc<Vec<usize>>,
+    p: usize,
+    pc: usize,
+    limits: Limits,
+    eof: EofPolicy,
+    extension: Option<Box<dyn Extension>>,
+    growable: bool,
+    wrapping: bool,
+    max_depth: usize,
+    deterministic: bool,
+    strictness: Strictness,
+    steps: u64,
+    output_len: usize,
+    input_len: usize,
+    peak_pointer: usize,
+}
+
+impl BFI {
+    pub fn new(s: String) -> Self {
+        Self::with_source(Source::Text(s))
+    }
+
+    fn with_source(c: Source) -> Self {
+        let commands = scan(c.as_bytes(), None);
+        let (assertions, invalid_assertions) = attach_assertions(c.as_bytes(), &commands);
+        Self {
+            x: Tape::new(32767 + 1),
+            commands: Arc::new(commands),
+            assertions: Arc::new(assertions),
+            invalid_assertions: Arc::new(invalid_assertions),
+            c: Arc::new(c),
+            p: 0,
+            pc: 0,
+            limits: Limits::default(),
+            eof: EofPolicy::default(),
+            extension: None,
+            growable: false,
+            wrapping: false,
+            max_depth: DEFAULT_MAX_DEPTH,
+            deterministic: false,
+            strictness: Strictness::default(),
+            steps: 0,
+            output_len: 0,
+            input_len: 0,
+            peak_pointer: 0,
+        }
+    }
+
+    /// Applies `limits` to the following executions.
+    pub fn set_limits(&mut self, limits: Limits) {
+        if let Some(max_cells) = limits.max_cells {
+            self.x.truncate(max_cells.max(1));
+            self.p = self.p.min(self.x.len() - 1);
+        }
+        self.limits = limits;
+    }
+
+    /// Sets what `,` stores once the input has ended.
+    pub fn set_eof_policy(&mut self, eof: EofPolicy) {
+        self.eof = eof;
+    }
+
+    /// Grows the memory past its end on demand, up to `Limits::max_cells`
+    /// cells if set. Beyond that, or if no more memory can be allocated, `>`
+    /// fails with `MemoryLimitExceeded`.
+    pub fn set_growable(&mut self, growable: bool) {
+        self.growable = growable;
+    }
+
+    /// Wraps cells around on overflow, so that they behave as unsigned bytes,
+    /// instead of failing with `ArithmeticOverflow`.
+    pub fn set_wrapping(&mut self, wrapping: bool) {
+        self.wrapping = wrapping;
+    }
+
+    /// Sets how deeply loops may be nested, `DEFAULT_MAX_DEPTH` by default.
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    /// Rejects programs containing `,` in `check_syntax`, so that the output
+    /// depends only on the program.
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        self.deterministic = deterministic;
+    }
+
+    /// Sets which suspicious characters `check_syntax` rejects.
+    pub fn set_strictness(&mut self, strictness: Strictness) {
+        self.strictness = strictness;
+    }
+
+    /// Executes the commands handled by `extension` as well.
+    pub fn set_extension(&mut self, extension: Box<dyn Extension>) {
+        let commands = scan(self.c.as_bytes(), Some(&*extension));
+        self.set_commands(commands);
+        self.extension = Some(extension);
+    }
+
+    /// Replaces the program, keeping the memory and the pointer.
+    pub fn set_code(&mut self, code: String) {
+        let commands = scan(code.as_bytes(), self.extension.as_deref());
+        self.c = Arc::new(Source::Text(code));
+        self.set_commands(commands);
+        self.restart();
+    }
+
+    /// Replaces the commands of the source and their assertions.
+    fn set_commands(&mut self, commands: Vec<Command>) {
+        let (assertions, invalid_assertions) = attach_assertions(self.c.as_bytes(), &commands);
+        self.commands = Arc::new(commands);
+        self.assertions = Arc::new(assertions);
+        self.invalid_assertions = Arc::new(invalid_assertions);
+    }
+
+    /// Returns a copy of the interpreter in its current state, e.g. to explore
+    /// both branches of a search. The program is shared, and so is the memory,
+    /// in pages that the copy writing to them first copies, so that forking
+    /// copies a pointer per 4096 cells rather than the cells.
+    ///
+    /// Returns `None` if the extension cannot be forked.
+    pub fn fork(&self) -> Option<Self> {
+        let extension = match self.extension {
+            Some(ref extension) => Some(extension.fork()?),
+            None => None,
+        };
+        Some(Self {
+            x: self.x.clone(),
+            c: Arc::clone(&self.c),
+            commands: Arc::clone(&self.commands),
+            assertions: Arc::clone(&self.assertions),
+            invalid_assertions: Arc::clone(&self.invalid_assertions),
+            p: self.p,
+            pc: self.pc,
+            limits: self.limits,
+            eof: self.eof,
+            extension,
+            growable: self.growable,
+            wrapping: self.wrapping,
+            max_depth: self.max_depth,
+            deterministic: self.deterministic,
+            strictness: self.strictness,
+            steps: self.steps,
+            output_len: self.output_len,
+            input_len: self.input_len,
+            peak_pointer: self.peak_pointer,
+        })
+    }
+
+    /// Moves to the start of the program and resets the statistics of the run.
+    fn restart(&mut self) {
+        self.pc = 0;
+        self.steps = 0;
+        self.output_len = 0;
+        self.input_len = 0;
+        self.peak_pointer = self.p;
+    }
+
+    /// Index of the cell under the pointer.
+    pub fn pointer(&self) -> usize {
+        self.p
+    }
+
+    /// Number of commands executed by the current run.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Number of bytes written by the current run.
+    pub fn output_len(&self) -> usize {
+        self.output_len
+    }
+
+    /// Source of the program, comments included.
+    pub fn code(&self) -> &[u8] {
+        self.c.as_bytes()
+    }
+
+    /// Commands of the program, including those of the extension, in order,
+    /// with their byte offsets in the source.
+    pub fn commands(&self) -> impl Iterator<Item = (usize, u8)> + '_ {
+        self.commands.iter().map(|command| (command.offset, command.byte))
+    }
+
+    #[cfg(all(feature = "std", not(feature = "mmap")))]
+    pub fn from_file(file_path: String) -> Result<Self, BFIError> {
+        let mut code = String::new();
+        let mut file = std::fs::File::open(file_path)?;
+        std::io::Read::read_to_string(&mut file, &mut code)?;
+
+        Ok(Self::new(code))
+    }
+
+    /// Maps the file into memory instead of reading it, so that only the
+    /// commands of a huge program take up memory. Comments need not be UTF-8.
+    ///
+    /// The file must not be modified while the interpreter is alive.
+    #[cfg(feature = "mmap")]
+    pub fn from_file(file_path: String) -> Result<Self, BFIError> {
+        let file = std::fs::File::open(file_path)?;
+        // SAFETY: the map is read only, and the caller keeps the file unmodified.
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        Ok(Self::with_source(Source::Mapped(map)))
+    }
+
+    /// Returns the command at the program counter, or `None` at the end.
+    fn current_command(&self) -> Option<u8> {
+        self.commands.get(self.pc).map(|command| command.byte)
+    }
+
+    /// Byte offset in the source of the command at the program counter, or
+    /// `None` once the program has ended.
+    pub fn offset(&self) -> Option<usize> {
+        self.commands.get(self.pc).map(|command| command.offset)
+    }
+
+    pub fn check_syntax(&self) -> Result<(), BFIError> {
+        match self.syntax_issues().into_iter().next() {
+            Some(issue) => Err(issue.into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Matches the brackets with a stack, and returns the first unmatched `]`
+    /// and every unclosed `[` in order of position, e.g. both brackets of `][`.
+    /// Invalid assertions are reported as well, in deterministic mode the
+    /// first `,`, and in strict mode every suspicious character.
+    ///
+    /// Scanning stops at the first `[` nested deeper than the maximum depth,
+    /// which is then the last issue.
+    pub fn syntax_issues(&self) -> Vec<SyntaxIssue> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("parse", code_bytes = self.c.as_bytes().len()).entered();
+
+        let mut issues = Vec::new();
+        let mut open = Vec::new();
+        let mut unmatched = false;
+        let mut input = false;
+        let mut line = 1;
+        let mut column = 0;
+        // Whether the last character before on the line, spaces aside, is a word character.
+        let mut after_word = false;
+        let code = self.c.as_bytes();
+        for &offset in self.invalid_assertions.iter() {
+            let line_start = code[..offset].iter().rposition(|&byte| byte == b'\n').map_or(0, |n| n + 1);
+            issues.push(SyntaxIssue {
+                kind: SyntaxIssueKind::InvalidAssertion,
+                offset,
+                line: code[..offset].iter().filter(|&&byte| byte == b'\n').count() + 1,
+                column: code[line_start..offset].iter().filter(|&&byte| byte & 0xc0 != 0x80).count() + 1,
+            });
+        }
+        for (offset, byte) in source_bytes(code) {
+            // UTF-8 continuation bytes do not start a character.
+            if byte & 0xc0 == 0x80 {
+                continue;
+            }
+            column += 1;
+            let issue = |kind| SyntaxIssue { kind, offset, line, column };
+            if let Some(kind) = self.strict_issue(code, offset, after_word) {
+                issues.push(issue(kind));
+            }
+            if byte != b' ' && byte != b'\t' {
+                after_word = is_word(byte);
+            }
+            match byte {
+                b'\n' => {
+                    line += 1;
+                    column = 0;
+                    after_word = false;
+                }
+                b'[' if open.len() == self.max_depth => {
+                    issues.push(issue(SyntaxIssueKind::TooDeep));
+                    return issues;
+                }
+                b'[' => open.push(issue(SyntaxIssueKind::Unclosed)),
+                b']' if open.pop().is_none() && !unmatched => {
+                    issues.push(issue(SyntaxIssueKind::UnmatchedClosing));
+                    unmatched = true;
+                }
+                b',' if self.deterministic && !input => {
+                    issues.push(issue(SyntaxIssueKind::Input));
+                    input = true;
+                }
+                _ => (),
+            }
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(unclosed = open.len(), unmatched = issues.len(), "brackets matched");
+
+        issues.extend(open);
+        issues.sort_by_key(|issue| issue.offset);
+        issues
+    }
+
+    /// Checks the character at `offset` against the strictness.
+    fn strict_issue(&self, code: &[u8], offset: usize, after_word: bool) -> Option<SyntaxIssueKind> {
+        let byte = code[offset];
+        let command = is_command(byte);
+        let extended = byte.is_ascii() && self.extension.as_ref().is_some_and(|extension| extension.handles(byte));
+        match self.strictness {
+            Strictness::Lenient => None,
+            Strictness::Pure if !command && !extended && !byte.is_ascii_whitespace() => {
+                Some(SyntaxIssueKind::Unexpected(char_at(code, offset)))
+            }
+            _ if command && after_word && before_word(&code[offset + 1..]) => {
+                Some(SyntaxIssueKind::Stray(byte as char))
+            }
+            _ if self.extension.is_some() && byte.is_ascii_punctuation() && !command && !extended => {
+                Some(SyntaxIssueKind::Unexpected(byte as char))
+            }
+            _ => None,
+        }
+    }
+
+    fn increment_pointer(&mut self) -> Result<(), BFIError> {
+        if self.p + 1 >= self.x.len() && self.growable {
+            self.grow()?;
+        }
+        if self.p + 1 >= self.x.len() {
+            Err(BFIError::OutOfMemory)
+        } else {
+            self.p += 1;
+            self.peak_pointer = self.peak_pointer.max(self.p);
+            Ok(())
+        }
+    }
+
+    /// Doubles the memory, without exceeding `max_cells`.
+    fn grow(&mut self) -> Result<(), BFIError> {
+        let max_cells = self.limits.max_cells.unwrap_or(usize::MAX);
+        let len = self.x.len().saturating_mul(2).min(max_cells);
+        if len <= self.x.len() {
+            return Err(BFIError::MemoryLimitExceeded);
+        }
+        self.x.try_grow(len).map_err(|_| BFIError::MemoryLimitExceeded)
+    }
+
+    fn decrement_pointer(&mut self) -> Result<(), BFIError> {
+        if self.p == 0 {
+            Err(BFIError::OutOfMemory)
+        } else {
+            self.p -= 1;
+            Ok(())
+        }
+    }
+
+    fn increment_byte_at_pointer(&mut self) -> Result<(), BFIError> {
+        let cell = self.x.read(self.p);
+        if self.wrapping {
+            self.x.write(self.p, cell.wrapping_add(1));
+            Ok(())
+        } else if cell == i8::MAX {
+            Err(BFIError::ArithmeticOverflow)
+        } else {
+            self.x.write(self.p, cell + 1);
+            Ok(())
+        }
+    }
+
+    fn decrement_byte_at_pointer(&mut self) -> Result<(), BFIError> {
+        let cell = self.x.read(self.p);
+        if self.wrapping {
+            self.x.write(self.p, cell.wrapping_sub(1));
+            Ok(())
+        } else if cell == i8::MIN {
+            Err(BFIError::ArithmeticOverflow)
+        } else {
+            self.x.write(self.p, cell - 1);
+            Ok(())
+        }
+    }
+
+    fn count_step(&mut self) -> Result<(), BFIError> {
+        if self.limits.max_steps.is_some_and(|max_steps| self.steps >= max_steps) {
+            Err(BFIError::StepLimitExceeded)
+        } else {
+            self.steps += 1;
+            #[cfg(feature = "tracing")]
+            if self.steps.is_multiple_of(TRACE_PROGRESS_INTERVAL) {
+                tracing::trace!(steps = self.steps, offset = self.offset(), pointer = self.p, "progress");
+            }
+            Ok(())
+        }
+    }
+
+    /// Reports the end of an execution at the debug level.
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    fn trace_finished(&self, result: &Result<(), BFIError>) {
+        #[cfg(feature = "tracing")]
+        match *result {
+            Ok(()) => tracing::debug!(steps = self.steps, output_bytes = self.output_len, "finished"),
+            Err(ref err) => tracing::debug!(
+                steps = self.steps,
+                output_bytes = self.output_len,
+                error = %err,
+                "failed"
+            ),
+        }
+    }
+
+    fn count_output(&mut self) -> Result<(), BFIError> {
+        if self.limits.max_output.is_some_and(|max_output| self.output_len >= max_output) {
+            Err(BFIError::OutputLimitExceeded)
+        } else {
+            self.output_len += 1;
+            Ok(())
+        }
+    }
+
+    fn output(&mut self, writer: &mut dyn ByteOut) -> Result<(), BFIError> {
+        self.count_output()?;
+        writer.write_byte(self.x.read(self.p) as u8)?;
+        Ok(())
+    }
+
+    fn store_input(&mut self, byte: Option<u8>) {
+        self.input_len += byte.is_some() as usize;
+        match (byte, self.eof) {
+            (Some(byte), _) => self.x.write(self.p, byte as i8),
+            (None, EofPolicy::Zero) => self.x.write(self.p, 0),
+            (None, EofPolicy::MinusOne) => self.x.write(self.p, -1),
+            (None, EofPolicy::Unchanged) => {}
+        }
+    }
+
+    fn input(&mut self, reader: &mut dyn ByteIn) -> Result<(), BFIError> {
+        let byte = reader.read_byte()?;
+        self.store_input(byte);
+        Ok(())
+    }
+
+    /// Returns the index of the next command after the `[` at `pc`.
+    fn start_jump(&self, command: Command) -> usize {
+        if self.x.read(self.p) == 0 {
+            command.jump + 1
+        } else {
+            self.pc + 1
+        }
+    }
+
+    /// Returns the index of the `[` matching the `]` at `pc`, which tests the
+    /// cell again.
+    fn end_jump(&self, command: Command) -> usize {
+        command.jump
+    }
+
+    /// Copies `image` into the first cells of the memory, one byte per cell,
+    /// keeping the other cells. A growable memory grows to hold it; otherwise
+    /// an image longer than the memory fails with `MemoryLimitExceeded`.
+    pub fn load_tape(&mut self, image: &[u8]) -> Result<(), BFIError> {
+        while self.growable && self.x.len() < image.len() {
+            self.grow()?;
+        }
+        if image.len() > self.x.len() {
+            return Err(BFIError::MemoryLimitExceeded);
+        }
+        for (index, &byte) in image.iter().enumerate() {
+            self.x.write(index, byte as i8);
+        }
+        Ok(())
+    }
+
+    /// Returns every cell of the memory as a byte, as read by `load_tape`.
+    pub fn save_tape(&self) -> Vec<u8> {
+        self.x.iter().map(|cell| cell as u8).collect()
+    }
+
+    /// Returns the value of the cell at `index`, or `None` if it is out of range of memory.
+    pub fn cell(&self, index: usize) -> Option<i8> {
+        self.x.get(index)
+    }
+
+    /// Returns the value of the cell at `index` as shown to users: unsigned if
+    /// the cells wrap around, signed otherwise.
+    pub fn cell_value(&self, index: usize) -> Option<i16> {
+        let cell = self.x.get(index)?;
+        Some(if self.wrapping { cell as u8 as i16 } else { cell as i16 })
+    }
+
+    fn extended_command(&mut self, command: u8) -> Result<(), BFIError> {
+        if let Some(ref mut extension) = self.extension {
+            let mut cell = self.x.read(self.p);
+            let result = extension.execute(command, &mut cell);
+            self.x.write(self.p, cell);
+            result?;
+        }
+        Ok(())
+    }
+
+    /// Executes the instruction at the program counter and advances it.
+    ///
+    /// Returns `Ok(false)` once the end of the program has been reached.
+    /// The syntax is not checked here; call `check_syntax` before stepping.
+    pub fn step(&mut self, reader: &mut dyn ByteIn, writer: &mut dyn ByteOut) -> Result<bool, BFIError> {
+        if self.steps == 0 {
+            self.check_assertions(self.pc)?;
+        }
+        let command = match self.commands.get(self.pc) {
+            Some(&command) => command,
+            None => return Ok(false),
+        };
+        self.count_step()?;
+
+        let pc = match command.byte {
+            b'>' => self.increment_pointer().map(|()| self.pc + 1)?,
+            b'<' => self.decrement_pointer().map(|()| self.pc + 1)?,
+            b'+' => self.increment_byte_at_pointer().map(|()| self.pc + 1)?,
+            b'-' => self.decrement_byte_at_pointer().map(|()| self.pc + 1)?,
+            b'.' => self.output(writer).map(|()| self.pc + 1)?,
+            b',' => self.input(reader).map(|()| self.pc + 1)?,
+            b'[' => self.start_jump(command),
+            b']' => self.end_jump(command),
+            byte => self.extended_command(byte).map(|()| self.pc + 1)?,
+        };
+        self.advance(pc)?;
+        Ok(self.pc < self.commands.len())
+    }
+
+    /// Moves the program counter to `pc`, checking the assertions passed if
+    /// it moves forward.
+    fn advance(&mut self, pc: usize) -> Result<(), BFIError> {
+        let forward = pc > self.pc;
+        self.pc = pc;
+        if forward {
+            self.check_assertions(pc)?;
+        }
+        Ok(())
+    }
+
+    /// Checks the assertions right before the command at `index`.
+    fn check_assertions(&self, index: usize) -> Result<(), BFIError> {
+        if self.assertions.is_empty() {
+            return Ok(());
+        }
+        let start = self.assertions.partition_point(|&(before, _)| before < index);
+        for (_, assertion) in self.assertions[start..].iter().take_while(|&&(before, _)| before == index) {
+            // Cells the memory has not grown to yet are zero.
+            let cell = self.x.get(assertion.cell()).unwrap_or(0);
+            if !assertion.holds(cell) {
+                return Err(AssertionFailure {
+                    assertion: assertion.clone(),
+                    cell,
+                }
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn interpret(&mut self, reader: &mut dyn ByteIn, writer: &mut dyn ByteOut) -> Result<(), BFIError> {
+        self.check_syntax()?;
+        self.execute(reader, writer)
+    }
+
+    /// Runs the program checked by `check_syntax` from the beginning.
+    fn execute(&mut self, reader: &mut dyn ByteIn, writer: &mut dyn ByteOut) -> Result<(), BFIError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("execute", code_bytes = self.c.as_bytes().len()).entered();
+
+        self.restart();
+        let mut result = self.run(reader, writer);
+        // Flushes on failure as well, keeping the error of the program.
+        if let Err(err) = writer.flush_bytes() {
+            result = result.and(Err(err.into()));
+        }
+        self.trace_finished(&result);
+        result
+    }
+
+    /// Same as `interpret` but reports the work done, also when it failed.
+    pub fn interpret_reported(&mut self, reader: &mut dyn ByteIn, writer: &mut dyn ByteOut) -> ExecutionReport {
+        if let Err(err) = self.check_syntax() {
+            self.restart();
+            return self.report(HaltReason::Error(err));
+        }
+        let halted_reason = match self.execute(reader, writer) {
+            Ok(()) => HaltReason::End,
+            Err(err) => HaltReason::Error(err),
+        };
+        self.report(halted_reason)
+    }
+
+    fn report(&self, halted_reason: HaltReason) -> ExecutionReport {
+        ExecutionReport {
+            steps: self.steps,
+            bytes_in: self.input_len,
+            bytes_out: self.output_len,
+            peak_pointer: self.peak_pointer,
+            halted_reason,
+        }
+    }
+
+    fn run(&mut self, reader: &mut dyn ByteIn, writer: &mut dyn ByteOut) -> Result<(), BFIError> {
+        while self.step(reader, writer)? {}
+        Ok(())
+    }
+}
+
+/// Discards the output of `fuzz_entry`, which is bounded by `max_output` alone.
+struct Discard;
+
+impl ByteOut for Discard {
+    fn write_byte(&mut self, _byte: u8) -> Result<(), io::Error> {
+        Ok(())
+    }
+}
+
+/// Runs `code` over `input` under `limits`, for fuzzers.
+///
+/// Never panics: invalid programs, including ones that are not UTF-8, and
+/// every limit surface as a `BFIError`. Memory stays within the default tape
+/// and the output is discarded, but only `limits.max_steps` bounds the time.
+pub fn fuzz_entry(code: &[u8], input: &[u8], limits: Limits) -> Result<(), BFIError> {
+    let mut bfi = BFI::new(String::from_utf8_lossy(code).into_owned());
+    bfi.set_limits(limits);
+    bfi.interpret(&mut { input }, &mut Discard)
+}
+
+#[cfg(test)]
+#[allow(clippy::legacy_numeric_constants, clippy::match_like_matches_macro)]
+mod tests {
+    use std::i8;
+    use std::io::Cursor;
+
+    use crate::fuzz_entry;
+    use crate::EofPolicy;
+    use crate::Extension;
+    use crate::HaltReason;
+    use crate::BFI;
+    use crate::BFIError;
+    use crate::Limits;
+    use crate::Strictness;
+    use crate::SyntaxIssue;
+    use crate::SyntaxIssueKind;
+
+    #[test]
+    fn test_check_syntax() {
+        let bfi = BFI::new("[->+<]".to_string());
+        assert!(bfi.check_syntax().is_ok());
+
+        let bfi = BFI::new("+->[".to_string());
+        assert!(if let BFIError::MissingClosingBrackets = bfi.check_syntax().unwrap_err() {
+            true
+        } else {
+            false
+        });
+
+        let bfi = BFI::new("[]+-]".to_string());
+        assert!(if let BFIError::MissingOpeningBrackets = bfi.check_syntax().unwrap_err() {
+            true
+        } else {
+            false
+        });
+
+        let bfi = BFI::new("+][".to_string());
+        assert!(matches!(bfi.check_syntax().unwrap_err(), BFIError::MissingOpeningBrackets));
+    }
+
+    #[test]
+    fn test_syntax_issues() {
+        assert_eq!(BFI::new("[->+<]".to_string()).syntax_issues(), []);
+
+        let issue = |kind, offset, line, column| SyntaxIssue { kind, offset, line, column };
+        assert_eq!(
+            BFI::new("+][".to_string()).syntax_issues(),
+            [
+                issue(SyntaxIssueKind::UnmatchedClosing, 1, 1, 2),
+                issue(SyntaxIssueKind::Unclosed, 2, 1, 3),
+            ]
+        );
+        assert_eq!(
+            BFI::new("[\né[[-]\n]]]".to_string()).syntax_issues(),
+            [issue(SyntaxIssueKind::UnmatchedClosing, 11, 3, 3)]
+        );
+        assert_eq!(
+            BFI::new("[\n  [+]\n  [".to_string()).syntax_issues(),
+            [issue(SyntaxIssueKind::Unclosed, 0, 1, 1), issue(SyntaxIssueKind::Unclosed, 10, 3, 3)]
+        );
+        assert_eq!(
+            BFI::new("é][".to_string()).syntax_issues()[0].to_string(),
+            "Unmatched ']' at line 1, column 2"
+        );
+
+        let mut bfi = BFI::new("[[]]\n [[[-]]]".to_string());
+        bfi.set_max_depth(2);
+        assert_eq!(bfi.syntax_issues(), [issue(SyntaxIssueKind::TooDeep, 8, 2, 4)]);
+        assert!(matches!(bfi.check_syntax(), Err(BFIError::NestingTooDeep)));
+
+        let mut bfi = BFI::new("[,]\n,".to_string());
+        assert_eq!(bfi.syntax_issues(), []);
+        bfi.set_deterministic(true);
+        assert_eq!(bfi.syntax_issues(), [issue(SyntaxIssueKind::Input, 1, 1, 2)]);
+        assert!(matches!(bfi.check_syntax(), Err(BFIError::InputForbidden)));
+        let mut bfi = BFI::new("[[,".to_string());
+        bfi.set_deterministic(true);
+        assert_eq!(
+            bfi.syntax_issues(),
+            [
+                issue(SyntaxIssueKind::Unclosed, 0, 1, 1),
+                issue(SyntaxIssueKind::Unclosed, 1, 1, 2),
+                issue(SyntaxIssueKind::Input, 2, 1, 3),
+            ]
+        );
+
+        let mut bfi = BFI::new("Hello, world >\n+[-]>> a-b é.d".to_string());
+        assert_eq!(bfi.syntax_issues(), []);
+        bfi.set_strictness(Strictness::Strict);
+        assert_eq!(
+            bfi.syntax_issues(),
+            [
+                issue(SyntaxIssueKind::Stray(','), 5, 1, 6),
+                issue(SyntaxIssueKind::Stray('-'), 23, 2, 9),
+                issue(SyntaxIssueKind::Stray('.'), 28, 2, 13),
+            ]
+        );
+        assert!(matches!(bfi.check_syntax(), Err(BFIError::StrayCharacters)));
+        bfi.set_strictness(Strictness::Pure);
+        let issues = bfi.syntax_issues();
+        assert_eq!(issues.len(), 17);
+        assert_eq!(issues[0], issue(SyntaxIssueKind::Unexpected('H'), 0, 1, 1));
+        assert_eq!(issues[14].to_string(), "Unexpected 'é' at line 2, column 12");
+
+        let bfi = BFI::new("][".to_string() + &"[".repeat(1_000_000));
+        assert_eq!(
+            bfi.syntax_issues(),
+            [
+                issue(SyntaxIssueKind::UnmatchedClosing, 0, 1, 1),
+                issue(SyntaxIssueKind::TooDeep, 1025, 1, 1026),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_increment_pointer() {
+        let mut bfi = BFI::new(".".to_string());
+        assert_eq!(bfi.p, 0);
+        bfi.increment_pointer().unwrap();
+        assert_eq!(bfi.p, 1);
+        bfi.increment_pointer().unwrap();
+        assert_eq!(bfi.p, 2);
+
+        bfi.p = bfi.x.len() - 2;
+        bfi.increment_pointer().unwrap();
+        assert_eq!(bfi.p, bfi.x.len() - 1);
+        assert!(if let BFIError::OutOfMemory = bfi.increment_pointer().unwrap_err() {
+            true
+        } else {
+            false
+        });
+        assert_eq!(bfi.p, bfi.x.len() - 1);
+    }
+
+    #[test]
+    fn test_check_decrement_pointer() {
+        let mut bfi = BFI::new(".".to_string());
+        assert_eq!(bfi.p, 0);
+        assert!(if let BFIError::OutOfMemory = bfi.decrement_pointer().unwrap_err() {
+            true
+        } else {
+            false
+        });
+        assert_eq!(bfi.p, 0);
+
+        bfi.p = 1;
+        bfi.decrement_pointer().unwrap();
+        assert_eq!(bfi.p, 0);
+
+        bfi.p = bfi.x.len() - 1;
+        bfi.decrement_pointer().unwrap();
+        assert_eq!(bfi.p, bfi.x.len() - 2);
+    }
+
+    #[test]
+    fn test_increment_byte_at_pointer() {
+        let mut bfi = BFI::new(".".to_string());
+        bfi.p = 0;
+        assert_eq!(bfi.x.read(0), 0);
+        bfi.increment_byte_at_pointer().unwrap();
+        assert_eq!(bfi.x.read(0), 1);
+        bfi.increment_byte_at_pointer().unwrap();
+        assert_eq!(bfi.x.read(0), 2);
+
+        bfi.p = 1;
+        bfi.x.write(1, i8::MAX - 1);
+        bfi.increment_byte_at_pointer().unwrap();
+        assert_eq!(bfi.x.read(1), i8::MAX);
+        assert!(if let BFIError::ArithmeticOverflow = bfi.increment_byte_at_pointer().unwrap_err() {
+            true
+        } else {
+            false
+        });
+        assert_eq!(bfi.x.read(1), i8::MAX);
+
+        bfi.p = 2;
+        bfi.x.write(2, i8::MIN);
+        bfi.increment_byte_at_pointer().unwrap();
+        assert_eq!(bfi.x.read(2), i8::MIN + 1);
+    }
+
+    #[test]
+    fn test_decrement_byte_at_pointer() {
+        let mut bfi = BFI::new(".".to_string());
+        bfi.p = 0;
+        assert_eq!(bfi.x.read(0), 0);
+        bfi.decrement_byte_at_pointer().unwrap();
+        assert_eq!(bfi.x.read(0), -1);
+        bfi.decrement_byte_at_pointer().unwrap();
+        assert_eq!(bfi.x.read(0), -2);
+
+        bfi.p = 1;
+        bfi.x.write(1, i8::MIN + 1);
+        bfi.decrement_byte_at_pointer().unwrap();
+        assert_eq!(bfi.x.read(1), i8::MIN);
+        assert!(if let BFIError::ArithmeticOverflow = bfi.decrement_byte_at_pointer().unwrap_err() {
+            true
+        } else {
+            false
+        });
+        assert_eq!(bfi.x.read(1), i8::MIN);
+
+        bfi.p = 2;
+        bfi.x.write(2, i8::MAX);
+        bfi.decrement_byte_at_pointer().unwrap();
+        assert_eq!(bfi.x.read(2), i8::MAX - 1);
+    }
+
+    #[test]
+    fn test_output() {
+        let mut bfi = BFI::new(".".to_string());
+        bfi.p = 0;
+        bfi.x.write(0, 0);
+
+        let mut cursor = Cursor::new(Vec::new());
+        bfi.output(&mut cursor).unwrap();
+        assert_eq!(cursor.get_ref()[0], 0);
+
+        bfi.p = 1;
+        bfi.x.write(1, 1);
+        bfi.output(&mut cursor).unwrap();
+        assert_eq!(cursor.get_ref()[0..2], [0, 1]);
+
+        bfi.p = 2;
+        bfi.x.write(2, i8::MAX);
+        bfi.output(&mut cursor).unwrap();
+        assert_eq!(cursor.get_ref()[0..3], [0, 1, i8::MAX as u8]);
+
+        bfi.p = 3;
+        bfi.x.write(3, i8::MIN);
+        bfi.output(&mut cursor).unwrap();
+        assert_eq!(cursor.get_ref()[0..4], [0, 1, i8::MAX as u8, i8::MIN as u8]);
+    }
+
+    #[test]
+    fn test_input() {
+        let mut bfi = BFI::new(".".to_string());
+        bfi.p = 0;
+        bfi.x.write(0, 0);
+
+        let mut cursor = Cursor::new(vec![0, 1, i8::MIN as u8, i8::MAX as u8, std::u8::MAX]);
+        bfi.input(&mut cursor).unwrap();
+        assert_eq!(bfi.x.read(0), 0);
+
+        bfi.p = 1;
+        bfi.input(&mut cursor).unwrap();
+        assert_eq!(bfi.x.to_vec()[0..2], [0, 1]);
+
+        bfi.p = 2;
+        bfi.input(&mut cursor).unwrap();
+        assert_eq!(bfi.x.to_vec()[0..3], [0, 1, i8::MIN]);
+
+        bfi.p = 3;
+        bfi.input(&mut cursor).unwrap();
+        assert_eq!(bfi.x.to_vec()[0..4], [0, 1, i8::MIN, i8::MAX]);
+
+        bfi.p = 4;
+        bfi.input(&mut cursor).unwrap();
+        assert_eq!(bfi.x.to_vec()[0..5], [0, 1, i8::MIN, i8::MAX, std::u8::MAX as i8]);
+    }
+
+    #[test]
+    fn test_start_jump() {
+        let mut bfi = BFI::new("[_]".to_string());
+        bfi.p = 0;
+        bfi.pc = 0;
+        bfi.x.write(0, 0);
+        assert_eq!(bfi.start_jump(bfi.commands[0]), 2);
+
+        bfi.x.write(0, 1);
+        assert_eq!(bfi.start_jump(bfi.commands[0]), 1);
+
+        let mut bfi = BFI::new("[_[[_][_]_]]".to_string());
+        bfi.p = 0;
+        bfi.pc = 0;
+        bfi.x.write(0, 0);
+        assert_eq!(bfi.start_jump(bfi.commands[0]), 8);
+        bfi.pc = 1;
+        assert_eq!(bfi.start_jump(bfi.commands[1]), 7);
+    }
+
+    #[test]
+    fn test_end_jump() {
+        let bfi = BFI::new("[_]".to_string());
+        assert_eq!(bfi.end_jump(bfi.commands[1]), 0);
+
+        let bfi = BFI::new("[_[[_][_]_]]".to_string());
+        assert_eq!(bfi.end_jump(bfi.commands[7]), 0);
+        assert_eq!(bfi.end_jump(bfi.commands[6]), 1);
+    }
+
+    #[test]
+    fn test_interpret() {
+        let code = r#"
+            ### Simple Adder ###
+            ,>,<
+            >[-<+>]<
+            .
+        "#;
+        let mut bfi = BFI::new(code.to_string());
+        let mut reader = Cursor::new(vec![1, 2]);
+        let mut writer = Cursor::new(Vec::new());
+        bfi.interpret(&mut reader, &mut writer).unwrap();
+        assert_eq!(writer.into_inner(), vec![3]);
+        assert_eq!(bfi.pc, bfi.commands.len());
+
+        let hello_world = r#"
+            Hello World program
+            >+++++++++[<++++++++>-]<.>+++++++[<++++>-]<+.+++++++..+++.[-]>++++++++[<++++>-]
+            <.#>+++++++++++[<+++++>-]<.>++++++++[<+++>-]<.+++.------.--------.[-]>++++++++[
+            <++++>-]<+.[-]++++++++++.
+        "#; // http://esoteric.sange.fi/brainfuck/bf-source/prog/HELLOBF.BF
+        let mut bfi = BFI::new(hello_world.to_string());
+        let mut reader = Cursor::new(Vec::new());
+        let mut writer = Cursor::new(Vec::new());
+        bfi.interpret(&mut reader, &mut writer).unwrap();
+        assert_eq!(writer.into_inner(), "Hello World!\n".as_bytes());
+        assert_eq!(bfi.pc, bfi.commands.len());
+    }
+
+    #[test]
+    fn test_from_file() {
+        let path = std::env::temp_dir().join(format!("bfi-test-{}.b", std::process::id()));
+        std::fs::write(&path, "++++++++[>++++++++<-]>+ é .").unwrap();
+        let mut bfi = BFI::from_file(path.to_str().unwrap().to_string()).unwrap();
+        let mut writer = Cursor::new(Vec::new());
+        bfi.interpret(&mut Cursor::new(Vec::new()), &mut writer).unwrap();
+        assert_eq!(writer.into_inner(), b"A");
+
+        // Comments of a mapped file are not decoded.
+        #[cfg(feature = "mmap")]
+        {
+            std::fs::write(&path, b"\xff+[\xe3]").unwrap();
+            let bfi = BFI::from_file(path.to_str().unwrap().to_string()).unwrap();
+            assert!(matches!(bfi.check_syntax(), Ok(())));
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_unicode_comments() {
+        // Multi-byte characters inside and between loops are comments.
+        let code = "café ++++++++[>++++++++<-日本語]>+. «fin»";
+        let mut bfi = BFI::new(code.to_string());
+        let mut reader = Cursor::new(Vec::new());
+        let mut writer = Cursor::new(Vec::new());
+        bfi.interpret(&mut reader, &mut writer).unwrap();
+        assert_eq!(writer.into_inner(), b"A");
+        assert_eq!(bfi.steps(), 116);
+
+        let mut bfi = BFI::new("é+".to_string());
+        assert_eq!(bfi.offset(), Some(2));
+        bfi.step(&mut Cursor::new(Vec::new()), &mut Cursor::new(Vec::new())).unwrap();
+        assert_eq!(bfi.offset(), None);
+    }
+
+    #[test]
+    fn test_annotations() {
+        // The commands of annotation lines are not executed, unlike those of
+        // other comments starting with `;;`.
+        let code = ";; cell 0..2 = counter, result\n++ ;; not at the start of the line.\n  ;; cell 1 = [-]\n\
+                    ;; plain comment+\n";
+        let mut bfi = BFI::new(code.to_string());
+        let mut writer = Cursor::new(Vec::new());
+        bfi.interpret(&mut Cursor::new(Vec::new()), &mut writer).unwrap();
+        assert_eq!(writer.into_inner(), b"\x02");
+        assert_eq!(bfi.steps(), 4);
+
+        // Nor checked, and the lines after them are located as usual.
+        let mut bfi = BFI::new(";; cell 0 = x[\n]".to_string());
+        bfi.set_strictness(Strictness::Pure);
+        let issues = bfi.syntax_issues();
+        assert_eq!(issues.len(), 1);
+        assert_eq!((issues[0].kind, issues[0].line, issues[0].column), (SyntaxIssueKind::UnmatchedClosing, 2, 1));
+    }
+
+    #[test]
+    fn test_assertions() {
+        let run = |code: &str| {
+            let mut bfi = BFI::new(code.to_string());
+            bfi.interpret(&mut Cursor::new(Vec::new()), &mut Vec::new()).map(|()| bfi.steps())
+        };
+        // Checked at each iteration, after the loop, and at the very start and end.
+        let code = ";;assert cell[0] == 0\n+++[\n;;assert cell[0] > 0\n-]\n;;assert cell[0] == 0\n";
+        assert_eq!(run(code).unwrap(), 13);
+        let err = run("++[-\n;;assert cell[0] == 1 @loop\n]").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Assertion @loop failed at line 2: cell[0] == 1, but cell 0 is 0"
+        );
+        assert!(matches!(run(";;assert cell[0] != 0"), Err(BFIError::AssertionFailed(_))));
+
+        // Skipped loops and jumps back do not pass the assertions.
+        assert!(run("[\n;;assert cell[0] == 1\n]").is_ok());
+        assert!(run("+[\n;;assert cell[0] == 1\n-]").is_ok());
+
+        let bfi = BFI::new("+\n;;assert cell[0] = 1\n".to_string());
+        assert!(matches!(bfi.check_syntax(), Err(BFIError::InvalidAssertion)));
+        assert_eq!(bfi.syntax_issues()[0].to_string(), "Invalid assertion at line 2, column 1");
+    }
+
+    #[test]
+    fn test_set_code() {
+        let mut bfi = BFI::new("+++>++".to_string());
+        let mut reader = Cursor::new(Vec::new());
+        let mut writer = Cursor::new(Vec::new());
+        bfi.interpret(&mut reader, &mut writer).unwrap();
+        bfi.set_code("<.>.".to_string());
+        assert_eq!(bfi.code(), b"<.>.");
+        bfi.interpret(&mut reader, &mut writer).unwrap();
+        assert_eq!(writer.into_inner(), vec![3, 2]);
+        assert_eq!(bfi.pointer(), 1);
+        assert_eq!(bfi.steps(), 4);
+    }
+
+    #[test]
+    fn test_limits() {
+        let mut bfi = BFI::new("+[]".to_string());
+        bfi.set_limits(Limits { max_steps: Some(100), ..Limits::default() });
+        let mut reader = Cursor::new(Vec::new());
+        let mut writer = Cursor::new(Vec::new());
+        assert!(matches!(bfi.interpret(&mut reader, &mut writer).unwrap_err(), BFIError::StepLimitExceeded));
+        assert_eq!(bfi.steps(), 100);
+
+        let mut bfi = BFI::new("+[.]".to_string());
+        bfi.set_limits(Limits { max_output: Some(3), ..Limits::default() });
+        let mut writer = Cursor::new(Vec::new());
+        assert!(matches!(bfi.interpret(&mut reader, &mut writer).unwrap_err(), BFIError::OutputLimitExceeded));
+        assert_eq!(writer.into_inner(), vec![1, 1, 1]);
+        assert_eq!(bfi.output_len(), 3);
+
+        let mut bfi = BFI::new(">>".to_string());
+        bfi.set_limits(Limits { max_cells: Some(2), ..Limits::default() });
+        let mut writer = Cursor::new(Vec::new());
+        assert!(matches!(bfi.interpret(&mut reader, &mut writer).unwrap_err(), BFIError::OutOfMemory));
+        assert_eq!(bfi.steps(), 2);
+    }
+
+    #[test]
+    fn test_interpret_reported() {
+        let mut bfi = BFI::new(">>,[.>,]<<".to_string());
+        let mut writer = Cursor::new(Vec::new());
+        let report = bfi.interpret_reported(&mut Cursor::new(b"abc".to_vec()), &mut writer);
+        assert!(matches!(report.halted_reason, HaltReason::End));
+        assert_eq!((report.steps, report.bytes_in, report.bytes_out), (21, 3, 3));
+        assert_eq!(report.peak_pointer, 5);
+
+        let report = bfi.interpret_reported(&mut Cursor::new(Vec::new()), &mut writer);
+        assert_eq!((report.bytes_in, report.bytes_out, report.peak_pointer), (0, 0, 5));
+
+        let mut bfi = BFI::new("+[.]".to_string());
+        bfi.set_limits(Limits { max_output: Some(2), ..Limits::default() });
+        let report = bfi.interpret_reported(&mut Cursor::new(Vec::new()), &mut writer);
+        assert!(matches!(report.halted_reason, HaltReason::Error(BFIError::OutputLimitExceeded)));
+        assert_eq!(report.bytes_out, 2);
+
+        let mut bfi = BFI::new("+]".to_string());
+        let report = bfi.interpret_reported(&mut Cursor::new(Vec::new()), &mut writer);
+        assert!(matches!(report.halted_reason, HaltReason::Error(BFIError::MissingOpeningBrackets)));
+        assert_eq!(report.steps, 0);
+    }
+
+    #[test]
+    fn test_growable() {
+        let mut reader = Cursor::new(Vec::new());
+        let mut writer = Cursor::new(Vec::new());
+        let mut bfi = BFI::new("+[>+]".to_string());
+        bfi.set_growable(true);
+        bfi.set_limits(Limits { max_cells: Some(100_000), ..Limits::default() });
+        assert!(matches!(bfi.interpret(&mut reader, &mut writer).unwrap_err(), BFIError::MemoryLimitExceeded));
+        assert_eq!(bfi.pointer(), 99_999);
+        assert_eq!(bfi.cell(99_999), Some(1));
+        assert_eq!(bfi.cell(100_000), None);
+
+        // Grows only as far as the pointer goes.
+        let mut bfi = BFI::new(">".repeat(40_000) + "+.");
+        bfi.set_growable(true);
+        bfi.interpret(&mut reader, &mut writer).unwrap();
+        assert_eq!(bfi.x.len(), 65536);
+        assert_eq!(writer.into_inner(), [1]);
+    }
+
+    #[test]
+    fn test_tape() {
+        let mut reader = Cursor::new(Vec::new());
+        let mut writer = Cursor::new(Vec::new());
+        let mut bfi = BFI::new("[>]+".to_string());
+        bfi.set_limits(Limits { max_cells: Some(4), ..Limits::default() });
+        bfi.load_tape(&[1, 0xff]).unwrap();
+        bfi.interpret(&mut reader, &mut writer).unwrap();
+        assert_eq!(bfi.save_tape(), [1, 0xff, 1, 0]);
+        assert!(matches!(bfi.load_tape(&[0; 5]), Err(BFIError::MemoryLimitExceeded)));
+
+        let mut bfi = BFI::new(String::new());
+        bfi.set_growable(true);
+        bfi.load_tape(&[7; 40_000]).unwrap();
+        assert_eq!(bfi.x.len(), 65536);
+        assert_eq!(bfi.cell(39_999), Some(7));
+    }
+
+    #[test]
+    fn test_wrapping() {
+        let mut reader = Cursor::new(Vec::new());
+        let mut writer = Cursor::new(Vec::new());
+        let code = "-.>".to_string() + &"+".repeat(257) + ".";
+        let mut bfi = BFI::new(code.clone());
+        assert!(matches!(bfi.interpret(&mut reader, &mut writer).unwrap_err(), BFIError::ArithmeticOverflow));
+
+        let mut writer = Cursor::new(Vec::new());
+        let mut bfi = BFI::new(code);
+        bfi.set_wrapping(true);
+        bfi.interpret(&mut reader, &mut writer).unwrap();
+        assert_eq!(writer.into_inner(), [255, 1]);
+        assert_eq!((bfi.cell(0), bfi.cell_value(0)), (Some(-1), Some(255)));
+    }
+
+    #[test]
+    fn test_eof_policy() {
+        for &(eof, expected) in &[
+            (EofPolicy::Zero, 0u8),
+            (EofPolicy::MinusOne, 255),
+            (EofPolicy::Unchanged, 7),
+        ] {
+            let mut bfi = BFI::new("+++++++,.".to_string());
+            bfi.set_eof_policy(eof);
+            let mut reader = Cursor::new(Vec::new());
+            let mut writer = Cursor::new(Vec::new());
+            bfi.interpret(&mut reader, &mut writer).unwrap();
+            assert_eq!(writer.into_inner(), vec![expected]);
+        }
+        assert_eq!("minus-one".parse(), Ok(EofPolicy::MinusOne));
+        assert!("-1".parse::<EofPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_cat() {
+        // Stops at the end of input, which is no zero byte of the input.
+        let mut bfi = BFI::new(",+[-.,+]".to_string());
+        bfi.set_eof_policy(EofPolicy::MinusOne);
+        let mut reader = Cursor::new(b"a\0b".to_vec());
+        let mut writer = Cursor::new(Vec::new());
+        bfi.interpret(&mut reader, &mut writer).unwrap();
+        assert_eq!(writer.into_inner(), b"a\0b");
+
+        // Reads the end of input again after it has been reached.
+        let mut bfi = BFI::new(",[.,]+,.".to_string());
+        let mut reader = Cursor::new(b"cat".to_vec());
+        let mut writer = Cursor::new(Vec::new());
+        bfi.interpret(&mut reader, &mut writer).unwrap();
+        assert_eq!(writer.into_inner(), b"cat\0");
+    }
+
+    #[test]
+    fn test_fuzz_entry() {
+        let limits = Limits {
+            max_steps: Some(10_000),
+            max_cells: Some(16),
+            max_output: Some(8),
+        };
+        assert!(fuzz_entry(b",[.,]", b"abc", limits).is_ok());
+        assert!(matches!(fuzz_entry(b"]+[", b"", limits), Err(BFIError::MissingOpeningBrackets)));
+        assert!(matches!(fuzz_entry(b"+[]", b"", limits), Err(BFIError::StepLimitExceeded)));
+        assert!(matches!(fuzz_entry(b"+[.]", b"", limits), Err(BFIError::OutputLimitExceeded)));
+        assert!(matches!(fuzz_entry(b"+[>+]", b"", limits), Err(BFIError::OutOfMemory)));
+        assert!(matches!(fuzz_entry(b"<", b"", limits), Err(BFIError::OutOfMemory)));
+        assert!(fuzz_entry(b"\xff[\xe3\x81]+\xc3", b"", limits).is_ok());
+    }
+
+    #[test]
+    fn test_fork() {
+        let mut reader = Cursor::new(Vec::new());
+        let mut writer = Cursor::new(Vec::new());
+        let mut bfi = BFI::new("+++>+.".to_string());
+        bfi.step(&mut reader, &mut writer).unwrap();
+        let mut fork = bfi.fork().unwrap();
+        bfi.load_tape(&[10]).unwrap();
+        while bfi.step(&mut reader, &mut writer).unwrap() {}
+        while fork.step(&mut reader, &mut writer).unwrap() {}
+        assert_eq!((bfi.cell(0), fork.cell(0)), (Some(12), Some(3)));
+        assert_eq!(fork.steps(), 6);
+        assert_eq!(writer.into_inner(), [1, 1]);
+
+        bfi.set_extension(Box::new(Double));
+        assert!(bfi.fork().is_none());
+    }
+
+    /// Doubles the current cell with `*`.
+    #[derive(Debug)]
+    struct Double;
+
+    impl Extension for Double {
+        fn handles(&self, command: u8) -> bool {
+            command == b'*'
+        }
+
+        fn execute(&mut self, _command: u8, cell: &mut i8) -> Result<(), BFIError> {
+            *cell = cell.checked_mul(2).ok_or(BFIError::ArithmeticOverflow)?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_extension() {
+        let mut bfi = BFI::new("+++** #.".to_string());
+        bfi.set_extension(Box::new(Double));
+        let mut reader = Cursor::new(Vec::new());
+        let mut writer = Cursor::new(Vec::new());
+        bfi.interpret(&mut reader, &mut writer).unwrap();
+        assert_eq!(writer.into_inner(), vec![12]);
+        assert_eq!(bfi.steps(), 6);
+
+        let mut bfi = BFI::new("+*******".to_string());
+        bfi.set_extension(Box::new(Double));
+        let mut writer = Cursor::new(Vec::new());
+        assert!(matches!(bfi.interpret(&mut reader, &mut writer).unwrap_err(), BFIError::ArithmeticOverflow));
+
+        // '#' is an unknown command of the dialect.
+        let mut bfi = BFI::new("+++** #.".to_string());
+        bfi.set_extension(Box::new(Double));
+        bfi.set_strictness(Strictness::Strict);
+        assert_eq!(bfi.syntax_issues()[0].to_string(), "Unexpected '#' at line 1, column 7");
+    }
+}
@@ -0,0 +1,408 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! Interpreters running together, the output of each one feeding the input
+//! of others through bounded channels, in any topology.
+//!
+//! ```
+//! use bfi::link::Network;
+//! use bfi::BFI;
+//!
+//! // The first program forwards its input, the second writes each byte twice.
+//! let mut network = Network::new();
+//! let forward = network.add(BFI::new(",[.,]".to_string()));
+//! let double = network.add(BFI::new(",[..,]".to_string()));
+//! network.connect_input(forward);
+//! network.connect(forward, double, 1);
+//! network.connect_output(double);
+//! let mut output = Vec::new();
+//! network.run(&mut &b"ab"[..], &mut output).unwrap();
+//! assert_eq!(output, b"aabb");
+//! ```
+
+use alloc::collections::VecDeque;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::fmt;
+
+use crate::io::{self, ByteIn, ByteOut};
+use crate::BFIError;
+use crate::BFI;
+
+/// Steps an interpreter takes before the next one gets its turn.
+const QUANTUM: usize = 1024;
+
+/// Channel of the input of the network.
+const INPUT: usize = 0;
+
+/// What a blocked interpreter waits for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wait {
+    /// A byte for `,` in an empty channel.
+    Input,
+    /// Room for `.` in a full channel.
+    Output,
+}
+
+#[derive(Debug)]
+pub enum LinkError {
+    /// The interpreter `node` failed.
+    Failed { node: usize, error: BFIError },
+    /// Every interpreter still running waits on a channel.
+    Deadlock { waiting: Vec<(usize, Wait)> },
+    /// Reading the input or flushing the output of the network failed.
+    Io(io::Error),
+}
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            LinkError::Failed { node, ref error } => write!(f, "Program {}: {}", node, error),
+            LinkError::Deadlock { ref waiting } => {
+                write!(f, "Deadlock:")?;
+                for (i, &(node, wait)) in waiting.iter().enumerate() {
+                    let separator = if i == 0 { "" } else { "," };
+                    match wait {
+                        Wait::Input => write!(f, "{} program {} waits for input", separator, node)?,
+                        Wait::Output => write!(f, "{} program {} waits to write", separator, node)?,
+                    }
+                }
+                Ok(())
+            }
+            LinkError::Io(ref err) => write!(f, "{}", err),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Channel {
+    bytes: VecDeque<u8>,
+    capacity: usize,
+    /// Whether the writer has ended, so that no more bytes will come.
+    closed: bool,
+}
+
+impl Channel {
+    fn new(capacity: usize) -> Self {
+        Self {
+            bytes: VecDeque::new(),
+            capacity: capacity.max(1),
+            closed: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Node {
+    bfi: BFI,
+    inputs: Vec<usize>,
+    outputs: Vec<usize>,
+    /// Whether `.` also writes to the output of the network.
+    output: bool,
+    ended: bool,
+}
+
+/// Reads the first byte of the channels in order of connection.
+struct Reader<'a> {
+    channels: &'a RefCell<&'a mut Vec<Channel>>,
+    inputs: &'a [usize],
+}
+
+impl ByteIn for Reader<'_> {
+    fn read_byte(&mut self) -> Result<Option<u8>, io::Error> {
+        let mut channels = self.channels.borrow_mut();
+        Ok(self.inputs.iter().find_map(|&channel| channels[channel].bytes.pop_front()))
+    }
+}
+
+/// Writes each byte to every channel, and possibly to the network output.
+struct Writer<'a> {
+    channels: &'a RefCell<&'a mut Vec<Channel>>,
+    outputs: &'a [usize],
+    output: Option<&'a mut dyn ByteOut>,
+}
+
+impl ByteOut for Writer<'_> {
+    fn write_byte(&mut self, byte: u8) -> Result<(), io::Error> {
+        let mut channels = self.channels.borrow_mut();
+        for &channel in self.outputs {
+            channels[channel].bytes.push_back(byte);
+        }
+        match self.output {
+            Some(ref mut output) => output.write_byte(byte),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Interpreters whose `,` and `.` are connected by channels.
+///
+/// The interpreters take turns in order of addition, each one running until
+/// it waits on a channel or for a fixed number of steps, so that runs are
+/// reproducible. A `,` reads from the first channel of the interpreter
+/// holding a byte, and gets the end of input once every channel has been
+/// emptied and the interpreters writing to them have ended. A `.` waits
+/// until every channel of the interpreter has room, and writes the byte to
+/// each of them.
+#[derive(Debug)]
+pub struct Network {
+    nodes: Vec<Node>,
+    channels: Vec<Channel>,
+}
+
+impl Default for Network {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Network {
+    pub fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            // Holds the byte of the input of the network being read.
+            channels: vec![Channel::new(1)],
+        }
+    }
+
+    /// Adds `bfi`, with neither input nor output yet, and returns its index.
+    pub fn add(&mut self, bfi: BFI) -> usize {
+        self.nodes.push(Node {
+            bfi,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            output: false,
+            ended: false,
+        });
+        self.nodes.len() - 1
+    }
+
+    /// Feeds the output of `from` to the input of `to` through a channel
+    /// holding up to `capacity` bytes, at least one.
+    pub fn connect(&mut self, from: usize, to: usize, capacity: usize) {
+        assert!(from < self.nodes.len() && to < self.nodes.len());
+        self.channels.push(Channel::new(capacity));
+        self.nodes[from].outputs.push(self.channels.len() - 1);
+        self.nodes[to].inputs.push(self.channels.len() - 1);
+    }
+
+    /// Feeds the input of the network to `node`, after its other channels.
+    pub fn connect_input(&mut self, node: usize) {
+        self.nodes[node].inputs.push(INPUT);
+    }
+
+    /// Writes the output of `node` to the output of the network.
+    pub fn connect_output(&mut self, node: usize) {
+        self.nodes[node].output = true;
+    }
+
+    /// Returns the interpreter `node`, e.g. to inspect its memory.
+    pub fn interpreter(&self, node: usize) -> &BFI {
+        &self.nodes[node].bfi
+    }
+
+    /// Returns what `node` waits for, if it is blocked.
+    fn wait(&self, node: usize) -> Option<Wait> {
+        let node = &self.nodes[node];
+        let channels = &self.channels;
+        match node.bfi.current_command() {
+            Some(b',') => {
+                let ready = node.inputs.iter().any(|&channel| !channels[channel].bytes.is_empty())
+                    || node.inputs.iter().all(|&channel| channels[channel].closed);
+                (!ready).then_some(Wait::Input)
+            }
+            Some(b'.') => node
+                .outputs
+                .iter()
+                .any(|&channel| channels[channel].bytes.len() >= channels[channel].capacity)
+                .then_some(Wait::Output),
+            _ => None,
+        }
+    }
+
+    /// Reads a byte of `input` if `node` would otherwise wait for it.
+    fn refill(&mut self, node: usize, input: &mut dyn ByteIn) -> Result<(), io::Error> {
+        let reads = self.nodes[node].inputs.contains(&INPUT) && !self.channels[INPUT].closed;
+        if reads && self.wait(node) == Some(Wait::Input) {
+            match input.read_byte()? {
+                Some(byte) => self.channels[INPUT].bytes.push_back(byte),
+                None => self.channels[INPUT].closed = true,
+            }
+        }
+        Ok(())
+    }
+
+    /// Executes a command of `node`, and returns whether it has not ended.
+    fn step(&mut self, node: usize, output: &mut dyn ByteOut) -> Result<bool, BFIError> {
+        let Node {
+            ref mut bfi,
+            ref inputs,
+            ref outputs,
+            output: writes,
+            ..
+        } = self.nodes[node];
+        let channels = RefCell::new(&mut self.channels);
+        let mut reader = Reader {
+            channels: &channels,
+            inputs,
+        };
+        let mut writer = Writer {
+            channels: &channels,
+            outputs,
+            output: if writes { Some(output) } else { None },
+        };
+        bfi.step(&mut reader, &mut writer)
+    }
+
+    /// Ends `node`, closing its channels.
+    fn end(&mut self, node: usize) {
+        self.nodes[node].ended = true;
+        for &channel in &self.nodes[node].outputs {
+            self.channels[channel].closed = true;
+        }
+    }
+
+    fn schedule(&mut self, input: &mut dyn ByteIn, output: &mut dyn ByteOut) -> Result<(), LinkError> {
+        loop {
+            let mut running = false;
+            let mut progress = false;
+            for node in 0..self.nodes.len() {
+                if self.nodes[node].ended {
+                    continue;
+                }
+                running = true;
+                for _ in 0..QUANTUM {
+                    self.refill(node, input).map_err(LinkError::Io)?;
+                    if self.wait(node).is_some() {
+                        break;
+                    }
+                    progress = true;
+                    let more = self.step(node, output).map_err(|error| LinkError::Failed { node, error })?;
+                    if !more {
+                        self.end(node);
+                        break;
+                    }
+                }
+            }
+            if !running {
+                return Ok(());
+            }
+            if !progress {
+                let waiting = (0..self.nodes.len())
+                    .filter(|&node| !self.nodes[node].ended)
+                    .filter_map(|node| self.wait(node).map(|wait| (node, wait)))
+                    .collect();
+                return Err(LinkError::Deadlock { waiting });
+            }
+        }
+    }
+
+    /// Runs every interpreter from its current state until all have ended,
+    /// one has failed, or all those still running wait on each other.
+    ///
+    /// `output` is flushed at the end, even on failure.
+    pub fn run(&mut self, input: &mut dyn ByteIn, output: &mut dyn ByteOut) -> Result<(), LinkError> {
+        for (node, n) in self.nodes.iter().enumerate() {
+            n.bfi
+                .check_syntax()
+                .map_err(|error| LinkError::Failed { node, error })?;
+        }
+        let mut result = self.schedule(input, output);
+        if let Err(err) = output.flush_bytes() {
+            result = result.and(Err(LinkError::Io(err)));
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::link::{LinkError, Network, Wait};
+    use crate::BFI;
+
+    #[test]
+    fn test_ring() {
+        // The first program sends a byte around the ring, and each program
+        // increments it before passing it on.
+        let mut network = Network::new();
+        let first = network.add(BFI::new("+.,+.".to_string()));
+        let second = network.add(BFI::new(",+.".to_string()));
+        network.connect(first, second, 1);
+        network.connect(second, first, 1);
+        network.connect_output(first);
+        let mut output = Vec::new();
+        network.run(&mut &b""[..], &mut output).unwrap();
+        assert_eq!(output, [1, 3]);
+        assert_eq!(network.interpreter(second).cell(0), Some(2));
+
+        // Both read before writing.
+        let mut network = Network::new();
+        let first = network.add(BFI::new(",.".to_string()));
+        let second = network.add(BFI::new(",.".to_string()));
+        network.connect(first, second, 1);
+        network.connect(second, first, 1);
+        match network.run(&mut &b""[..], &mut Vec::new()) {
+            Err(LinkError::Deadlock { waiting }) => {
+                assert_eq!(waiting, [(first, Wait::Input), (second, Wait::Input)])
+            }
+            result => panic!("{:?}", result),
+        }
+
+        // Both write more than the other channel holds.
+        let mut network = Network::new();
+        let first = network.add(BFI::new("+..,".to_string()));
+        let second = network.add(BFI::new("+..,".to_string()));
+        network.connect(first, second, 1);
+        network.connect(second, first, 1);
+        let err = network.run(&mut &b""[..], &mut Vec::new()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Deadlock: program 0 waits to write, program 1 waits to write"
+        );
+    }
+
+    #[test]
+    fn test_fan_out() {
+        // Each byte read goes to two programs, which merge into the last.
+        let mut network = Network::new();
+        let source = network.add(BFI::new(",[.,]".to_string()));
+        let lower = network.add(BFI::new(",[-.,]".to_string()));
+        let upper = network.add(BFI::new(",[+.,]".to_string()));
+        let sink = network.add(BFI::new(",[.,]".to_string()));
+        network.connect_input(source);
+        network.connect(source, lower, 1);
+        network.connect(source, upper, 1);
+        network.connect(lower, sink, 4);
+        network.connect(upper, sink, 4);
+        network.connect_output(sink);
+        let mut output = Vec::new();
+        network.run(&mut &b"bc"[..], &mut output).unwrap();
+        output.sort_unstable();
+        assert_eq!(output, b"abcd");
+
+        let mut network = Network::new();
+        network.add(BFI::new("+.".to_string()));
+        let failing = network.add(BFI::new("<".to_string()));
+        match network.run(&mut &b""[..], &mut Vec::new()) {
+            Err(LinkError::Failed { node, .. }) => assert_eq!(node, failing),
+            result => panic!("{:?}", result),
+        }
+    }
+}
@@ -0,0 +1,317 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! Programs as trees of commands, balanced by construction.
+//!
+//! With the `proptest` feature, `Program` implements
+//! `proptest::arbitrary::Arbitrary`, so `proptest!` tests can take programs as
+//! arguments. Generated programs are bounded in size and nesting, and shrink
+//! towards fewer and shallower commands.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+
+use crate::source_bytes;
+use crate::BFIError;
+use crate::DEFAULT_MAX_DEPTH;
+
+/// A command, with the body of a loop nested in it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Node {
+    Right,
+    Left,
+    Increment,
+    Decrement,
+    Output,
+    Input,
+    Loop(Vec<Node>),
+}
+
+/// A program whose brackets are balanced.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Program {
+    pub nodes: Vec<Node>,
+}
+
+fn write_nodes(f: &mut fmt::Formatter, nodes: &[Node]) -> fmt::Result {
+    for node in nodes {
+        match *node {
+            Node::Right => f.write_str(">")?,
+            Node::Left => f.write_str("<")?,
+            Node::Increment => f.write_str("+")?,
+            Node::Decrement => f.write_str("-")?,
+            Node::Output => f.write_str(".")?,
+            Node::Input => f.write_str(",")?,
+            Node::Loop(ref body) => {
+                f.write_str("[")?;
+                write_nodes(f, body)?;
+                f.write_str("]")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn count(nodes: &[Node]) -> usize {
+    nodes
+        .iter()
+        .map(|node| match *node {
+            Node::Loop(ref body) => 2 + count(body),
+            _ => 1,
+        })
+        .sum()
+}
+
+fn depth(nodes: &[Node]) -> usize {
+    nodes
+        .iter()
+        .map(|node| match *node {
+            Node::Loop(ref body) => 1 + depth(body),
+            _ => 0,
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+fn cancels(a: &Node, b: &Node) -> bool {
+    matches!(
+        (a, b),
+        (Node::Right, Node::Left)
+            | (Node::Left, Node::Right)
+            | (Node::Increment, Node::Decrement)
+            | (Node::Decrement, Node::Increment)
+    )
+}
+
+fn optimize(nodes: &[Node], top: bool) -> Vec<Node> {
+    let mut optimized: Vec<Node> = Vec::new();
+    for node in nodes {
+        let node = match *node {
+            // A loop right after another one, or before anything has
+            // changed the memory, starts on a zero cell and never runs.
+            Node::Loop(_) if matches!(optimized.last(), Some(Node::Loop(_))) => continue,
+            Node::Loop(_) if top && optimized.is_empty() => continue,
+            Node::Loop(ref body) => Node::Loop(optimize(body, false)),
+            ref node => node.clone(),
+        };
+        match optimized.last() {
+            Some(last) if cancels(last, &node) => {
+                optimized.pop();
+            }
+            _ => optimized.push(node),
+        }
+    }
+    if top {
+        // Moves and arithmetic after the last loop or I/O have no effect on the output.
+        while let Some(Node::Right | Node::Left | Node::Increment | Node::Decrement) = optimized.last() {
+            optimized.pop();
+        }
+    }
+    optimized
+}
+
+impl Program {
+    /// Number of commands, brackets included.
+    pub fn len(&self) -> usize {
+        count(&self.nodes)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Maximum nesting depth of the loops, 0 without loops.
+    pub fn depth(&self) -> usize {
+        depth(&self.nodes)
+    }
+
+    /// Returns the program without the commands that cannot change its
+    /// output: adjacent `+-`, `-+`, `<>`, and `><`, loops that start on a
+    /// cell known to be zero, and commands after the last loop or I/O.
+    ///
+    /// The output is the same for every program that runs without error,
+    /// but some that fail, e.g. on an overflow undone right away, no longer
+    /// do.
+    pub fn optimized(&self) -> Program {
+        Program {
+            nodes: optimize(&self.nodes, true),
+        }
+    }
+}
+
+/// Formats the program as source code.
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_nodes(f, &self.nodes)
+    }
+}
+
+/// Parses source code, skipping comments. Loops may be nested at most
+/// `DEFAULT_MAX_DEPTH` deep, so that the recursive `Display` is safe.
+impl FromStr for Program {
+    type Err = BFIError;
+
+    fn from_str(code: &str) -> Result<Self, BFIError> {
+        // Bodies of the loops being parsed, innermost last.
+        let mut stack = vec![Vec::new()];
+        for (_, byte) in source_bytes(code.as_bytes()) {
+            let node = match byte {
+                b'>' => Node::Right,
+                b'<' => Node::Left,
+                b'+' => Node::Increment,
+                b'-' => Node::Decrement,
+                b'.' => Node::Output,
+                b',' => Node::Input,
+                b'[' if stack.len() > DEFAULT_MAX_DEPTH => return Err(BFIError::NestingTooDeep),
+                b'[' => {
+                    stack.push(Vec::new());
+                    continue;
+                }
+                b']' if stack.len() > 1 => Node::Loop(stack.pop().unwrap_or_default()),
+                b']' => return Err(BFIError::MissingOpeningBrackets),
+                _ => continue,
+            };
+            if let Some(body) = stack.last_mut() {
+                body.push(node);
+            }
+        }
+        match stack.pop() {
+            Some(nodes) if stack.is_empty() => Ok(Program { nodes }),
+            _ => Err(BFIError::MissingClosingBrackets),
+        }
+    }
+}
+
+/// Nesting depth of the loops of generated programs.
+#[cfg(feature = "proptest")]
+const MAX_DEPTH: u32 = 4;
+/// Number of commands of generated programs, loops included.
+#[cfg(feature = "proptest")]
+const MAX_NODES: u32 = 64;
+/// Number of commands in the top level and in each loop body.
+#[cfg(feature = "proptest")]
+const MAX_BODY: usize = 16;
+
+#[cfg(feature = "proptest")]
+impl proptest::arbitrary::Arbitrary for Program {
+    type Parameters = ();
+    type Strategy = proptest::strategy::BoxedStrategy<Program>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        use proptest::collection::vec;
+        use proptest::prelude::*;
+
+        let command = prop_oneof![
+            Just(Node::Right),
+            Just(Node::Left),
+            Just(Node::Increment),
+            Just(Node::Decrement),
+            Just(Node::Output),
+            Just(Node::Input),
+        ];
+        let node = command.prop_recursive(MAX_DEPTH, MAX_NODES, MAX_BODY as u32, |inner| {
+            vec(inner, 0..MAX_BODY).prop_map(Node::Loop)
+        });
+        vec(node, 0..MAX_BODY)
+            .prop_map(|nodes| Program { nodes })
+            .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::program::{Node, Program};
+    use crate::BFIError;
+
+    #[test]
+    fn test_parse() {
+        let program: Program = "+[->,[.]<]x".parse().unwrap();
+        assert_eq!(
+            program.nodes,
+            vec![
+                Node::Increment,
+                Node::Loop(vec![
+                    Node::Decrement,
+                    Node::Right,
+                    Node::Input,
+                    Node::Loop(vec![Node::Output]),
+                    Node::Left,
+                ]),
+            ]
+        );
+        assert_eq!(program.to_string(), "+[->,[.]<]");
+        assert_eq!(";; cell 0..2 = a, b\n+".parse::<Program>().unwrap().to_string(), "+");
+
+        assert!(matches!(
+            "[[]".parse::<Program>(),
+            Err(BFIError::MissingClosingBrackets)
+        ));
+        assert!(matches!(
+            "][".parse::<Program>(),
+            Err(BFIError::MissingOpeningBrackets)
+        ));
+
+        let deep = "[".repeat(1024) + &"]".repeat(1024);
+        assert_eq!(deep.parse::<Program>().unwrap().to_string(), deep);
+        assert!(matches!(
+            "[".repeat(1_000_000).parse::<Program>(),
+            Err(BFIError::NestingTooDeep)
+        ));
+    }
+
+    #[test]
+    fn test_optimized() {
+        let optimize = |code: &str| code.parse::<Program>().unwrap().optimized().to_string();
+        assert_eq!(optimize("[.]+-[-]+>+<-<>[-]>.[.][,]"), "+>+<-[-]>.[.]");
+        assert_eq!(optimize("+[>+<-]><+-[x]>."), "+[>+<-]>.");
+        assert_eq!(optimize("+[<>[-][+]]>+"), "+[[-]]");
+        assert_eq!(optimize("+>+-<-"), "");
+
+        let program: Program = "+[->[.]<],".parse().unwrap();
+        assert_eq!(program.len(), 10);
+        assert_eq!(program.depth(), 2);
+        assert_eq!(Program::default().depth(), 0);
+        assert!(Program::default().is_empty());
+    }
+
+    #[cfg(feature = "proptest")]
+    proptest::proptest! {
+        #[test]
+        fn test_arbitrary(program: Program) {
+            let code = program.to_string();
+            proptest::prop_assert!(crate::BFI::new(code.clone()).check_syntax().is_ok());
+            proptest::prop_assert_eq!(code.parse::<Program>().unwrap(), program);
+        }
+
+        #[test]
+        fn test_optimized_output(program: Program) {
+            let run = |program: &Program| {
+                let mut bfi = crate::BFI::new(program.to_string());
+                bfi.set_limits(crate::Limits { max_steps: Some(10_000), ..crate::Limits::default() });
+                let mut output = Vec::new();
+                bfi.interpret(&mut &b"bfi"[..], &mut output).map(|_| output)
+            };
+            if let Ok(output) = run(&program) {
+                proptest::prop_assert_eq!(run(&program.optimized()).unwrap(), output);
+            }
+        }
+    }
+}
@@ -0,0 +1,102 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! Python bindings of the interpreter, built with `maturin develop`.
+
+use std::io::Cursor;
+
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use crate::BFIError;
+use crate::BFI;
+
+create_exception!(bfi, BfiError, PyException);
+
+impl From<BFIError> for PyErr {
+    fn from(err: BFIError) -> PyErr {
+        BfiError::new_err(err.to_string())
+    }
+}
+
+/// Brainfuck interpreter holding the program, the memory, and the I/O buffers.
+#[pyclass(name = "Bfi", unsendable)]
+pub struct PyBfi {
+    bfi: BFI,
+    input: Cursor<Vec<u8>>,
+    output: Vec<u8>,
+}
+
+#[pymethods]
+impl PyBfi {
+    /// With `wrap`, cells wrap around as unsigned bytes instead of overflowing.
+    #[new]
+    #[pyo3(signature = (code = String::new(), input = Vec::new(), wrap = false))]
+    fn new(code: String, input: Vec<u8>, wrap: bool) -> PyResult<Self> {
+        let mut bfi = BFI::new(code);
+        bfi.check_syntax()?;
+        bfi.set_wrapping(wrap);
+        Ok(Self {
+            bfi,
+            input: Cursor::new(input),
+            output: Vec::new(),
+        })
+    }
+
+    /// Runs `code` from scratch with `input` and returns the output, keeping
+    /// whether the cells wrap.
+    #[pyo3(signature = (code, input = Vec::new()))]
+    fn run<'py>(&mut self, py: Python<'py>, code: String, input: Vec<u8>) -> PyResult<Bound<'py, PyBytes>> {
+        *self = Self::new(code, input, self.bfi.wrapping)?;
+        self.bfi.interpret(&mut self.input, &mut self.output)?;
+        Ok(PyBytes::new(py, &self.output))
+    }
+
+    /// Executes one character of the program. Returns `False` once the program has ended.
+    fn step(&mut self) -> PyResult<bool> {
+        Ok(self.bfi.step(&mut self.input, &mut self.output)?)
+    }
+
+    /// Output written so far.
+    #[getter]
+    fn output<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.output)
+    }
+
+    /// Values of all cells of the memory, from 0 to 255 if they wrap.
+    #[getter]
+    fn tape(&self) -> Vec<i16> {
+        (0..self.bfi.x.len()).map_while(|i| self.bfi.cell_value(i)).collect()
+    }
+
+    /// Index of the cell under the pointer.
+    #[getter]
+    fn pointer(&self) -> usize {
+        self.bfi.p
+    }
+}
+
+#[pymodule]
+fn bfi(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyBfi>()?;
+    m.add("BfiError", m.py().get_type::<BfiError>())?;
+    Ok(())
+}
@@ -0,0 +1,154 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! Names of memory cells given by annotation lines in the source.
+//!
+//! A line starting with `;;`, spaces aside, is an annotation if it names
+//! cells or is an assertion, see `assertion`: its commands are not executed.
+//! `;; cell 4 = flag` names a single cell, and
+//! `;; cell 0..3 = counter, result, tmp` names cells 0, 1, and 2 in order.
+//! Other lines starting with `;;` are plain comments, whose commands run.
+//!
+//! ```
+//! use bfi::symbols::Symbols;
+//!
+//! let symbols = Symbols::parse(b";; cell 0..2 = x, y\n+>++");
+//! assert_eq!(symbols.name(1), Some("y"));
+//! assert_eq!(symbols.label(2), "cell 2");
+//! ```
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::assertion;
+
+/// Text of the annotation on `line`, after the `;;`.
+pub(crate) fn annotation(line: &[u8]) -> Option<&[u8]> {
+    let start = line.iter().position(|&byte| byte != b' ' && byte != b'\t')?;
+    line[start..].strip_prefix(b";;")
+}
+
+/// Whether the line starting `code` is an annotation whose commands are not
+/// executed: one naming cells or an assertion, valid or not.
+pub(crate) fn is_annotation(code: &[u8]) -> bool {
+    let line = code.split(|&byte| byte == b'\n').next().unwrap_or_default();
+    let names = |text: &[u8]| core::str::from_utf8(text).ok().and_then(parse_names).is_some();
+    annotation(line).is_some_and(|text| assertion::assertion_text(text).is_some() || names(text))
+}
+
+/// Names of the cells, by index.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Symbols {
+    names: BTreeMap<usize, String>,
+}
+
+impl Symbols {
+    /// Collects the cell names of the annotations of `code`.
+    pub fn parse(code: &[u8]) -> Self {
+        let mut symbols = Self::default();
+        symbols.add(code);
+        symbols
+    }
+
+    /// Adds the cell names of the annotations of `code`, replacing the names
+    /// already given to the same cells.
+    pub fn add(&mut self, code: &[u8]) {
+        for line in code.split(|&byte| byte == b'\n') {
+            let names = annotation(line)
+                .and_then(|text| core::str::from_utf8(text).ok())
+                .and_then(parse_names);
+            if let Some(names) = names {
+                self.names.extend(names);
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Index of the cell called `name`, if any.
+    pub fn index(&self, name: &str) -> Option<usize> {
+        self.names.iter().find(|&(_, cell)| cell == name).map(|(&index, _)| index)
+    }
+
+    /// Name of the cell at `index`, if any.
+    pub fn name(&self, index: usize) -> Option<&str> {
+        self.names.get(&index).map(String::as_str)
+    }
+
+    /// Name of the cell at `index`, or else `cell INDEX`.
+    pub fn label(&self, index: usize) -> String {
+        match self.name(index) {
+            Some(name) => name.to_string(),
+            None => format!("cell {}", index),
+        }
+    }
+}
+
+/// Parses `cell N = name` or `cell A..B = name, ...` with as many names as
+/// cells from A up to B excluded.
+fn parse_names(text: &str) -> Option<Vec<(usize, String)>> {
+    let rest = text.trim_start().strip_prefix("cell")?;
+    if !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+    let (range, names) = rest.split_once('=')?;
+    let range = range.trim();
+    let (start, end) = match range.split_once("..") {
+        Some((start, end)) => (start.trim().parse().ok()?, end.trim().parse().ok()?),
+        None => {
+            let index: usize = range.parse().ok()?;
+            (index, index.checked_add(1)?)
+        }
+    };
+    let names: Vec<&str> = names.split(',').map(str::trim).collect();
+    if start >= end || names.len() != end - start || names.iter().any(|name| name.is_empty()) {
+        return None;
+    }
+    Some((start..).zip(names.into_iter().map(String::from)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::symbols::Symbols;
+
+    #[test]
+    fn test_parse() {
+        let symbols = Symbols::parse(
+            b"  ;; cell 0..3 = counter, result, tmp\n\
+              ;;cell 5=flag\n\
+              ;; cell 6..8 = too, many, names\n\
+              ;; cells 9 = typo\n\
+              cell 10 = not an annotation\n\
+              ;; cell 2 = scratch\n",
+        );
+        assert_eq!(symbols.name(0), Some("counter"));
+        assert_eq!(symbols.name(1), Some("result"));
+        assert_eq!(symbols.name(2), Some("scratch"));
+        assert_eq!(symbols.name(5), Some("flag"));
+        assert_eq!(symbols.label(3), "cell 3");
+        assert_eq!(symbols.label(0), "counter");
+        assert_eq!(symbols.index("flag"), Some(5));
+        assert!((6..=10).all(|index| symbols.name(index).is_none()));
+        assert!(Symbols::parse(b"+[>]").is_empty());
+    }
+}
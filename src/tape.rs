@@ -0,0 +1,152 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! Memory of the interpreter, in pages shared with its forks until written.
+
+use alloc::collections::TryReserveError;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::array;
+use core::sync::atomic::{AtomicI8, Ordering};
+
+/// Cells per page.
+const PAGE: usize = 4096;
+
+// Atomic cells let the owner of the only reference to a page write to it
+// without the read-modify-write of `Arc::get_mut` at every command.
+type Page = [AtomicI8; PAGE];
+
+fn zero_page() -> Arc<Page> {
+    Arc::new(array::from_fn(|_| AtomicI8::new(0)))
+}
+
+/// Cells of the memory. The cells of the last page past the end are zero.
+#[derive(Debug, Clone)]
+pub struct Tape {
+    pages: Vec<Arc<Page>>,
+    len: usize,
+}
+
+impl Tape {
+    /// Returns `len` zero cells, sharing a single page until written.
+    pub fn new(len: usize) -> Self {
+        Self {
+            pages: vec![zero_page(); len.div_ceil(PAGE)],
+            len,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn get(&self, index: usize) -> Option<i8> {
+        (index < self.len).then(|| self.pages[index / PAGE][index % PAGE].load(Ordering::Relaxed))
+    }
+
+    // Past the end, the index is only checked in debug builds, since the
+    // pages still bound it and the interpreter keeps the pointer in range.
+
+    /// Returns the cell at `index`, which must be in range.
+    pub fn read(&self, index: usize) -> i8 {
+        debug_assert!(index < self.len, "cell {} out of range of {} cells", index, self.len);
+        self.pages[index / PAGE][index % PAGE].load(Ordering::Relaxed)
+    }
+
+    /// Sets the cell at `index`, which must be in range, first copying its
+    /// page if a fork shares it.
+    pub fn write(&mut self, index: usize, value: i8) {
+        debug_assert!(index < self.len, "cell {} out of range of {} cells", index, self.len);
+        self.unshare(index / PAGE)[index % PAGE].store(value, Ordering::Relaxed);
+    }
+
+    fn unshare(&mut self, page: usize) -> &Page {
+        let page = &mut self.pages[page];
+        // Other references can only be made from this one, which is borrowed.
+        if Arc::strong_count(page) > 1 {
+            *page = Arc::new(array::from_fn(|i| AtomicI8::new(page[i].load(Ordering::Relaxed))));
+        }
+        page
+    }
+
+    /// Lets the memory end after `len` cells, if it is longer.
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+        self.pages.truncate(len.div_ceil(PAGE));
+        self.len = len;
+        let end = len % PAGE;
+        let last = self.pages.len().wrapping_sub(1);
+        if end > 0 && self.pages[last][end..].iter().any(|cell| cell.load(Ordering::Relaxed) != 0) {
+            for cell in &self.unshare(last)[end..] {
+                cell.store(0, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Adds zero cells up to `len`, if it is shorter.
+    pub fn try_grow(&mut self, len: usize) -> Result<(), TryReserveError> {
+        let pages = len.div_ceil(PAGE);
+        if pages > self.pages.len() {
+            self.pages.try_reserve_exact(pages - self.pages.len())?;
+            self.pages.resize(pages, zero_page());
+        }
+        self.len = self.len.max(len);
+        Ok(())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = i8> + '_ {
+        self.pages
+            .iter()
+            .flat_map(|page| page.iter().map(|cell| cell.load(Ordering::Relaxed)))
+            .take(self.len)
+    }
+
+    #[cfg(test)]
+    pub fn to_vec(&self) -> Vec<i8> {
+        self.iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tape::{Tape, PAGE};
+
+    #[test]
+    fn test_tape() {
+        let mut tape = Tape::new(PAGE + 10);
+        tape.write(PAGE + 9, 3);
+        let mut fork = tape.clone();
+        fork.write(PAGE + 9, 4);
+        fork.write(0, 1);
+        assert_eq!((tape.read(0), tape.read(PAGE + 9)), (0, 3));
+        assert_eq!((fork.read(0), fork.read(PAGE + 9)), (1, 4));
+
+        // The cells past the end come back as zero.
+        tape.truncate(PAGE + 5);
+        assert_eq!(tape.get(PAGE + 5), None);
+        tape.try_grow(3 * PAGE).unwrap();
+        assert_eq!(tape.len(), 3 * PAGE);
+        assert_eq!(tape.get(PAGE + 9), Some(0));
+        assert_eq!(tape.iter().count(), 3 * PAGE);
+        assert_eq!(fork.to_vec()[PAGE + 9], 4);
+    }
+}
@@ -0,0 +1,195 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! Termination proofs for programs whose loops count down.
+//!
+//! A loop halts if its body, which must itself halt, either moves the
+//! pointer by a constant other than zero, so that it leaves a bounded memory,
+//! or returns to the cell of the loop having changed it by a constant that
+//! reaches zero or overflows. Anything else is left unknown.
+//!
+//! ```
+//! use bfi::program::Program;
+//! use bfi::termination::{termination, Semantics, Termination};
+//!
+//! let program: Program = ",[->++<]>.".parse().unwrap();
+//! assert_eq!(termination(&program, Semantics::default()), Termination::Halts);
+//! let program: Program = ",[>+<]".parse().unwrap();
+//! assert_eq!(termination(&program, Semantics::default()), Termination::Unknown { command: 1 });
+//! ```
+
+use alloc::collections::BTreeMap;
+
+use crate::program::{Node, Program};
+
+/// How the analyzed program runs.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Semantics {
+    /// Bytes wrap around instead of overflowing, see `BFI::set_wrapping`.
+    pub wrapping: bool,
+    /// The memory grows without a ceiling, see `BFI::set_growable`.
+    pub unbounded: bool,
+}
+
+/// Outcome of `termination`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Termination {
+    /// The program halts on every input, possibly with an error.
+    Halts,
+    /// No proof was found for the loop starting at the 0-based `command`,
+    /// counting the commands of the program as it is displayed.
+    Unknown { command: usize },
+}
+
+/// What running some commands once does to a cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Cell {
+    /// Adds a constant.
+    Add(i32),
+    /// Stores a constant.
+    Set(i32),
+    Unknown,
+}
+
+/// What running some commands once does, relative to the starting cell.
+#[derive(Debug, Default)]
+struct Effect {
+    /// Move of the pointer, or `None` if it depends on the memory.
+    shift: Option<isize>,
+    /// Cells changed, by their offset, while the shift is known.
+    cells: BTreeMap<isize, Cell>,
+}
+
+impl Effect {
+    fn update(&mut self, f: impl FnOnce(Cell) -> Cell) {
+        if let Some(at) = self.shift {
+            let cell = self.cells.entry(at).or_insert(Cell::Add(0));
+            *cell = f(*cell);
+        }
+    }
+}
+
+/// Whether a loop halts once entered, if an iteration of it has `effect`.
+fn halts(effect: &Effect, semantics: Semantics) -> bool {
+    match effect.shift {
+        Some(0) => match effect.cells.get(&0) {
+            // An odd step reaches zero modulo 256; any other one overflows
+            // unless bytes wrap around.
+            Some(&Cell::Add(delta)) => delta % 2 != 0 || (!semantics.wrapping && delta != 0),
+            Some(&Cell::Set(value)) => value.rem_euclid(256) == 0,
+            _ => false,
+        },
+        Some(_) => !semantics.unbounded,
+        None => false,
+    }
+}
+
+/// Effect of `nodes`, the first of which is the command `index`, or the
+/// command of the first loop in them with no proof.
+fn effect(nodes: &[Node], index: &mut usize, semantics: Semantics) -> Result<Effect, usize> {
+    let mut effect = Effect {
+        shift: Some(0),
+        cells: BTreeMap::new(),
+    };
+    for node in nodes {
+        let start = *index;
+        *index += 1;
+        match *node {
+            Node::Right => effect.shift = effect.shift.map(|at| at + 1),
+            Node::Left => effect.shift = effect.shift.map(|at| at - 1),
+            Node::Increment => effect.update(|cell| match cell {
+                Cell::Add(delta) => Cell::Add(delta + 1),
+                Cell::Set(value) => Cell::Set(value + 1),
+                Cell::Unknown => Cell::Unknown,
+            }),
+            Node::Decrement => effect.update(|cell| match cell {
+                Cell::Add(delta) => Cell::Add(delta - 1),
+                Cell::Set(value) => Cell::Set(value - 1),
+                Cell::Unknown => Cell::Unknown,
+            }),
+            Node::Output => (),
+            Node::Input => effect.update(|_| Cell::Unknown),
+            Node::Loop(ref body) => {
+                let inner = self::effect(body, index, semantics)?;
+                *index += 1;
+                if !halts(&inner, semantics) {
+                    return Err(start);
+                }
+                // The loop runs an unknown number of times, and ends on a zero cell.
+                match (effect.shift, inner.shift) {
+                    (Some(at), Some(0)) => {
+                        for &offset in inner.cells.keys() {
+                            effect.cells.insert(at + offset, Cell::Unknown);
+                        }
+                        effect.cells.insert(at, Cell::Set(0));
+                    }
+                    _ => effect.shift = None,
+                }
+            }
+        }
+    }
+    Ok(effect)
+}
+
+/// Tries to prove that `program`, run with `semantics`, halts on every input.
+pub fn termination(program: &Program, semantics: Semantics) -> Termination {
+    match effect(&program.nodes, &mut 0, semantics) {
+        Ok(_) => Termination::Halts,
+        Err(command) => Termination::Unknown { command },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::program::Program;
+    use crate::termination::{termination, Semantics, Termination};
+
+    fn check(code: &str, semantics: Semantics) -> Termination {
+        termination(&code.parse::<Program>().unwrap(), semantics)
+    }
+
+    #[test]
+    fn test_termination() {
+        let wrapping = Semantics {
+            wrapping: true,
+            ..Semantics::default()
+        };
+        let unbounded = Semantics {
+            unbounded: true,
+            ..Semantics::default()
+        };
+        assert_eq!(check("+++.,.", wrapping), Termination::Halts);
+        assert_eq!(check(",[-]", wrapping), Termination::Halts);
+        assert_eq!(check(",[>+++<---]", wrapping), Termination::Halts);
+        // An even step may skip zero, but then overflows.
+        assert_eq!(check(",[--]", Semantics::default()), Termination::Halts);
+        assert_eq!(check(",[--]", wrapping), Termination::Unknown { command: 1 });
+        // The inner loop clears the cell, which ends the outer one.
+        assert_eq!(check(",[>,[-]<[-]]", wrapping), Termination::Halts);
+        assert_eq!(check(",[[-]+]", wrapping), Termination::Unknown { command: 1 });
+        // Scans leave a bounded memory.
+        assert_eq!(check(",[>,]", Semantics::default()), Termination::Halts);
+        assert_eq!(check(",[>,]", unbounded), Termination::Unknown { command: 1 });
+        // A scan inside a loop leaves the pointer unknown.
+        assert_eq!(check(",[[>]<-]", wrapping), Termination::Unknown { command: 1 });
+        assert_eq!(check(",[,]", wrapping), Termination::Unknown { command: 1 });
+        assert_eq!(check("+[-[++]]", wrapping), Termination::Unknown { command: 3 });
+        assert_eq!(check("+[]", Semantics::default()), Termination::Unknown { command: 1 });
+    }
+}
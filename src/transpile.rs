@@ -0,0 +1,174 @@
+/*
+ *  bfi-rs (Brainfuck Interpreter by Rust)
+ *  Copyright (C) 2018 cat-in-136
+ *
+ *  This program is free software; you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation; either version 2 of the License, or
+ *  (at your option) any later version.
+ *
+ *  This program is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with this program; if not, write to the Free Software
+ *  Foundation, Inc., 59 Temple Place, Suite 330, Boston, MA  02111-1307 USA
+ */
+
+//! Translation of brainfuck programs into other languages.
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use crate::source_bytes;
+use crate::BFIError;
+use crate::BFI;
+
+fn push_line(out: &mut String, depth: usize, line: &str) {
+    for _ in 0..depth {
+        out.push_str("    ");
+    }
+    out.push_str(line);
+    out.push('\n');
+}
+
+/// Translates `code` into a Rust function named `name`.
+///
+/// The generated function has the signature
+/// `fn(&mut dyn std::io::Read, &mut dyn std::io::Write) -> Result<(), bfi::BFIError>`
+/// and behaves like `BFI::interpret` with the default settings: reads are retried
+/// when interrupted, and the writer is flushed at the end, also on failure. The
+/// crate using it must depend on `bfi`.
+pub fn to_rust(code: &str, name: &str) -> Result<String, BFIError> {
+    BFI::new(code.to_string()).check_syntax()?;
+
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("transpile", name).entered();
+
+    let mut out = String::new();
+    out.push_str("#[allow(dead_code, unused_mut, unused_variables)]\n");
+    let _ = writeln!(
+        out,
+        "pub fn {}(reader: &mut dyn ::std::io::Read, writer: &mut dyn ::std::io::Write) \
+         -> ::std::result::Result<(), ::bfi::BFIError> {{",
+        name
+    );
+    for line in [
+        "fn read_byte(reader: &mut dyn ::std::io::Read) -> ::std::io::Result<i8> {",
+        "    let mut buf = [0u8; 1];",
+        "    loop {",
+        "        match ::std::io::Read::read(reader, &mut buf) {",
+        "            Ok(0) => return Ok(0),",
+        "            Ok(_) => return Ok(buf[0] as i8),",
+        "            Err(ref err) if err.kind() == ::std::io::ErrorKind::Interrupted => (),",
+        "            Err(err) => return Err(err),",
+        "        }",
+        "    }",
+        "}",
+        "let mut run = || -> ::std::result::Result<(), ::bfi::BFIError> {",
+    ] {
+        push_line(&mut out, 1, line);
+    }
+    push_line(&mut out, 2, "let mut x = vec![0i8; 32767 + 1];");
+    push_line(&mut out, 2, "let mut p: usize = 0;");
+
+    let commands: Vec<char> = source_bytes(code.as_bytes())
+        .map(|(_, byte)| byte as char)
+        .filter(|c| "<>+-.,[]".contains(*c))
+        .collect();
+    let mut depth = 2;
+    let mut i = 0;
+    while i < commands.len() {
+        let c = commands[i];
+        let mut count = 1;
+        if "<>+-".contains(c) {
+            while i + count < commands.len() && commands[i + count] == c {
+                count += 1;
+            }
+        }
+
+        // A cell is an `i8`, so larger steps would be overflowing literals.
+        if c == '+' || c == '-' {
+            let method = if c == '+' { "checked_add" } else { "checked_sub" };
+            let mut rest = count;
+            while rest > 0 {
+                let step = rest.min(i8::MAX as usize);
+                let line = format!(
+                    "x[p] = x[p].{}({}).ok_or(::bfi::BFIError::ArithmeticOverflow)?;",
+                    method, step
+                );
+                push_line(&mut out, depth, &line);
+                rest -= step;
+            }
+            i += count;
+            continue;
+        }
+
+        let line = match c {
+            '>' => format!(
+                "p = p.checked_add({}).filter(|p| *p < x.len()).ok_or(::bfi::BFIError::OutOfMemory)?;",
+                count
+            ),
+            '<' => format!("p = p.checked_sub({}).ok_or(::bfi::BFIError::OutOfMemory)?;", count),
+            '.' => "::std::io::Write::write_all(writer, &[x[p] as u8])?;".to_string(),
+            ',' => "x[p] = read_byte(reader)?;".to_string(),
+            '[' => "while x[p] != 0 {".to_string(),
+            _ => {
+                depth -= 1;
+                "}".to_string()
+            }
+        };
+        push_line(&mut out, depth, &line);
+        if c == '[' {
+            depth += 1;
+        }
+        i += count;
+    }
+
+    push_line(&mut out, 2, "Ok(())");
+    for line in [
+        "};",
+        "let mut result = run();",
+        "if let Err(err) = ::std::io::Write::flush(writer) {",
+        "    result = result.and(Err(err.into()));",
+        "}",
+        "result",
+    ] {
+        push_line(&mut out, 1, line);
+    }
+    out.push_str("}\n");
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transpile::to_rust;
+    use crate::BFIError;
+
+    #[test]
+    fn test_to_rust() {
+        let rust = to_rust("+++[->>+<<]comment.", "adder").unwrap();
+        assert!(rust.contains("\npub fn adder(reader: &mut dyn ::std::io::Read,"));
+        assert!(rust.contains("\n        x[p] = x[p].checked_add(3)"));
+        assert!(rust.contains("\n        while x[p] != 0 {\n            x[p] = x[p].checked_sub(1)"));
+        assert!(rust.contains("\n            p = p.checked_add(2)"));
+        assert!(rust.contains("\n            p = p.checked_sub(2)"));
+        assert!(rust.contains("\n        }\n        ::std::io::Write::write_all"));
+        assert!(rust.contains("\n    let mut result = run();\n    if let Err(err) = ::std::io::Write::flush(writer)"));
+        assert!(!rust.contains("comment"));
+
+        assert!(matches!(to_rust("[", "f").unwrap_err(), BFIError::MissingClosingBrackets));
+    }
+
+    #[test]
+    fn test_to_rust_long_run() {
+        let rust = to_rust(&"-".repeat(300), "f").unwrap();
+        assert_eq!(rust.matches("checked_sub(127)").count(), 2);
+        assert_eq!(rust.matches("checked_sub(46)").count(), 1);
+        assert_eq!(rust.matches("checked_sub(").count(), 3);
+    }
+}